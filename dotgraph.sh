@@ -1,14 +1,24 @@
 #!/bin/bash
 DIR="generated"
 
-for filename in $DIR/*.dot; do
-  [ -f "$filename" ] || continue
-  echo $filename
-  dot -Tpdf "$filename" -o $DIR/$(basename "$filename" .dot).pdf
-done
+# Create the pdfs of a folder, and assemble them in out.pdf
+function graphs {
+  for filename in $1/*.dot; do
+    [ -f "$filename" ] || continue
+    echo $filename
+    dot -Tpdf "$filename" -o $1/$(basename "$filename" .dot).pdf
+  done
+
+  # Test if there is any pdf
+  if ls $1/*.pdf > /dev/null 2>&1; then
+    (cd $1 && pdfunite graph_*.pdf out.pdf)
+  fi
+}
 
-# Test if dir is empty or not
-if [ "$(ls -A $DIR)" ]; then
-  cd $DIR
-  pdfunite *.pdf out.pdf
-fi
+graphs $DIR
+
+# Files with several terms to run: one sub-folder per term
+for sub in $DIR/*/; do
+  [ -d "$sub" ] || continue
+  graphs ${sub%/}
+done
@@ -1,29 +1,378 @@
 use std::fmt;
+use std::collections::HashSet;
+
+
+/// Location of an item in a source file: byte offsets `[lo, hi)` in the file `file`.
+/// The file is identified by the number given to the parser, see `parser::TextParser::parse`.
+/// Items that do not come from a file (e.g. terms read back from a network) use `Span::default()`.
+#[derive(Copy,Clone,Debug,Default,PartialEq,Eq,Hash)]
+pub struct Span {
+    pub file:usize,
+    pub lo:usize,
+    pub hi:usize
+}
+
+impl Span {
+    /// Create a new span
+    pub fn new(file:usize, lo:usize, hi:usize) -> Span {
+        Span{file, lo, hi}
+    }
+
+    /// Check if a span is included in this one.
+    pub fn contains(self, other:Span) -> bool {
+        self.file == other.file && self.lo <= other.lo && other.hi <= self.hi
+    }
+
+    /// Smallest span covering two spans of the same file.
+    pub fn join(self, other:Span) -> Span {
+        Span{file:self.file, lo:self.lo.min(other.lo), hi:self.hi.max(other.hi)}
+    }
+}
+
+
 
 /// A lambda term.
 #[derive(Clone,Debug)]
 pub enum Term {
     // Symbol
-    Sym {vname:String},
+    Sym {vname:String, span:Span},
     // Abstraction
-    Lambda {vname:String, body:Box<Term>},
+    Lambda {vname:String, body:Box<Term>, span:Span},
     // Application
-    App {fun:Box<Term>, arg:Box<Term>},
+    App {fun:Box<Term>, arg:Box<Term>, span:Span},
+    // Numeric literal, expanded into a numeral during the conversion
+    Num {value:u64, span:Span},
+    // List literal, expanded into a list during the conversion
+    List {items:Vec<Box<Term>>, span:Span},
+    // String literal: a list of characters, each one being the numeral of its code point
+    Str {value:String, span:Span},
+    // Recursive definitions, visible in their own bodies and in the term.
+    // Not produced by the parser: built from the `rec` sentences during the conversion.
+    Rec {defs:Vec<(String, Box<Term>)>, body:Box<Term>, span:Span},
+    // Pattern matching on a data type, desugared into applications before the conversion
+    Case {scrutinee:Box<Term>, arms:Vec<Arm>, span:Span},
+    // Type annotation: '(term : type)'. Only used by the type inference, the conversion ignores it.
+    Annot {term:Box<Term>, ty:Type, span:Span},
+    // Operator expression 'a + b * c', as written: n operands and the n-1 operators between them.
+    // Resolved into applications once the declarations of the operators are known, see `infix::resolve`.
+    Infix {operands:Vec<Box<Term>>, operators:Vec<(String, Span)>, span:Span},
 }
 
 
-impl fmt::Display for Term {
+/// A type, as written in the annotations.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Type {
+    // Type variable
+    Var(String),
+    // Function type: 'a -> b'
+    Arrow(Box<Type>, Box<Type>),
+    // Unspecified type: '_'
+    Hole,
+}
+
+impl fmt::Display for Type {
     fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match self {
+            Type::Var(v) => write!(f, "{}", v),
+            Type::Hole => write!(f, "_"),
+            Type::Arrow(a, b) => match **a {
+                Type::Arrow(..) => write!(f, "({}) -> {}", a, b),
+                _ => write!(f, "{} -> {}", a, b)
+            }
+        }
+    }
+}
+
+
+/// An arm of a `case`: `Cons h t -> body`.
+/// The constructor `_` matches the constructors without an arm, and has no binder.
+#[derive(Clone,Debug)]
+pub struct Arm {
+    pub ctor:String,
+    pub binders:Vec<(String, Span)>,
+    pub body:Box<Term>,
+    pub span:Span
+}
+
+
+impl Term {
+    /// Get the location of the term.
+    pub fn span(&self) -> Span {
         use self::Term::*;
         match self {
-            Sym{vname}          => write!(f, "{}", vname),
-            Lambda{vname, body} => write!(f, "({}->{})", vname, *body),
-            App{fun, arg}       => write!(f, "({} {})", *fun, *arg)
+            Sym{span, ..} | Lambda{span, ..} | App{span, ..} | Num{span, ..} | List{span, ..} | Str{span, ..}
+            | Rec{span, ..} | Case{span, ..} | Annot{span, ..} | Infix{span, ..} => *span
         }
     }
 }
 
 
+/// Processing function used by the parser: read an application of variables `a b c` as a list of binders.
+/// A binder can be annotated with its type: `(x : T)`.
+/// Return false if the term contains something else (e.g. a qualified name or an abstraction).
+pub fn as_binders(term:&Term, binders:&mut Vec<((String, Span), Option<Type>)>) -> bool {
+    match term {
+        Term::Sym{vname, span} if !vname.contains("::") => {
+            binders.push(((vname.clone(), *span), None));
+            true
+        }
+        Term::Annot{term, ty, ..} => match **term {
+            Term::Sym{ref vname, span} if !vname.contains("::") => {
+                binders.push(((vname.clone(), span), Some(ty.clone())));
+                true
+            }
+            _ => false
+        },
+        Term::App{fun, arg, ..} => match **arg {
+            Term::Sym{..} | Term::Annot{..} => as_binders(fun, binders) && as_binders(arg, binders),
+            _ => false
+        },
+        _ => false
+    }
+}
+
+/// Processing function used by the parser: nest abstractions over `body`, the first binder being the outermost one.
+/// Each abstraction spans from its binder to `hi`.
+pub fn abstract_over(binders:Vec<(String, Span)>, body:Box<Term>, hi:usize) -> Box<Term> {
+    binders.into_iter().rev().fold(body, |acc, (vname, span)|
+        Box::new(Term::Lambda{vname, body:acc, span:Span::new(span.file, span.lo, hi)})
+    )
+}
+
+/// Processing function used by the parser: like `abstract_over`, with optional type annotations on the binders.
+/// The abstraction of a binder `(x : T)` is annotated with the type `T -> _`.
+pub fn abstract_over_annotated(binders:Vec<((String, Span), Option<Type>)>, body:Box<Term>, hi:usize) -> Box<Term> {
+    binders.into_iter().rev().fold(body, |acc, ((vname, span), ty)| {
+        let span = Span::new(span.file, span.lo, hi);
+        let lambda = Box::new(Term::Lambda{vname, body:acc, span});
+        match ty {
+            None => lambda,
+            Some(ty) => Box::new(Term::Annot{term:lambda, ty:Type::Arrow(Box::new(ty), Box::new(Type::Hole)), span})
+        }
+    })
+}
+
+
+/// Precedence levels of the terms, following the grammar (see `parser.lalrpop`).
+/// A term is parenthesized when printed at a higher level than its own.
+const PREC_LAMBDA:u8 = 0;   // Abstraction (and recursive definitions, case): extends as far as possible
+const PREC_INFIX:u8 = 1;    // Operator expression, before its resolution
+const PREC_APP:u8 = 2;      // Application: left associative
+const PREC_ATOM:u8 = 3;     // Variable and literals
+
+impl Term {
+    /// Precedence level of the term.
+    fn prec(&self) -> u8 {
+        use self::Term::*;
+        match self {
+            Lambda{..} | Rec{..} | Case{..} => PREC_LAMBDA,
+            Annot{..} if self.annotated_binder().is_some() => PREC_LAMBDA,
+            Annot{..} => PREC_ATOM,
+            Infix{..} => PREC_INFIX,
+            App{..} => PREC_APP,
+            Sym{..} | Num{..} | List{..} | Str{..} => PREC_ATOM
+        }
+    }
+
+    /// Print the term at the precedence level `prec`, only adding the required parenthesis.
+    fn fmt_prec(&self, f:&mut fmt::Formatter, prec:u8)->fmt::Result{
+        use self::Term::*;
+        if self.prec() < prec {
+            write!(f, "(")?;
+            self.fmt_prec(f, PREC_LAMBDA)?;
+            return write!(f, ")");
+        }
+        match self {
+            Sym{vname, ..}          => write!(f, "{}", vname),
+            Num{value, ..}          => write!(f, "{}", value),
+            Str{value, ..}          => write!(f, "\"{}\"", escape(value)),
+            List{items, ..}         => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    item.fmt_prec(f, PREC_LAMBDA)?;
+                }
+                write!(f, "]")
+            }
+            Lambda{vname, body, ..} => {
+                write!(f, "{}->", vname)?;
+                body.fmt_prec(f, PREC_LAMBDA)
+            }
+            App{fun, arg, ..}       => {
+                fun.fmt_prec(f, PREC_APP)?;
+                write!(f, " ")?;
+                arg.fmt_prec(f, PREC_ATOM)
+            }
+            Infix{operands, operators, ..} => {
+                operands[0].fmt_prec(f, PREC_APP)?;
+                for ((op, _), operand) in operators.iter().zip(&operands[1..]) {
+                    write!(f, " {} ", op)?;
+                    operand.fmt_prec(f, PREC_APP)?;
+                }
+                Ok(())
+            }
+            Rec{defs, body, ..}     => {
+                write!(f, "rec ")?;
+                for (i, (vname, def)) in defs.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "{} = ", vname)?;
+                    def.fmt_prec(f, PREC_LAMBDA)?;
+                }
+                write!(f, ". ")?;
+                body.fmt_prec(f, PREC_LAMBDA)
+            }
+            Annot{term, ty, ..}     => match self.annotated_binder() {
+                // '(x : T) -> body'
+                Some((vname, ty, body)) => {
+                    write!(f, "({} : {}) -> ", vname, ty)?;
+                    body.fmt_prec(f, PREC_LAMBDA)
+                }
+                None => {
+                    write!(f, "(")?;
+                    term.fmt_prec(f, PREC_LAMBDA)?;
+                    write!(f, " : {})", ty)
+                }
+            }
+            Case{scrutinee, arms, ..} => {
+                write!(f, "case ")?;
+                scrutinee.fmt_prec(f, PREC_LAMBDA)?;
+                write!(f, " of")?;
+                for (i, arm) in arms.iter().enumerate() {
+                    if i > 0 { write!(f, " |")?; }
+                    write!(f, " {}", arm.ctor)?;
+                    for (vname, _) in &arm.binders { write!(f, " {}", vname)?; }
+                    write!(f, " -> ")?;
+                    arm.body.fmt_prec(f, PREC_INFIX)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+
+    /// Get the annotated binder of an abstraction `(x : T) -> body`, see `abstract_over_annotated`.
+    fn annotated_binder(&self) -> Option<(&str, &Type, &Term)> {
+        match self {
+            Term::Annot{term, ty:Type::Arrow(ty, res), ..} if **res == Type::Hole => match &**term {
+                Term::Lambda{vname, body, ..} => Some((vname, ty, body)),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+
+    /// Canonical renaming of the bound variables.
+    /// A binder is named after its depth (number of enclosing binders): `a`, `b`, ..., `z`, `a1`, `b1`, ...
+    /// Unused binders are named `_`. The names of the free variables are kept, and never used for a binder.
+    /// Hence, two alpha-equivalent terms are printed identically.
+    pub fn canonical(&self) -> Term {
+        let free = self.free_vars();
+        self.canonical_(&mut vec![], &free)
+    }
+
+    /// `env` maps the enclosing binders to their new names, the last one being the innermost.
+    fn canonical_(&self, env:&mut Vec<(String, String)>, free:&HashSet<String>) -> Term {
+        use self::Term::*;
+        match self {
+            Sym{vname, span} => {
+                let vname = match env.iter().rev().find(|(old, _)| old == vname) {
+                    Some((_, new)) => new.clone(),
+                    None => vname.clone()
+                };
+                Sym{vname, span:*span}
+            }
+            Lambda{vname, body, span} => {
+                let new = if body.occurs_free(vname) { canonical_name(env.len(), free) } else { String::from("_") };
+                env.push((vname.clone(), new.clone()));
+                let body = Box::new(body.canonical_(env, free));
+                env.pop();
+                Lambda{vname:new, body, span:*span}
+            }
+            App{fun, arg, span} => App{
+                fun:Box::new(fun.canonical_(env, free)),
+                arg:Box::new(arg.canonical_(env, free)),
+                span:*span
+            },
+            Num{..} | Str{..} => self.clone(),
+            List{items, span} => List{items:items.iter().map(|item| Box::new(item.canonical_(env, free))).collect(), span:*span},
+            Rec{defs, body, span} => {
+                let depth = env.len();
+                for (vname, _) in defs {
+                    let new = canonical_name(env.len(), free);
+                    env.push((vname.clone(), new));
+                }
+                let names:Vec<String> = env[depth..].iter().map(|(_, new)| new.clone()).collect();
+                let defs = defs.iter().zip(names)
+                    .map(|((_, def), new)| (new, Box::new(def.canonical_(env, free))))
+                    .collect();
+                let body = Box::new(body.canonical_(env, free));
+                env.truncate(depth);
+                Rec{defs, body, span:*span}
+            }
+            Annot{term, ty, span} => Annot{term:Box::new(term.canonical_(env, free)), ty:ty.clone(), span:*span},
+            Infix{operands, operators, span} => Infix{
+                operands:operands.iter().map(|operand| Box::new(operand.canonical_(env, free))).collect(),
+                operators:operators.clone(),
+                span:*span
+            },
+            Case{scrutinee, arms, span} => {
+                let scrutinee = Box::new(scrutinee.canonical_(env, free));
+                let arms = arms.iter().map(|arm| {
+                    let depth = env.len();
+                    let mut binders = vec![];
+                    for (vname, bspan) in &arm.binders {
+                        // Binders on the right shadow the ones on the left
+                        let shadowed = arm.binders.iter().skip(binders.len()+1).any(|(n, _)| n == vname);
+                        let used = !shadowed && arm.body.occurs_free(vname);
+                        let new = if used { canonical_name(env.len(), free) } else { String::from("_") };
+                        env.push((vname.clone(), new.clone()));
+                        binders.push((new, *bspan));
+                    }
+                    let body = Box::new(arm.body.canonical_(env, free));
+                    env.truncate(depth);
+                    Arm{ctor:arm.ctor.clone(), binders, body, span:arm.span}
+                }).collect();
+                Case{scrutinee, arms, span:*span}
+            }
+        }
+    }
+}
+
+
+/// Name of the binder at depth `depth` for the canonical renaming, avoiding the free variables.
+fn canonical_name(depth:usize, free:&HashSet<String>) -> String {
+    (0..)
+        .map(|i| {
+            let letter = (b'a' + (i % 26) as u8) as char;
+            if i < 26 { letter.to_string() } else { format!("{}{}", letter, i / 26) }
+        })
+        .filter(|n| !free.contains(n))
+        .nth(depth)
+        .unwrap()
+}
+
+
+/// Escape the double quotes, backslashes, new lines and tabulations of a string literal.
+fn escape(value:&str) -> String {
+    value.chars().map(|c| match c {
+        '"' => String::from("\\\""),
+        '\\' => String::from("\\\\"),
+        '\n' => String::from("\\n"),
+        '\t' => String::from("\\t"),
+        c => c.to_string()
+    }).collect()
+}
+
+
+/// Print the term with the minimal amount of parenthesis.
+impl fmt::Display for Term {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        self.fmt_prec(f, PREC_LAMBDA)
+    }
+}
+
+
 
 
 
@@ -35,7 +384,34 @@ pub enum Sentence {
     // Execution
     Run(SRun),
     // Import
-    Read(SRead)
+    Read(SRead),
+    // Recursive definitions
+    Rec(SRec),
+    // Data type
+    Data(SData),
+    // Operator declaration
+    Infix(SInfix),
+    // Option of the file
+    Pragma(SPragma),
+    // Assertion
+    Assert(SAssert)
+}
+
+impl Sentence {
+    /// Get the location of the sentence.
+    pub fn span(&self) -> Span {
+        use self::Sentence::*;
+        match self {
+            Let(s) => s.span,
+            Run(s) => s.span,
+            Read(s) => s.span,
+            Rec(s) => s.span,
+            Data(s) => s.span,
+            Infix(s) => s.span,
+            Pragma(s) => s.span,
+            Assert(s) => s.span
+        }
+    }
 }
 
 
@@ -45,7 +421,12 @@ impl fmt::Display for Sentence {
         match self {
             Let(s) => s.fmt(f),
             Run(s) => s.fmt(f),
-            Read(s) => s.fmt(f)
+            Read(s) => s.fmt(f),
+            Rec(s) => s.fmt(f),
+            Data(s) => s.fmt(f),
+            Infix(s) => s.fmt(f),
+            Pragma(s) => s.fmt(f),
+            Assert(s) => s.fmt(f)
         }
     }
 }
@@ -56,7 +437,8 @@ impl fmt::Display for Sentence {
 #[derive(Clone,Debug)]
 pub struct SLet {
     pub vname:String,
-    pub body:Box<Term>
+    pub body:Box<Term>,
+    pub span:Span
 }
 
 impl fmt::Display for SLet {
@@ -68,16 +450,118 @@ impl fmt::Display for SLet {
 
 
 
-/// A top-level "sentence" term, to be reduced.
+/// A top-level group of (mutually) recursive definitions.
+/// Each definition is visible in all the bodies of the group.
+#[derive(Clone,Debug)]
+pub struct SRec {
+    pub defs:Vec<SLet>,
+    pub span:Span
+}
+
+impl fmt::Display for SRec {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        let defs:Vec<String> = self.defs.iter().map(|d| format!("{} = {}", d.vname, d.body)).collect();
+        write!(f, "rec {}.", defs.join(", "))
+    }
+}
+
+
+
+
+/// A top-level algebraic data type, Scott encoded: each constructor is a definition.
+#[derive(Clone,Debug)]
+pub struct SData {
+    pub name:String,
+    pub ctors:Vec<Ctor>,
+    pub span:Span
+}
+
+/// A constructor of a data type, with the names of its fields.
+#[derive(Clone,Debug)]
+pub struct Ctor {
+    pub name:String,
+    pub fields:Vec<String>,
+    pub span:Span
+}
+
+impl fmt::Display for SData {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        let ctors:Vec<String> = self.ctors.iter()
+            .map(|c| std::iter::once(&c.name).chain(&c.fields).cloned().collect::<Vec<_>>().join(" "))
+            .collect();
+        write!(f, "data {} = {}.", self.name, ctors.join(" | "))
+    }
+}
+
+
+
+
+/// A top-level operator declaration: `infixl 6 + = plus.`
+/// `a + b` stands for `plus a b`, see `infix::resolve`.
+#[derive(Clone,Debug)]
+pub struct SInfix {
+    pub assoc:Assoc,
+    pub prec:u64,
+    pub op:String,
+    pub vname:String,
+    pub span:Span
+}
+
+/// Associativity of an operator.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum Assoc {
+    // 'infixl': 'a + b + c' is '(a + b) + c'
+    Left,
+    // 'infixr': 'a + b + c' is 'a + (b + c)'
+    Right,
+    // 'infix': 'a + b + c' is an error
+    Non
+}
+
+impl fmt::Display for SInfix {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        let kw = match self.assoc {
+            Assoc::Left => "infixl",
+            Assoc::Right => "infixr",
+            Assoc::Non => "infix"
+        };
+        write!(f, "{} {} {} = {}.", kw, self.prec, self.op, self.vname)
+    }
+}
+
+
+
+
+/// A top-level "sentence" term, to be reduced. It can be named to identify its result.
 #[derive(Clone,Debug)]
 pub struct SRun {
-    pub term:Box<Term>
+    pub name:Option<String>,
+    pub term:Box<Term>,
+    pub span:Span
 }
 
 
 impl fmt::Display for SRun {
     fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
-        write!(f, "{}.", self.term)
+        match &self.name {
+            None => write!(f, "{}.", self.term),
+            Some(n) => write!(f, "{}: {}.", n, self.term)
+        }
+    }
+}
+
+
+/// A top-level assertion: both sides are reduced, and their normal forms must be alpha-equivalent.
+#[derive(Clone,Debug)]
+pub struct SAssert {
+    pub lhs:Box<Term>,
+    pub rhs:Box<Term>,
+    pub span:Span
+}
+
+impl fmt::Display for SAssert {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        write!(f, "assert {} == {}.", self.lhs, self.rhs)
     }
 }
 
@@ -86,7 +570,8 @@ impl fmt::Display for SRun {
 #[derive(Clone,Debug)]
 pub struct SRead {
     pub path:String,
-    pub name:Option<String>
+    pub name:Option<String>,
+    pub span:Span
 }
 
 impl fmt::Display for SRead {
@@ -99,72 +584,20 @@ impl fmt::Display for SRead {
 }
 
 
-/// Processing function used by the parser
-pub fn read_process(mut rpath: String)->String{
-    // Remove the 'read' part
-    rpath.drain(0..4);
-    // Remove the whitespaces
-    rpath.trim().to_string()
-}
-
-
-
-
-
-// 
-// 
-// #[allow(non_camel_case_types)]
-// #[derive(Clone,Debug)]
-// pub enum Tok<'input> {
-//     // Decorated
-//     Identifier(&'input str),
-//     // Keywords
-//     KWlet, KWrun,
-//     // Other
-//     lpar, rpar,
-//     comma, dot,
-//     exclam, arrow, equal,
-// }
-// 
-// 
-// impl<'input> fmt::Display for Tok<'input> {
-//     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-//         use self::Tok::*;
-//         let s = match *self {
-//             Identifier(_) => "Identifier",
-//             KWlet => "KWlet",
-//             KWrun => "KWrun,",
-//             // Other
-//             lpar => "lpar",
-//             rpar => "rpar",
-//             comma => "comma",
-//             dot => "dot",
-//             exclam => "exclam",
-//             arrow => "arrow",
-//             equal => "equal",
-//         };
-//         s.fmt(f)
-//     }
-// }
-// 
-// 
-// pub enum LexicalError {
-//     /* */
-// }
-// 
-// 
-// use std::str::CharIndices;
-// 
-// pub struct Lexer<'input> {
-//     chars: CharIndices<'input>,
-// }
-// 
-// impl<'input> Lexer<'input> {
-//     pub fn new(input: &'input str) -> Self {
-//         Lexer { chars: input.char_indices() }
-//     }
-// }
-// 
-// 
-// 
-// 
+
+/// A top level pragma: `#pragma name value`, setting an option of the file (the value is optional).
+#[derive(Clone,Debug)]
+pub struct SPragma {
+    pub name:String,
+    pub value:Option<String>,
+    pub span:Span
+}
+
+impl fmt::Display for SPragma {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match &self.value {
+            None => write!(f, "#pragma {}", self.name),
+            Some(v) => write!(f, "#pragma {} {}", self.name, v)
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! # Binary Lambda Calculus
+//! Read and write terms in John Tromp's binary lambda calculus (BLC).
+//!
+//! BLC encodes the de Bruijn form of a closed term:
+//!   * an abstraction `λM` is `00` followed by `M`;
+//!   * an application `M N` is `01` followed by `M` and `N`;
+//!   * the variable of de Bruijn index `n` (starting at 1 for the closest binder) is `1` repeated `n` times, followed by `0`.
+//!
+//! For example, the identity is `0010` and `λx.λy.x` is `0000110`.
+//! Terms come in two forms:
+//!   * bits: a text of `0` and `1`, whitespace being ignored (`.blc` files);
+//!   * bytes: the bits packed in bytes, most significant bit first (`.Blc` files).
+//!     The unused bits of the last byte are padding, and are written as `0`.
+//!     As the bytes are not a text, they are shown in the bits form (see `bytes_as_bits`).
+
+use crate::ast::{Span, Term, Term::*};
+
+use std::fmt;
+
+
+/// Errors while reading or writing BLC, located by their offsets `[lo, hi)`:
+/// characters of the bits form, characters of its rendering (see `bytes_as_bits`) for the bytes form,
+/// and the span of the faulty term when writing.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct BlcError {
+    pub kind:BlcErrorKind,
+    pub lo:usize,
+    pub hi:usize
+}
+
+/// Kinds of BLC errors
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum BlcErrorKind {
+    /// A character other than `0`, `1` or a whitespace in the bits form.
+    UnexpectedChar(char),
+    /// The input ends in the middle of a term.
+    UnexpectedEnd,
+    /// Data after the term.
+    TrailingData,
+    /// A de Bruijn index greater than the number of enclosing abstractions: BLC terms are closed.
+    UnboundIndex(usize),
+    /// Writing a term with a free variable.
+    FreeVariable(String),
+    /// Writing a term with a literal, recursive definitions, a case or operators, which have no BLC equivalent.
+    Unsupported,
+}
+
+impl fmt::Display for BlcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::BlcErrorKind::*;
+        match self.kind {
+            UnexpectedChar(c) => write!(f, "unexpected character `{}` in binary lambda calculus", c),
+            UnexpectedEnd => write!(f, "unexpected end of binary lambda calculus term"),
+            TrailingData => write!(f, "unexpected data after the binary lambda calculus term"),
+            UnboundIndex(i) => write!(f, "de Bruijn index {} is not bound", i),
+            FreeVariable(ref name) => write!(f, "free variable `{}` cannot be written in binary lambda calculus", name),
+            Unsupported => write!(f, "literals, recursive definitions and case expressions cannot be written in binary lambda calculus"),
+        }
+    }
+}
+
+
+/// Extension of the files in the bits form.
+pub const BITS_EXTENSION:&str = "blc";
+
+/// Extension of the files in the bytes form.
+pub const BYTES_EXTENSION:&str = "Blc";
+
+
+/// A bit of the input, with its offset.
+type Bit = Result<(usize, bool), BlcError>;
+
+/// Number of characters of a byte rendered by `bytes_as_bits`, separator included.
+const BYTE_WIDTH:usize = 9;
+
+
+
+// --- --- --- Reading
+
+/// Read a term in the bits form, from the file `file` (used for the spans).
+pub fn from_bits(file:usize, text:&str) -> Result<Box<Term>, BlcError> {
+    let mut bits = text.char_indices()
+        .filter(|(_, c)| !c.is_whitespace())
+        .map(|(i, c)| match c {
+            '0' => Ok((i, false)),
+            '1' => Ok((i, true)),
+            c => Err(BlcError{kind:BlcErrorKind::UnexpectedChar(c), lo:i, hi:i + c.len_utf8()})
+        });
+    let term = read(&mut bits, 0, file, text.len())?;
+    match bits.next() {
+        None => Ok(Box::new(term.canonical())),
+        Some(Err(e)) => Err(e),
+        Some(Ok((i, _))) => Err(BlcError{kind:BlcErrorKind::TrailingData, lo:i, hi:text.len()})
+    }
+}
+
+/// Read a term in the bytes form, from the file `file` (used for the spans).
+/// The spans are offsets in the rendering of the bytes given by `bytes_as_bits`.
+pub fn from_bytes(file:usize, bytes:&[u8]) -> Result<Box<Term>, BlcError> {
+    let mut bits = bytes.iter().enumerate()
+        .flat_map(|(i, b)| (0..8).map(move |k| Ok((BYTE_WIDTH*i + k, (b >> (7 - k)) & 1 == 1))));
+    // Before the newline ending the rendering
+    let end = (BYTE_WIDTH*bytes.len()).saturating_sub(1);
+    let term = read(&mut bits, 0, file, end)?;
+    // The remaining bits of the last byte are padding
+    let next_byte = term.span().hi / BYTE_WIDTH + 1;
+    if next_byte < bytes.len() {
+        return Err(BlcError{kind:BlcErrorKind::TrailingData, lo:BYTE_WIDTH*next_byte, hi:end});
+    }
+    Ok(Box::new(term.canonical()))
+}
+
+/// Render bytes as a text in the bits form, to show the source of the bytes form:
+/// each byte is written as 8 bits followed by a space, or by a newline every 8 bytes and after the last one.
+pub fn bytes_as_bits(bytes:&[u8]) -> String {
+    let mut res = String::with_capacity(BYTE_WIDTH*bytes.len());
+    for (i, b) in bytes.iter().enumerate() {
+        res.push_str(&format!("{:08b}", b));
+        res.push(if i % 8 == 7 || i + 1 == bytes.len() { '\n' } else { ' ' });
+    }
+    res
+}
+
+/// Get the next bit, `end` being the offset of the end of the input.
+fn next<I:Iterator<Item=Bit>>(bits:&mut I, end:usize) -> Result<(usize, bool), BlcError> {
+    bits.next().unwrap_or(Err(BlcError{kind:BlcErrorKind::UnexpectedEnd, lo:end, hi:end}))
+}
+
+/// Read a term under `depth` abstractions.
+/// The binder at depth `d` is named `v<d>`: the terms are renamed canonically afterward.
+fn read<I:Iterator<Item=Bit>>(bits:&mut I, depth:usize, file:usize, end:usize) -> Result<Term, BlcError> {
+    let (lo, b0) = next(bits, end)?;
+    if b0 {
+        // Variable: count the 1s
+        let mut index = 1;
+        let hi = loop {
+            match next(bits, end)? {
+                (_, true) => index += 1,
+                (i, false) => break i+1
+            }
+        };
+        if index > depth {
+            return Err(BlcError{kind:BlcErrorKind::UnboundIndex(index), lo, hi});
+        }
+        Ok(Sym{vname:format!("v{}", depth - index), span:Span::new(file, lo, hi)})
+    } else if next(bits, end)?.1 {
+        // Application
+        let fun = read(bits, depth, file, end)?;
+        let arg = read(bits, depth, file, end)?;
+        let span = Span::new(file, lo, arg.span().hi);
+        Ok(App{fun:Box::new(fun), arg:Box::new(arg), span})
+    } else {
+        // Abstraction
+        let body = read(bits, depth+1, file, end)?;
+        let span = Span::new(file, lo, body.span().hi);
+        Ok(Lambda{vname:format!("v{}", depth), body:Box::new(body), span})
+    }
+}
+
+
+
+// --- --- --- Writing
+
+/// Write a closed term in the bits form.
+pub fn to_bits(term:&Term) -> Result<String, BlcError> {
+    let mut res = String::new();
+    write(term, &mut vec![], &mut res)?;
+    Ok(res)
+}
+
+/// Write a closed term in the bytes form.
+pub fn to_bytes(term:&Term) -> Result<Vec<u8>, BlcError> {
+    let bits = to_bits(term)?;
+    Ok(bits.as_bytes()
+        .chunks(8)
+        .map(|chunk| chunk.iter().enumerate().fold(0u8, |acc, (k, b)| acc | ((b - b'0') << (7 - k))))
+        .collect())
+}
+
+/// Write a term, `env` being the enclosing binders (the last one being the closest).
+fn write(term:&Term, env:&mut Vec<String>, res:&mut String) -> Result<(), BlcError> {
+    match term {
+        Sym{vname, span} => match env.iter().rev().position(|n| n == vname) {
+            Some(i) => {
+                res.push_str(&"1".repeat(i+1));
+                res.push('0');
+                Ok(())
+            }
+            None => Err(BlcError{kind:BlcErrorKind::FreeVariable(vname.clone()), lo:span.lo, hi:span.hi})
+        },
+        Lambda{vname, body, ..} => {
+            res.push_str("00");
+            env.push(vname.clone());
+            let r = write(body, env, res);
+            env.pop();
+            r
+        }
+        App{fun, arg, ..} => {
+            res.push_str("01");
+            write(fun, env, res)?;
+            write(arg, env, res)
+        }
+        // Annotations are erased
+        Annot{term, ..} => write(term, env, res),
+        Num{span, ..} | List{span, ..} | Str{span, ..} | Rec{span, ..} | Case{span, ..} | Infix{span, ..} => Err(BlcError{kind:BlcErrorKind::Unsupported, lo:span.lo, hi:span.hi})
+    }
+}
@@ -0,0 +1,135 @@
+//! # Infix operators
+//! Operators are declared by a sentence giving their associativity, their precedence and the definition they stand for:
+//! ```text
+//! infixl 6 + = plus.
+//! infixl 7 * = times.
+//! infixr 5 ++ = append.
+//! ```
+//! `a + b` then stands for `plus a b`.
+//! Operators bind less tightly than the application: `f a + g b` is `plus (f a) (g b)`.
+//! Between operators, the highest precedence binds first: `a + b * c` is `plus a (times b c)`.
+//! Operators of the same precedence are grouped according to their associativity:
+//!   * `infixl`: to the left, `a + b + c` is `plus (plus a b) c`;
+//!   * `infixr`: to the right, `a ++ b ++ c` is `append a (append b c)`;
+//!   * `infix`: not at all, `a == b == c` is an error.
+//!
+//! Mixing operators of the same precedence but of different associativities is an error too.
+//!
+//! The parser reads an operator expression as a flat sequence of operands and operators (`Term::Infix`):
+//! the declarations can follow the expression, or come from another file.
+//! `resolve` nests the operands once all the declarations are known.
+
+use crate::ast::{*, Term::*};
+
+use std::collections::HashMap;
+use std::fmt;
+
+
+/// Declarations of the operators, by operator.
+pub type Fixities = HashMap<String, SInfix>;
+
+
+/// Errors while resolving the operators, located by the span of the faulty operator.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct InfixError {
+    pub kind:InfixErrorKind,
+    pub span:Span
+}
+
+/// Kinds of operator errors
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum InfixErrorKind {
+    /// An operator without declaration.
+    Unknown(String),
+    /// Two operators of the same precedence follow each other, without associativity to group them.
+    Ambiguous(String, String),
+}
+
+impl fmt::Display for InfixError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::InfixErrorKind::*;
+        match self.kind {
+            Unknown(ref op) => write!(f, "unknown operator `{}`", op),
+            Ambiguous(ref left, ref right) =>
+                write!(f, "`{}` and `{}` have the same precedence, and cannot be grouped without parenthesis", left, right),
+        }
+    }
+}
+
+
+/// Resolve the operator expressions of a term according to `fixities`.
+/// Each operator is replaced by the application of its definition, located at the operator.
+pub fn resolve(term:&Term, fixities:&Fixities) -> Result<Box<Term>, InfixError> {
+    Ok(Box::new(match term {
+        Sym{..} | Num{..} | Str{..} => term.clone(),
+        Lambda{vname, body, span} => Lambda{vname:vname.clone(), body:resolve(body, fixities)?, span:*span},
+        App{fun, arg, span} => App{fun:resolve(fun, fixities)?, arg:resolve(arg, fixities)?, span:*span},
+        List{items, span} => List{
+            items:items.iter().map(|item| resolve(item, fixities)).collect::<Result<_, _>>()?,
+            span:*span
+        },
+        Rec{defs, body, span} => Rec{
+            defs:defs.iter().map(|(vname, def)| Ok((vname.clone(), resolve(def, fixities)?))).collect::<Result<_, _>>()?,
+            body:resolve(body, fixities)?,
+            span:*span
+        },
+        Case{scrutinee, arms, span} => Case{
+            scrutinee:resolve(scrutinee, fixities)?,
+            arms:arms.iter()
+                .map(|arm| Ok(Arm{body:resolve(&arm.body, fixities)?, ..arm.clone()}))
+                .collect::<Result<_, _>>()?,
+            span:*span
+        },
+        Annot{term, ty, span} => Annot{term:resolve(term, fixities)?, ty:ty.clone(), span:*span},
+        Infix{operands, operators, ..} => {
+            let operands = operands.iter().map(|operand| resolve(operand, fixities).map(|t| *t)).collect::<Result<Vec<_>, _>>()?;
+            return nest(operands, operators, fixities).map(Box::new);
+        }
+    }))
+}
+
+
+/// Nest the operands of an operator expression, with an operator-precedence parser.
+/// The pending operators form a stack of strictly increasing precedences
+/// (or equal precedences, for right associative operators).
+fn nest(operands:Vec<Term>, operators:&[(String, Span)], fixities:&Fixities) -> Result<Term, InfixError> {
+    let mut operands = operands.into_iter();
+    let mut terms = vec![operands.next().unwrap()];
+    let mut pending:Vec<(&SInfix, Span)> = vec![];
+
+    for ((op, span), operand) in operators.iter().zip(operands) {
+        let fixity = fixities.get(op)
+            .ok_or_else(|| InfixError{kind:InfixErrorKind::Unknown(op.clone()), span:*span})?;
+        while let Some((top, top_span)) = pending.last() {
+            if top.prec < fixity.prec { break; }
+            if top.prec == fixity.prec {
+                match (top.assoc, fixity.assoc) {
+                    (Assoc::Left, Assoc::Left) => {}
+                    (Assoc::Right, Assoc::Right) => break,
+                    _ => return Err(InfixError{
+                        kind:InfixErrorKind::Ambiguous(top.op.clone(), op.clone()),
+                        span:top_span.join(*span)
+                    })
+                }
+            }
+            apply(&mut terms, pending.pop().unwrap());
+        }
+        pending.push((fixity, *span));
+        terms.push(operand);
+    }
+
+    while let Some(top) = pending.pop() {
+        apply(&mut terms, top);
+    }
+    Ok(terms.pop().unwrap())
+}
+
+/// Replace the two last terms `a` and `b` by the application of the definition of the operator: `vname a b`.
+fn apply(terms:&mut Vec<Term>, (fixity, span):(&SInfix, Span)) {
+    let b = terms.pop().unwrap();
+    let a = terms.pop().unwrap();
+    let outer = a.span().join(b.span());
+    let fun = Box::new(Sym{vname:fixity.vname.clone(), span});
+    let fun = Box::new(App{fun, arg:Box::new(a), span:outer});
+    terms.push(App{fun, arg:Box::new(b), span:outer});
+}
@@ -0,0 +1,418 @@
+//! # Lexer
+//! Hand-written lexer feeding the LALRPOP parser.
+//!
+//! Comments are whitespace: they can appear anywhere between two tokens, including inside terms.
+//!   * Line comments start with `//` and end with the line.
+//!   * Block comments are enclosed in `/*` and `*/`, and can be nested.
+//!
+//! `_` alone is the wildcard binder, not an identifier (but `_x` is an identifier).
+//! Abstractions can also be written with `\` or `λ`.
+//!
+//! Numeric literals are sequences of decimal digits, fitting in 64 bits.
+//!
+//! String literals are enclosed in double quotes, on a single line.
+//! The escape sequences are `\"`, `\\`, `\n` and `\t`.
+//!
+//! The path following the `read` keyword is lexed as a single token, up to the next whitespace or comment.
+//!
+//! A pragma `#pragma name value` is lexed as a single token, up to the end of the line or the start of a comment.
+//! The token holds the text following `#pragma`: the parser splits it into the name and the value.
+//!
+//! Operators are sequences of the symbols `+-*/<>=!&^%$~?@|:`, such as `+` or `<=`.
+//! `=`, `==`, `|`, `->`, `:` and `::` are reserved, and an operator stops before the start of a comment.
+
+use std::fmt;
+use std::str::CharIndices;
+use std::iter::Peekable;
+
+
+/// A token, borrowing its text from the input.
+#[allow(non_camel_case_types)]
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum Tok<'input> {
+    // Decorated
+    Identifier(&'input str),
+    Path(&'input str),
+    Number(u64),
+    Str(&'input str),
+    Operator(&'input str),
+    Pragma(&'input str),
+    // Keywords
+    KWread, KWas, KWwith, KWrec, KWdata, KWcase, KWof,
+    KWinfixl, KWinfixr, KWinfix, KWassert,
+    // Other
+    lpar, rpar, lbracket, rbracket,
+    comma, dot,
+    arrow, equal, equals, bar,
+    colons, colon,
+    underscore, backslash, lambda,
+}
+
+
+impl<'input> fmt::Display for Tok<'input> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Tok::*;
+        let s = match *self {
+            Identifier(s) => s,
+            Path(s) => s,
+            Number(n) => return n.fmt(f),
+            Str(s) => return write!(f, "\"{}\"", s),
+            Operator(s) => s,
+            Pragma(s) => return write!(f, "#pragma {}", s),
+            // Keywords
+            KWread => "read",
+            KWas => "as",
+            KWwith => "with",
+            KWrec => "rec",
+            KWdata => "data",
+            KWcase => "case",
+            KWof => "of",
+            KWinfixl => "infixl",
+            KWinfixr => "infixr",
+            KWinfix => "infix",
+            KWassert => "assert",
+            // Other
+            lpar => "(",
+            rpar => ")",
+            lbracket => "[",
+            rbracket => "]",
+            comma => ",",
+            dot => ".",
+            arrow => "->",
+            equal => "=",
+            equals => "==",
+            bar => "|",
+            colons => "::",
+            colon => ":",
+            underscore => "_",
+            backslash => "\\",
+            lambda => "λ",
+        };
+        s.fmt(f)
+    }
+}
+
+
+/// Errors of the lexer, located by their byte offsets `[lo, hi)`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct LexicalError {
+    pub kind:LexicalErrorKind,
+    pub lo:usize,
+    pub hi:usize
+}
+
+/// Kinds of lexical errors
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum LexicalErrorKind {
+    /// A character that cannot start a token.
+    UnexpectedChar(char),
+    /// A block comment not closed before the end of the input.
+    UnterminatedComment,
+    /// A `read` without path.
+    MissingPath,
+    /// A `#` not followed by `pragma`.
+    UnknownDirective(String),
+    /// A `#pragma` without name.
+    MissingPragma,
+    /// A numeric literal that does not fit in 64 bits.
+    NumberTooLarge,
+    /// A string literal not closed before the end of the line.
+    UnterminatedString,
+    /// An unknown escape sequence in a string literal.
+    UnknownEscape(char),
+}
+
+impl fmt::Display for LexicalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl fmt::Display for LexicalErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::LexicalErrorKind::*;
+        match *self {
+            UnexpectedChar(c) => write!(f, "unexpected character `{}`", c),
+            UnterminatedComment => write!(f, "unterminated block comment"),
+            MissingPath => write!(f, "expected a path after `read`"),
+            UnknownDirective(ref name) => write!(f, "unknown directive `#{}`, expected `#pragma`", name),
+            MissingPragma => write!(f, "expected a name after `#pragma`"),
+            NumberTooLarge => write!(f, "number too large (the maximum is {})", u64::MAX),
+            UnterminatedString => write!(f, "unterminated string literal"),
+            UnknownEscape(c) => write!(f, "unknown escape sequence `\\{}`", c),
+        }
+    }
+}
+
+
+/// Processing function used by the parser: replace the escape sequences of a string literal, checked by the lexer.
+pub fn unescape(raw:&str) -> String {
+    let mut res = String::new();
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        res.push(match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(c) => c,
+                None => break
+            },
+            c => c
+        });
+    }
+    res
+}
+
+
+/// Item produced by the lexer, as expected by LALRPOP: (start, token, end) or an error.
+pub type Spanned<'input> = Result<(usize, Tok<'input>, usize), LexicalError>;
+
+
+/// The lexer: an iterator over the tokens of the input.
+pub struct Lexer<'input> {
+    input: &'input str,
+    chars: Peekable<CharIndices<'input>>,
+    /// Set after a `read`: the next token is a path.
+    expect_path: bool,
+}
+
+impl<'input> Lexer<'input> {
+    pub fn new(input: &'input str) -> Self {
+        Lexer { input, chars: input.char_indices().peekable(), expect_path: false }
+    }
+
+    /// Offset of the next character (or of the end of the input).
+    fn offset(&mut self) -> usize {
+        match self.chars.peek() {
+            Some((i, _)) => *i,
+            None => self.input.len()
+        }
+    }
+
+    /// Peek the character following the next one.
+    fn peek2(&self) -> Option<char> {
+        let mut it = self.chars.clone();
+        it.next();
+        it.next().map(|(_, c)| c)
+    }
+
+    /// Skip whitespaces and comments.
+    fn skip_blanks(&mut self) -> Result<(), LexicalError> {
+        loop {
+            match self.chars.peek() {
+                Some((_, c)) if c.is_whitespace() => { self.chars.next(); }
+                Some((_, '/')) => match self.peek2() {
+                    Some('/') => self.skip_line_comment(),
+                    Some('*') => self.skip_block_comment()?,
+                    _ => return Ok(())
+                },
+                _ => return Ok(())
+            }
+        }
+    }
+
+    /// Skip a line comment, up to the end of line (excluded).
+    fn skip_line_comment(&mut self) {
+        while let Some((_, c)) = self.chars.peek() {
+            if *c == '\n' { break; }
+            self.chars.next();
+        }
+    }
+
+    /// Skip a (possibly nested) block comment.
+    fn skip_block_comment(&mut self) -> Result<(), LexicalError> {
+        let lo = self.offset();
+        let mut depth = 0;
+        loop {
+            match self.chars.next() {
+                None => return Err(LexicalError{kind:LexicalErrorKind::UnterminatedComment, lo, hi:lo+2}),
+                Some((_, '/')) if self.chars.peek().map(|p| p.1) == Some('*') => {
+                    self.chars.next();
+                    depth += 1;
+                }
+                Some((_, '*')) if self.chars.peek().map(|p| p.1) == Some('/') => {
+                    self.chars.next();
+                    depth -= 1;
+                    if depth == 0 { return Ok(()); }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Take the characters while `pred` holds, returning the end offset.
+    fn take_while<P:Fn(char)->bool>(&mut self, pred:P) -> usize {
+        while let Some((_, c)) = self.chars.peek() {
+            if !pred(*c) { break; }
+            self.chars.next();
+        }
+        self.offset()
+    }
+
+    /// Lex the content of a string literal, after the opening quote, checking the escape sequences.
+    /// Return the offset of the closing quote.
+    fn string(&mut self, lo:usize) -> Result<usize, LexicalError> {
+        loop {
+            match self.chars.next() {
+                None | Some((_, '\n')) => {
+                    let hi = self.offset();
+                    return Err(LexicalError{kind:LexicalErrorKind::UnterminatedString, lo, hi});
+                }
+                Some((i, '"')) => return Ok(i),
+                Some((i, '\\')) => match self.chars.next() {
+                    Some((_, 'n')) | Some((_, 't')) | Some((_, '"')) | Some((_, '\\')) => {}
+                    Some((_, c)) => {
+                        let hi = self.offset();
+                        return Err(LexicalError{kind:LexicalErrorKind::UnknownEscape(c), lo:i, hi});
+                    }
+                    None => return Err(LexicalError{kind:LexicalErrorKind::UnterminatedString, lo, hi:self.input.len()})
+                },
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Lex a path, ending at a whitespace or at the start of a comment.
+    fn path(&mut self, lo:usize) -> usize {
+        loop {
+            match self.chars.peek().map(|p| p.1) {
+                None => break,
+                Some(c) if c.is_whitespace() => break,
+                Some('/') if self.peek2() == Some('/') || self.peek2() == Some('*') => break,
+                _ => { self.chars.next(); }
+            }
+        }
+        self.offset().max(lo)
+    }
+
+    /// Lex the rest of a pragma line, ending at the end of the line or at the start of a comment.
+    fn pragma(&mut self) -> usize {
+        loop {
+            match self.chars.peek().map(|p| p.1) {
+                None | Some('\n') => break,
+                Some('/') if self.peek2() == Some('/') || self.peek2() == Some('*') => break,
+                _ => { self.chars.next(); }
+            }
+        }
+        self.offset()
+    }
+
+    /// Lex the rest of an operator, stopping before the start of a comment. Return the end offset.
+    fn operator(&mut self) -> usize {
+        while let Some(c) = self.chars.peek().map(|p| p.1) {
+            if !is_operator(c) || (c == '/' && (self.peek2() == Some('/') || self.peek2() == Some('*'))) { break; }
+            self.chars.next();
+        }
+        self.offset()
+    }
+}
+
+
+/// Check if a character can start an identifier.
+fn is_id_start(c:char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+/// Check if a character can be part of an operator.
+fn is_operator(c:char) -> bool {
+    "+-*/<>=!&^%$~?@|:".contains(c)
+}
+
+/// Check if a character can continue an identifier.
+fn is_id_continue(c:char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+
+impl<'input> Iterator for Lexer<'input> {
+    type Item = Spanned<'input>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use self::Tok::*;
+
+        if let Err(e) = self.skip_blanks() { return Some(Err(e)); }
+        let lo = self.offset();
+
+        // Path after a 'read'
+        if self.expect_path {
+            self.expect_path = false;
+            let hi = self.path(lo);
+            if hi == lo {
+                return Some(Err(LexicalError{kind:LexicalErrorKind::MissingPath, lo, hi}));
+            }
+            return Some(Ok((lo, Path(&self.input[lo..hi]), hi)));
+        }
+
+        let (_, c) = self.chars.next()?;
+        let tok = match c {
+            '(' => lpar,
+            ')' => rpar,
+            '[' => lbracket,
+            ']' => rbracket,
+            '"' => match self.string(lo) {
+                Ok(end) => Str(&self.input[lo+1..end]),
+                Err(e) => return Some(Err(e))
+            },
+            ',' => comma,
+            '.' => dot,
+            '#' => {
+                let start = self.offset();
+                let end = self.take_while(is_id_continue);
+                if &self.input[start..end] != "pragma" {
+                    let kind = LexicalErrorKind::UnknownDirective(self.input[start..end].to_string());
+                    return Some(Err(LexicalError{kind, lo, hi:end}));
+                }
+                let hi = self.pragma();
+                let text = self.input[end..hi].trim();
+                if text.is_empty() {
+                    return Some(Err(LexicalError{kind:LexicalErrorKind::MissingPragma, lo, hi}));
+                }
+                Pragma(text)
+            }
+            '\\' => backslash,
+            'λ' => lambda,
+            c if is_operator(c) => {
+                let hi = self.operator();
+                match &self.input[lo..hi] {
+                    "=" => equal,
+                    "==" => equals,
+                    "|" => bar,
+                    "->" => arrow,
+                    "::" => colons,
+                    ":" => colon,
+                    s => Operator(s)
+                }
+            }
+            c if c.is_ascii_digit() => {
+                let hi = self.take_while(|c| c.is_ascii_digit());
+                match self.input[lo..hi].parse() {
+                    Ok(n) => Number(n),
+                    Err(_) => return Some(Err(LexicalError{kind:LexicalErrorKind::NumberTooLarge, lo, hi}))
+                }
+            }
+            c if is_id_start(c) => {
+                let hi = self.take_while(is_id_continue);
+                match &self.input[lo..hi] {
+                    "read" => { self.expect_path = true; KWread }
+                    "as" => KWas,
+                    "with" => KWwith,
+                    "rec" => KWrec,
+                    "data" => KWdata,
+                    "case" => KWcase,
+                    "of" => KWof,
+                    "infixl" => KWinfixl,
+                    "infixr" => KWinfixr,
+                    "infix" => KWinfix,
+                    "assert" => KWassert,
+                    "_" => underscore,
+                    s => Identifier(s)
+                }
+            }
+            c => {
+                let hi = lo + c.len_utf8();
+                return Some(Err(LexicalError{kind:LexicalErrorKind::UnexpectedChar(c), lo, hi}));
+            }
+        };
+        Some(Ok((lo, tok, self.offset())))
+    }
+}
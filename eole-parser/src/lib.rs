@@ -1,5 +1,14 @@
 #[macro_use]
 extern crate lalrpop_util;
-lalrpop_mod!(pub parser);
+lalrpop_mod!(#[allow(clippy::all, unused)] pub parser);
 
 pub mod ast;
+pub mod term;
+pub mod lexer;
+pub mod syntax;
+pub mod blc;
+pub mod infix;
+pub mod ml;
+
+// Re-export the parser's error types
+pub use lalrpop_util::{ErrorRecovery, ParseError};
@@ -0,0 +1,720 @@
+//! # Mini-ML
+//! Syntax of Mini-ML, a small ML-like language compiled to Éole terms (`.eml` files).
+//!
+//! A program is a sequence of phrases, each one terminated by `;;`:
+//!   * type declarations: `type 'a tree = Leaf | Node of 'a tree * 'a * 'a tree;;`
+//!   * definitions: `let f x y = x + y;;`, or `let rec f x = ... and g y = ...;;`
+//!   * expressions to evaluate: `f 1 2;;`
+//!
+//! The expressions are the ones of ML: `let ... in`, `fun x -> ...`, `if ... then ... else`, `match ... with`,
+//! integers (natural numbers), booleans, strings, `()`, tuples, lists (`[1; 2]`, `x :: l`),
+//! the constructors of the declared types, and the operators `+ - * / mod = <> < <= > >= && || :: ^`.
+//! The patterns are made of `_`, variables, integers, booleans, `()`, tuples, lists and constructors.
+//! Comments are written `(* ... *)`, and can be nested.
+//!
+//! Parsing stops at the first error.
+
+use crate::ast::Span;
+
+use std::fmt;
+
+
+/// Extension of the Mini-ML files.
+pub const EXTENSION:&str = "eml";
+
+
+// --- --- --- Syntax tree
+
+/// A phrase of a program.
+#[derive(Clone, Debug)]
+pub enum Phrase {
+    /// Declaration of a data type.
+    Type(TypeDecl),
+    /// Top level definitions.
+    Let{rec:bool, bindings:Vec<Binding>, span:Span},
+    /// Expression to evaluate.
+    Run(Expr),
+}
+
+/// Declaration of a data type: `type ('a, 'b) name = C1 | C2 of t1 * t2`.
+#[derive(Clone, Debug)]
+pub struct TypeDecl {
+    pub name:String,
+    pub params:Vec<String>,
+    pub ctors:Vec<CtorDecl>,
+    pub span:Span,
+}
+
+/// Constructor of a data type, with the types of its fields.
+#[derive(Clone, Debug)]
+pub struct CtorDecl {
+    pub name:String,
+    pub fields:Vec<TypeExpr>,
+    pub span:Span,
+}
+
+/// Type, as written in the declarations.
+#[derive(Clone, Debug)]
+pub enum TypeExpr {
+    /// Type variable `'a`.
+    Var{name:String, span:Span},
+    /// Named type, with its arguments: `int`, `'a list`, `('a, 'b) pair`.
+    Con{name:String, args:Vec<TypeExpr>, span:Span},
+    Tuple{items:Vec<TypeExpr>, span:Span},
+    Arrow{arg:Box<TypeExpr>, res:Box<TypeExpr>, span:Span},
+}
+
+/// A definition: `pattern = body`, or `name params = body` for a function.
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub pattern:Pattern,
+    pub params:Vec<Pattern>,
+    pub body:Expr,
+    pub span:Span,
+}
+
+/// Binary operators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinOp {
+    Add, Sub, Mul, Div, Mod,
+    Eq, Ne, Lt, Le, Gt, Ge,
+    And, Or,
+    Cons, Concat,
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        use self::BinOp::*;
+        let s = match self {
+            Add => "+", Sub => "-", Mul => "*", Div => "/", Mod => "mod",
+            Eq => "=", Ne => "<>", Lt => "<", Le => "<=", Gt => ">", Ge => ">=",
+            And => "&&", Or => "||",
+            Cons => "::", Concat => "^",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Expressions.
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Int{value:u64, span:Span},
+    Str{value:String, span:Span},
+    Bool{value:bool, span:Span},
+    Unit{span:Span},
+    Var{name:String, span:Span},
+    /// Constructor, with its argument (a tuple for several fields).
+    Ctor{name:String, arg:Option<Box<Expr>>, span:Span},
+    Tuple{items:Vec<Expr>, span:Span},
+    List{items:Vec<Expr>, span:Span},
+    App{fun:Box<Expr>, arg:Box<Expr>, span:Span},
+    Fun{params:Vec<Pattern>, body:Box<Expr>, span:Span},
+    Let{rec:bool, bindings:Vec<Binding>, body:Box<Expr>, span:Span},
+    If{cond:Box<Expr>, then:Box<Expr>, other:Box<Expr>, span:Span},
+    Match{scrutinee:Box<Expr>, arms:Vec<(Pattern, Expr)>, span:Span},
+    BinOp{op:BinOp, lhs:Box<Expr>, rhs:Box<Expr>, span:Span},
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        use self::Expr::*;
+        match self {
+            Int{span, ..} | Str{span, ..} | Bool{span, ..} | Unit{span} | Var{span, ..} | Ctor{span, ..} |
+            Tuple{span, ..} | List{span, ..} | App{span, ..} | Fun{span, ..} | Let{span, ..} | If{span, ..} |
+            Match{span, ..} | BinOp{span, ..} => *span
+        }
+    }
+}
+
+/// Patterns.
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Wildcard{span:Span},
+    Var{name:String, span:Span},
+    Int{value:u64, span:Span},
+    Bool{value:bool, span:Span},
+    Unit{span:Span},
+    Tuple{items:Vec<Pattern>, span:Span},
+    Nil{span:Span},
+    Cons{head:Box<Pattern>, tail:Box<Pattern>, span:Span},
+    /// Constructor, with its argument (a tuple for several fields).
+    Ctor{name:String, arg:Option<Box<Pattern>>, span:Span},
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        use self::Pattern::*;
+        match self {
+            Wildcard{span} | Var{span, ..} | Int{span, ..} | Bool{span, ..} | Unit{span} | Tuple{span, ..} |
+            Nil{span} | Cons{span, ..} | Ctor{span, ..} => *span
+        }
+    }
+
+    /// Variables bound by the pattern, in order.
+    pub fn vars(&self) -> Vec<(&str, Span)> {
+        use self::Pattern::*;
+        match self {
+            Var{name, span} => vec![(name, *span)],
+            Tuple{items, ..} => items.iter().flat_map(|p| p.vars()).collect(),
+            Cons{head, tail, ..} => head.vars().into_iter().chain(tail.vars()).collect(),
+            Ctor{arg:Some(p), ..} => p.vars(),
+            _ => vec![]
+        }
+    }
+}
+
+
+// --- --- --- Errors
+
+/// Syntax error, located by its offsets `[lo, hi)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MlError {
+    pub kind:MlErrorKind,
+    pub lo:usize,
+    pub hi:usize,
+}
+
+/// Kinds of syntax errors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MlErrorKind {
+    UnexpectedChar(char),
+    UnterminatedComment,
+    UnterminatedString,
+    UnknownEscape(char),
+    NumberTooLarge,
+    /// An unexpected token, with a description of the expected ones.
+    Unexpected{found:String, expected:&'static str},
+}
+
+impl fmt::Display for MlError {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        use self::MlErrorKind::*;
+        match &self.kind {
+            UnexpectedChar(c) => write!(f, "unexpected character `{}`", c),
+            UnterminatedComment => write!(f, "unterminated comment"),
+            UnterminatedString => write!(f, "unterminated string"),
+            UnknownEscape(c) => write!(f, "unknown escape sequence `\\{}`", c),
+            NumberTooLarge => write!(f, "number too large"),
+            Unexpected{found, expected} => write!(f, "unexpected {}, expected {}", found, expected),
+        }
+    }
+}
+
+
+// --- --- --- Lexer
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Tok {
+    Int(u64),
+    Str(String),
+    LIdent(String),
+    UIdent(String),
+    TyVar(String),
+    Kw(&'static str),
+    Sym(&'static str),
+    Eof,
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match self {
+            Tok::Int(n) => write!(f, "`{}`", n),
+            Tok::Str(_) => write!(f, "string"),
+            Tok::LIdent(s) | Tok::UIdent(s) | Tok::TyVar(s) => write!(f, "`{}`", s),
+            Tok::Kw(s) | Tok::Sym(s) => write!(f, "`{}`", s),
+            Tok::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+const KEYWORDS:&[&str] = &["let", "rec", "and", "in", "fun", "if", "then", "else", "match", "with", "type", "of", "true", "false", "mod"];
+
+// Longest symbols first
+const SYMBOLS:&[&str] = &[";;", "->", "<>", "<=", ">=", "&&", "||", "::", "(", ")", "[", "]", ",", ";", "=", "<", ">", "+", "-", "*", "/", "^", "|", "_"];
+
+/// Split a text into tokens, with their offsets.
+fn tokenize(text:&str) -> Result<Vec<(usize, Tok, usize)>, MlError> {
+    let bytes = text.as_bytes();
+    let mut res = vec![];
+    let mut i = 0;
+    let error = |kind, lo, hi| Err(MlError{kind, lo, hi});
+    while i < text.len() {
+        let c = text[i..].chars().next().unwrap();
+        let lo = i;
+        if c.is_whitespace() {
+            i += c.len_utf8();
+        } else if text[i..].starts_with("(*") {
+            // Nested comments
+            let mut depth = 0;
+            loop {
+                if i >= text.len() { return error(MlErrorKind::UnterminatedComment, lo, lo+2); }
+                if text[i..].starts_with("(*") { depth += 1; i += 2; }
+                else if text[i..].starts_with("*)") { depth -= 1; i += 2; if depth == 0 { break; } }
+                else { i += text[i..].chars().next().unwrap().len_utf8(); }
+            }
+        } else if c.is_ascii_digit() {
+            while i < text.len() && bytes[i].is_ascii_digit() { i += 1; }
+            match text[lo..i].parse() {
+                Ok(n) => res.push((lo, Tok::Int(n), i)),
+                Err(_) => return error(MlErrorKind::NumberTooLarge, lo, i),
+            }
+        } else if c == '"' {
+            i += 1;
+            let mut value = String::new();
+            loop {
+                let c = match text[i..].chars().next() {
+                    None | Some('\n') => return error(MlErrorKind::UnterminatedString, lo, i),
+                    Some(c) => c
+                };
+                i += c.len_utf8();
+                match c {
+                    '"' => break,
+                    '\\' => {
+                        let e = match text[i..].chars().next() {
+                            None | Some('\n') => return error(MlErrorKind::UnterminatedString, lo, i),
+                            Some(e) => e
+                        };
+                        i += e.len_utf8();
+                        value.push(match e {
+                            '"' => '"', '\\' => '\\', 'n' => '\n', 't' => '\t',
+                            _ => return error(MlErrorKind::UnknownEscape(e), i - e.len_utf8() - 1, i)
+                        });
+                    }
+                    c => value.push(c)
+                }
+            }
+            res.push((lo, Tok::Str(value), i));
+        } else if c == '\'' || c.is_alphabetic() || c == '_' {
+            i += c.len_utf8();
+            while let Some(c) = text[i..].chars().next() {
+                if c.is_alphanumeric() || c == '_' || c == '\'' { i += c.len_utf8(); } else { break; }
+            }
+            let word = &text[lo..i];
+            let tok = if c == '\'' {
+                if word.len() == 1 { return error(MlErrorKind::UnexpectedChar('\''), lo, i); }
+                Tok::TyVar(word.to_string())
+            } else if word == "_" {
+                Tok::Sym("_")
+            } else if let Some(kw) = KEYWORDS.iter().find(|k| **k == word) {
+                Tok::Kw(kw)
+            } else if c.is_uppercase() {
+                Tok::UIdent(word.to_string())
+            } else {
+                Tok::LIdent(word.to_string())
+            };
+            res.push((lo, tok, i));
+        } else if let Some(s) = SYMBOLS.iter().find(|s| text[i..].starts_with(**s)) {
+            i += s.len();
+            res.push((lo, Tok::Sym(s), i));
+        } else {
+            return error(MlErrorKind::UnexpectedChar(c), lo, lo + c.len_utf8());
+        }
+    }
+    res.push((text.len(), Tok::Eof, text.len()));
+    Ok(res)
+}
+
+
+// --- --- --- Parser
+
+/// Parse a Mini-ML program, registered as `file` in the sources.
+pub fn parse(file:usize, text:&str) -> Result<Vec<Phrase>, MlError> {
+    let mut p = Parser{ file, toks:tokenize(text)?, pos:0 };
+    let mut phrases = vec![];
+    while p.peek() != &Tok::Eof {
+        phrases.push(p.phrase()?);
+    }
+    Ok(phrases)
+}
+
+struct Parser {
+    file:usize,
+    toks:Vec<(usize, Tok, usize)>,
+    pos:usize,
+}
+
+type Res<T> = Result<T, MlError>;
+
+impl Parser {
+
+    fn peek(&self) -> &Tok {
+        &self.toks[self.pos].1
+    }
+
+    fn peek_at(&self, k:usize) -> &Tok {
+        &self.toks[(self.pos + k).min(self.toks.len() - 1)].1
+    }
+
+    fn lo(&self) -> usize {
+        self.toks[self.pos].0
+    }
+
+    /// End of the last token.
+    fn hi(&self) -> usize {
+        if self.pos == 0 { 0 } else { self.toks[self.pos - 1].2 }
+    }
+
+    fn span(&self, lo:usize) -> Span {
+        Span::new(self.file, lo, self.hi())
+    }
+
+    fn next(&mut self) -> Tok {
+        let t = self.toks[self.pos].1.clone();
+        if t != Tok::Eof { self.pos += 1; }
+        t
+    }
+
+    fn is(&self, s:&str) -> bool {
+        matches!(self.peek(), Tok::Kw(k) | Tok::Sym(k) if *k == s)
+    }
+
+    /// Consume the keyword or symbol `s` if it is next.
+    fn eat(&mut self, s:&str) -> bool {
+        if self.is(s) { self.pos += 1; true } else { false }
+    }
+
+    fn unexpected<T>(&self, expected:&'static str) -> Res<T> {
+        let (lo, t, hi) = &self.toks[self.pos];
+        Err(MlError{ kind:MlErrorKind::Unexpected{found:t.to_string(), expected}, lo:*lo, hi:*hi })
+    }
+
+    fn expect(&mut self, s:&'static str) -> Res<()> {
+        if self.eat(s) { Ok(()) } else { self.unexpected(match s {
+            ";;" => "`;;`", ")" => "`)`", "]" => "`]`", "=" => "`=`", "->" => "`->`", "in" => "`in`",
+            "then" => "`then`", "else" => "`else`", "with" => "`with`", _ => "a symbol"
+        }) }
+    }
+
+    fn lident(&mut self) -> Res<String> {
+        match self.next() {
+            Tok::LIdent(s) => Ok(s),
+            _ => { self.pos -= 1; self.unexpected("an identifier") }
+        }
+    }
+
+    // --- Phrases
+
+    fn phrase(&mut self) -> Res<Phrase> {
+        let lo = self.lo();
+        let phrase = if self.eat("type") {
+            Phrase::Type(self.type_decl(lo)?)
+        } else if self.is("let") && !self.let_in() {
+            self.next();
+            let rec = self.eat("rec");
+            let bindings = self.bindings(rec)?;
+            Phrase::Let{rec, bindings, span:self.span(lo)}
+        } else {
+            Phrase::Run(self.expr()?)
+        };
+        self.expect(";;")?;
+        Ok(phrase)
+    }
+
+    /// Check if the `let` at the current position is a `let ... in` expression:
+    /// an `in` is found at the same nesting level before the end of the phrase.
+    fn let_in(&self) -> bool {
+        let mut depth = 0;
+        for (_, t, _) in &self.toks[self.pos..] {
+            match t {
+                Tok::Kw("let") => depth += 1,
+                Tok::Kw("in") => { depth -= 1; if depth == 0 { return true; } }
+                Tok::Sym(";;") | Tok::Eof => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    fn type_decl(&mut self, lo:usize) -> Res<TypeDecl> {
+        let mut params = vec![];
+        match self.peek().clone() {
+            Tok::TyVar(v) => { self.next(); params.push(v); }
+            Tok::Sym("(") if matches!(self.peek_at(1), Tok::TyVar(_)) => {
+                self.next();
+                loop {
+                    match self.next() {
+                        Tok::TyVar(v) => params.push(v),
+                        _ => { self.pos -= 1; return self.unexpected("a type variable"); }
+                    }
+                    if !self.eat(",") { break; }
+                }
+                self.expect(")")?;
+            }
+            _ => {}
+        }
+        let name = self.lident()?;
+        self.expect("=")?;
+        self.eat("|");
+        let mut ctors = vec![];
+        loop {
+            let clo = self.lo();
+            let cname = match self.next() {
+                Tok::UIdent(c) => c,
+                _ => { self.pos -= 1; return self.unexpected("a constructor"); }
+            };
+            let mut fields = vec![];
+            if self.eat("of") {
+                fields.push(self.type_app()?);
+                while self.eat("*") { fields.push(self.type_app()?); }
+            }
+            ctors.push(CtorDecl{name:cname, fields, span:self.span(clo)});
+            if !self.eat("|") { break; }
+        }
+        Ok(TypeDecl{name, params, ctors, span:self.span(lo)})
+    }
+
+    fn bindings(&mut self, rec:bool) -> Res<Vec<Binding>> {
+        let mut bindings = vec![self.binding(rec)?];
+        while self.eat("and") { bindings.push(self.binding(rec)?); }
+        Ok(bindings)
+    }
+
+    /// `name params = body`, or `pattern = body`. Recursive definitions are functions.
+    fn binding(&mut self, rec:bool) -> Res<Binding> {
+        let lo = self.lo();
+        let (pattern, params) = match self.peek().clone() {
+            Tok::LIdent(name) => {
+                self.next();
+                let pattern = Pattern::Var{name, span:self.span(lo)};
+                let mut params = vec![];
+                while self.starts_pattern_atom() { params.push(self.pattern_atom()?); }
+                (pattern, params)
+            }
+            _ if rec => return self.unexpected("an identifier"),
+            _ => (self.pattern()?, vec![])
+        };
+        self.expect("=")?;
+        let body = self.expr()?;
+        Ok(Binding{pattern, params, body, span:self.span(lo)})
+    }
+
+    // --- Types
+
+    fn type_expr(&mut self) -> Res<TypeExpr> {
+        let lo = self.lo();
+        let mut items = vec![self.type_app()?];
+        while self.eat("*") { items.push(self.type_app()?); }
+        let t = if items.len() == 1 { items.pop().unwrap() } else { TypeExpr::Tuple{items, span:self.span(lo)} };
+        if self.eat("->") {
+            let res = self.type_expr()?;
+            Ok(TypeExpr::Arrow{arg:Box::new(t), res:Box::new(res), span:self.span(lo)})
+        } else {
+            Ok(t)
+        }
+    }
+
+    /// Type atom followed by type names: `int list option`.
+    fn type_app(&mut self) -> Res<TypeExpr> {
+        let lo = self.lo();
+        let mut args = match self.next() {
+            Tok::TyVar(name) => vec![TypeExpr::Var{name, span:self.span(lo)}],
+            Tok::LIdent(name) => vec![TypeExpr::Con{name, args:vec![], span:self.span(lo)}],
+            Tok::Sym("(") => {
+                let mut args = vec![self.type_expr()?];
+                while self.eat(",") { args.push(self.type_expr()?); }
+                self.expect(")")?;
+                args
+            }
+            _ => { self.pos -= 1; return self.unexpected("a type"); }
+        };
+        while let Tok::LIdent(name) = self.peek().clone() {
+            self.next();
+            args = vec![TypeExpr::Con{name, args, span:self.span(lo)}];
+        }
+        if args.len() != 1 { return self.unexpected("a type name"); }
+        Ok(args.pop().unwrap())
+    }
+
+    // --- Expressions
+
+    fn expr(&mut self) -> Res<Expr> {
+        let lo = self.lo();
+        let mut items = vec![self.expr_op(0)?];
+        while self.eat(",") { items.push(self.expr_op(0)?); }
+        Ok(if items.len() == 1 { items.pop().unwrap() } else { Expr::Tuple{items, span:self.span(lo)} })
+    }
+
+    /// Expressions extending as far right as possible: `let`, `fun`, `if` and `match`.
+    fn expr_open(&mut self, lo:usize) -> Res<Option<Expr>> {
+        if self.eat("let") {
+            let rec = self.eat("rec");
+            let bindings = self.bindings(rec)?;
+            self.expect("in")?;
+            let body = self.expr()?;
+            Ok(Some(Expr::Let{rec, bindings, body:Box::new(body), span:self.span(lo)}))
+        } else if self.eat("fun") {
+            let mut params = vec![self.pattern_atom()?];
+            while self.starts_pattern_atom() { params.push(self.pattern_atom()?); }
+            self.expect("->")?;
+            let body = self.expr()?;
+            Ok(Some(Expr::Fun{params, body:Box::new(body), span:self.span(lo)}))
+        } else if self.eat("if") {
+            let cond = self.expr()?;
+            self.expect("then")?;
+            let then = self.expr()?;
+            self.expect("else")?;
+            let other = self.expr()?;
+            Ok(Some(Expr::If{cond:Box::new(cond), then:Box::new(then), other:Box::new(other), span:self.span(lo)}))
+        } else if self.eat("match") {
+            let scrutinee = self.expr()?;
+            self.expect("with")?;
+            self.eat("|");
+            let mut arms = vec![];
+            loop {
+                let p = self.pattern()?;
+                self.expect("->")?;
+                let e = self.expr()?;
+                arms.push((p, e));
+                if !self.eat("|") { break; }
+            }
+            Ok(Some(Expr::Match{scrutinee:Box::new(scrutinee), arms, span:self.span(lo)}))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Binary operators, by increasing precedence: `||`, `&&`, comparisons, `::`, `+ - ^`, `* / mod`.
+    fn expr_op(&mut self, level:usize) -> Res<Expr> {
+        const LEVELS:&[&[(&str, BinOp)]] = &[
+            &[("||", BinOp::Or)],
+            &[("&&", BinOp::And)],
+            &[("=", BinOp::Eq), ("<>", BinOp::Ne), ("<=", BinOp::Le), (">=", BinOp::Ge), ("<", BinOp::Lt), (">", BinOp::Gt)],
+            &[("::", BinOp::Cons)],
+            &[("+", BinOp::Add), ("-", BinOp::Sub), ("^", BinOp::Concat)],
+            &[("*", BinOp::Mul), ("/", BinOp::Div), ("mod", BinOp::Mod)],
+        ];
+        let lo = self.lo();
+        if let Some(e) = self.expr_open(lo)? { return Ok(e); }
+        if level == LEVELS.len() { return self.expr_app(); }
+        let mut lhs = self.expr_op(level + 1)?;
+        while let Some((_, op)) = LEVELS[level].iter().find(|(s, _)| self.is(s)) {
+            self.next();
+            // '||', '&&' and '::' are right associative
+            let right = matches!(op, BinOp::Or | BinOp::And | BinOp::Cons);
+            let rhs = self.expr_op(if right { level } else { level + 1 })?;
+            lhs = Expr::BinOp{op:*op, lhs:Box::new(lhs), rhs:Box::new(rhs), span:self.span(lo)};
+            if right { break; }
+        }
+        Ok(lhs)
+    }
+
+    /// Application, and constructors with their argument.
+    fn expr_app(&mut self) -> Res<Expr> {
+        let lo = self.lo();
+        if let Tok::UIdent(name) = self.peek().clone() {
+            self.next();
+            let arg = if self.starts_atom() { Some(Box::new(self.expr_atom()?)) } else { None };
+            return Ok(Expr::Ctor{name, arg, span:self.span(lo)});
+        }
+        let mut e = self.expr_atom()?;
+        loop {
+            let arg = if self.starts_atom() {
+                self.expr_atom()?
+            } else if let Tok::UIdent(name) = self.peek().clone() {
+                // A constructor as an argument does not take an argument itself
+                let alo = self.lo();
+                self.next();
+                Expr::Ctor{name, arg:None, span:self.span(alo)}
+            } else if let Some(open) = self.expr_open(self.lo())? {
+                // 'f fun x -> x' or 'f if ...': as in OCaml, the last argument can be an open expression
+                e = Expr::App{fun:Box::new(e), arg:Box::new(open), span:self.span(lo)};
+                break;
+            } else {
+                break;
+            };
+            e = Expr::App{fun:Box::new(e), arg:Box::new(arg), span:self.span(lo)};
+        }
+        Ok(e)
+    }
+
+    fn starts_atom(&self) -> bool {
+        matches!(self.peek(), Tok::Int(_) | Tok::Str(_) | Tok::LIdent(_) | Tok::Kw("true") | Tok::Kw("false") | Tok::Sym("(") | Tok::Sym("["))
+    }
+
+    fn expr_atom(&mut self) -> Res<Expr> {
+        let lo = self.lo();
+        match self.next() {
+            Tok::Int(value) => Ok(Expr::Int{value, span:self.span(lo)}),
+            Tok::Str(value) => Ok(Expr::Str{value, span:self.span(lo)}),
+            Tok::Kw("true") => Ok(Expr::Bool{value:true, span:self.span(lo)}),
+            Tok::Kw("false") => Ok(Expr::Bool{value:false, span:self.span(lo)}),
+            Tok::LIdent(name) => Ok(Expr::Var{name, span:self.span(lo)}),
+            Tok::UIdent(name) => Ok(Expr::Ctor{name, arg:None, span:self.span(lo)}),
+            Tok::Sym("(") => {
+                if self.eat(")") { return Ok(Expr::Unit{span:self.span(lo)}); }
+                let e = self.expr()?;
+                self.expect(")")?;
+                Ok(e)
+            }
+            Tok::Sym("[") => {
+                let mut items = vec![];
+                while !self.is("]") {
+                    items.push(self.expr_op(0)?);
+                    if !self.eat(";") { break; }
+                }
+                self.expect("]")?;
+                Ok(Expr::List{items, span:self.span(lo)})
+            }
+            _ => { self.pos -= 1; self.unexpected("an expression") }
+        }
+    }
+
+    // --- Patterns
+
+    fn pattern(&mut self) -> Res<Pattern> {
+        let lo = self.lo();
+        let mut items = vec![self.pattern_cons()?];
+        while self.eat(",") { items.push(self.pattern_cons()?); }
+        Ok(if items.len() == 1 { items.pop().unwrap() } else { Pattern::Tuple{items, span:self.span(lo)} })
+    }
+
+    fn pattern_cons(&mut self) -> Res<Pattern> {
+        let lo = self.lo();
+        let head = if let Tok::UIdent(name) = self.peek().clone() {
+            self.next();
+            let arg = if self.starts_pattern_atom() { Some(Box::new(self.pattern_atom()?)) } else { None };
+            Pattern::Ctor{name, arg, span:self.span(lo)}
+        } else {
+            self.pattern_atom()?
+        };
+        if self.eat("::") {
+            let tail = self.pattern_cons()?;
+            Ok(Pattern::Cons{head:Box::new(head), tail:Box::new(tail), span:self.span(lo)})
+        } else {
+            Ok(head)
+        }
+    }
+
+    fn starts_pattern_atom(&self) -> bool {
+        matches!(self.peek(), Tok::Int(_) | Tok::LIdent(_) | Tok::Kw("true") | Tok::Kw("false") | Tok::Sym("(") | Tok::Sym("[") | Tok::Sym("_"))
+    }
+
+    fn pattern_atom(&mut self) -> Res<Pattern> {
+        let lo = self.lo();
+        match self.next() {
+            Tok::Sym("_") => Ok(Pattern::Wildcard{span:self.span(lo)}),
+            Tok::LIdent(name) => Ok(Pattern::Var{name, span:self.span(lo)}),
+            Tok::Int(value) => Ok(Pattern::Int{value, span:self.span(lo)}),
+            Tok::Kw("true") => Ok(Pattern::Bool{value:true, span:self.span(lo)}),
+            Tok::Kw("false") => Ok(Pattern::Bool{value:false, span:self.span(lo)}),
+            Tok::UIdent(name) => Ok(Pattern::Ctor{name, arg:None, span:self.span(lo)}),
+            Tok::Sym("(") => {
+                if self.eat(")") { return Ok(Pattern::Unit{span:self.span(lo)}); }
+                let p = self.pattern()?;
+                self.expect(")")?;
+                Ok(p)
+            }
+            Tok::Sym("[") => {
+                let mut items = vec![];
+                while !self.is("]") {
+                    items.push(self.pattern_cons()?);
+                    if !self.eat(";") { break; }
+                }
+                self.expect("]")?;
+                let span = self.span(lo);
+                Ok(items.into_iter().rev().fold(Pattern::Nil{span}, |tail, head| Pattern::Cons{head:Box::new(head), tail:Box::new(tail), span}))
+            }
+            _ => { self.pos -= 1; self.unexpected("a pattern") }
+        }
+    }
+}
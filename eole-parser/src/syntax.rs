@@ -0,0 +1,58 @@
+//! # Syntax errors
+//! Errors reported by the parser: the errors of the lexer, and the errors of the checks the parser makes
+//! while building the sentences, which do not stop the parsing.
+//!
+//! The lexer produces `LexicalError`s: they are converted before reaching the parser,
+//! e.g. `Lexer::new(text).map(|t| t.map_err(SyntaxError::from))`.
+
+use crate::ast::Span;
+use crate::lexer::{LexicalError, LexicalErrorKind, Tok};
+use lalrpop_util::{ErrorRecovery, ParseError};
+
+use std::fmt;
+
+
+/// Errors of the parser, located by their byte offsets `[lo, hi)`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct SyntaxError {
+    pub kind:SyntaxErrorKind,
+    pub lo:usize,
+    pub hi:usize
+}
+
+/// Kinds of syntax errors
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum SyntaxErrorKind {
+    /// An error of the lexer.
+    Lexical(LexicalErrorKind),
+    /// The same name is used twice in the binders of an abstraction (e.g. `x x -> x`), which is ambiguous.
+    /// See `check_binders`.
+    DuplicateBinder(String),
+}
+
+impl From<LexicalError> for SyntaxError {
+    fn from(e:LexicalError) -> SyntaxError {
+        SyntaxError{kind:SyntaxErrorKind::Lexical(e.kind), lo:e.lo, hi:e.hi}
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            SyntaxErrorKind::Lexical(ref kind) => write!(f, "{}", kind),
+            SyntaxErrorKind::DuplicateBinder(ref name) => write!(f, "`{}` is bound more than once by the same abstraction", name),
+        }
+    }
+}
+
+
+/// Processing function used by the parser: report the names appearing twice in the binders of an abstraction.
+/// The error does not stop the parsing: the innermost binder shadows the others.
+pub fn check_binders(binders:&[(String, Span)], errors:&mut Vec<ErrorRecovery<usize, Tok, SyntaxError>>) {
+    for (i, (name, span)) in binders.iter().enumerate() {
+        if name != "_" && binders[..i].iter().any(|(n, _)| n == name) {
+            let error = SyntaxError{kind:SyntaxErrorKind::DuplicateBinder(name.clone()), lo:span.lo, hi:span.hi};
+            errors.push(ErrorRecovery{error:ParseError::User{error}, dropped_tokens:vec![]});
+        }
+    }
+}
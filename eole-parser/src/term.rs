@@ -0,0 +1,414 @@
+//! # Operations on terms
+//! The usual operations of the lambda calculus on `ast::Term`, for the tools built on top of the parser:
+//!   * free variables: `Term::occurs_free` and `Term::free_vars`;
+//!   * capture-avoiding substitution: `Term::substitute`;
+//!   * alpha-equivalence: `Term::alpha_eq`;
+//!   * de Bruijn indices: `Term::to_de_bruijn` and `DeBruijn::to_term`;
+//!   * metrics: `Term::size` and `Term::depth`;
+//!   * single-step reductions, in normal order: `Term::beta_step` and `Term::eta_step`.
+//!
+//! All the binders are taken into account: abstractions, but also recursive definitions and the binders of the case arms.
+//! The binders on the right of a case arm shadow the ones on the left.
+//! The spans are ignored by the comparisons.
+
+use crate::ast::{*, Term::*};
+
+use std::collections::HashSet;
+use std::fmt;
+
+
+impl Term {
+    /// Check if a variable occurs free in the term.
+    pub fn occurs_free(&self, name:&str) -> bool {
+        match self {
+            Sym{vname, ..} => vname == name,
+            Lambda{vname, body, ..} => vname != name && body.occurs_free(name),
+            App{fun, arg, ..} => fun.occurs_free(name) || arg.occurs_free(name),
+            Num{..} | Str{..} => false,
+            List{items, ..} => items.iter().any(|item| item.occurs_free(name)),
+            Rec{defs, body, ..} => !defs.iter().any(|(vname, _)| vname == name)
+                && (body.occurs_free(name) || defs.iter().any(|(_, def)| def.occurs_free(name))),
+            Annot{term, ..} => term.occurs_free(name),
+            Infix{operands, ..} => operands.iter().any(|operand| operand.occurs_free(name)),
+            Case{scrutinee, arms, ..} => scrutinee.occurs_free(name)
+                || arms.iter().any(|a| !a.binders.iter().any(|(vname, _)| vname == name) && a.body.occurs_free(name)),
+        }
+    }
+
+    /// Get the free variables of the term.
+    pub fn free_vars(&self) -> HashSet<String> {
+        let mut res = HashSet::new();
+        self.free_vars_(&mut vec![], &mut res);
+        res
+    }
+
+    fn free_vars_(&self, bound:&mut Vec<String>, res:&mut HashSet<String>) {
+        match self {
+            Sym{vname, ..} => if !bound.contains(vname) { res.insert(vname.clone()); },
+            Lambda{vname, body, ..} => {
+                bound.push(vname.clone());
+                body.free_vars_(bound, res);
+                bound.pop();
+            }
+            App{fun, arg, ..} => {
+                fun.free_vars_(bound, res);
+                arg.free_vars_(bound, res);
+            }
+            Num{..} | Str{..} => {}
+            List{items, ..} => for item in items { item.free_vars_(bound, res); },
+            Rec{defs, body, ..} => {
+                let depth = bound.len();
+                bound.extend(defs.iter().map(|(vname, _)| vname.clone()));
+                for (_, def) in defs { def.free_vars_(bound, res); }
+                body.free_vars_(bound, res);
+                bound.truncate(depth);
+            }
+            Annot{term, ..} => term.free_vars_(bound, res),
+            Infix{operands, ..} => for operand in operands { operand.free_vars_(bound, res); },
+            Case{scrutinee, arms, ..} => {
+                scrutinee.free_vars_(bound, res);
+                for arm in arms {
+                    let depth = bound.len();
+                    bound.extend(arm.binders.iter().map(|(vname, _)| vname.clone()));
+                    arm.body.free_vars_(bound, res);
+                    bound.truncate(depth);
+                }
+            }
+        }
+    }
+
+
+    /// Replace the free occurrences of `name` by `value`.
+    /// The binders which would capture a free variable of `value` are renamed, see `fresh`.
+    /// Substituting a variable keeps the span of the occurrences: renaming does not move the terms.
+    pub fn substitute(&self, name:&str, value:&Term) -> Term {
+        match self {
+            Sym{vname, span} if vname == name => match value {
+                Sym{vname, ..} => Sym{vname:vname.clone(), span:*span},
+                _ => value.clone()
+            },
+            Sym{..} | Num{..} | Str{..} => self.clone(),
+            Lambda{vname, body, span} => {
+                let (mut binders, mut bodies) = substitute_under(std::slice::from_ref(vname), &[body], name, value);
+                Lambda{vname:binders.pop().unwrap(), body:Box::new(bodies.pop().unwrap()), span:*span}
+            }
+            App{fun, arg, span} => App{
+                fun:Box::new(fun.substitute(name, value)),
+                arg:Box::new(arg.substitute(name, value)),
+                span:*span
+            },
+            List{items, span} => List{items:items.iter().map(|item| Box::new(item.substitute(name, value))).collect(), span:*span},
+            Rec{defs, body, span} => {
+                let binders:Vec<String> = defs.iter().map(|(vname, _)| vname.clone()).collect();
+                let terms:Vec<&Term> = defs.iter().map(|(_, def)| &**def).chain(Some(&**body)).collect();
+                let (binders, mut bodies) = substitute_under(&binders, &terms, name, value);
+                let body = Box::new(bodies.pop().unwrap());
+                Rec{defs:binders.into_iter().zip(bodies.into_iter().map(Box::new)).collect(), body, span:*span}
+            }
+            Case{scrutinee, arms, span} => Case{
+                scrutinee:Box::new(scrutinee.substitute(name, value)),
+                arms:arms.iter().map(|arm| {
+                    let binders:Vec<String> = arm.binders.iter().map(|(vname, _)| vname.clone()).collect();
+                    let (binders, mut bodies) = substitute_under(&binders, &[&arm.body], name, value);
+                    Arm{
+                        ctor:arm.ctor.clone(),
+                        binders:binders.into_iter().zip(arm.binders.iter().map(|(_, span)| *span)).collect(),
+                        body:Box::new(bodies.pop().unwrap()),
+                        span:arm.span
+                    }
+                }).collect(),
+                span:*span
+            },
+            Annot{term, ty, span} => Annot{term:Box::new(term.substitute(name, value)), ty:ty.clone(), span:*span},
+            Infix{operands, operators, span} => Infix{
+                operands:operands.iter().map(|operand| Box::new(operand.substitute(name, value))).collect(),
+                operators:operators.clone(),
+                span:*span
+            },
+        }
+    }
+
+
+    /// Check if two terms are equal up to the renaming of their bound variables.
+    pub fn alpha_eq(&self, other:&Term) -> bool {
+        alpha_eq_(self, other, &mut vec![])
+    }
+
+
+    /// Convert a term to de Bruijn indices.
+    /// Only the pure lambda terms can be converted (the annotations are erased):
+    /// the error is the span of the first construction which cannot.
+    pub fn to_de_bruijn(&self) -> Result<DeBruijn, Span> {
+        to_de_bruijn_(self, &mut vec![])
+    }
+
+
+    /// Number of nodes of the term: variables, abstractions and applications.
+    /// The literals count as one node, the annotations as none, and each operator as an application.
+    pub fn size(&self) -> usize {
+        match self {
+            Sym{..} | Num{..} | Str{..} => 1,
+            Lambda{body, ..} => 1 + body.size(),
+            App{fun, arg, ..} => 1 + fun.size() + arg.size(),
+            List{items, ..} => 1 + items.iter().map(|item| item.size()).sum::<usize>(),
+            Rec{defs, body, ..} => 1 + body.size() + defs.iter().map(|(_, def)| def.size()).sum::<usize>(),
+            Case{scrutinee, arms, ..} => 1 + scrutinee.size() + arms.iter().map(|arm| arm.body.size()).sum::<usize>(),
+            Annot{term, ..} => term.size(),
+            Infix{operands, operators, ..} => operators.len() + operands.iter().map(|operand| operand.size()).sum::<usize>(),
+        }
+    }
+
+    /// Number of nodes on the longest path from the root of the term to a leaf, counted as in `size`.
+    pub fn depth(&self) -> usize {
+        match self {
+            Sym{..} | Num{..} | Str{..} => 1,
+            Lambda{body, ..} => 1 + body.depth(),
+            App{fun, arg, ..} => 1 + fun.depth().max(arg.depth()),
+            List{items, ..} => 1 + items.iter().map(|item| item.depth()).max().unwrap_or(0),
+            Rec{defs, body, ..} => 1 + defs.iter().map(|(_, def)| def.depth()).max().unwrap_or(0).max(body.depth()),
+            Case{scrutinee, arms, ..} => 1 + arms.iter().map(|arm| arm.body.depth()).max().unwrap_or(0).max(scrutinee.depth()),
+            Annot{term, ..} => term.depth(),
+            // The operators are not grouped yet: count the path through all of them
+            Infix{operands, operators, ..} => operators.len() + operands.iter().map(|operand| operand.depth()).max().unwrap_or(0),
+        }
+    }
+
+
+    /// Contract the leftmost outermost beta-redex `(x -> body) arg`, if any.
+    pub fn beta_step(&self) -> Option<Term> {
+        step(self, &|term| match term {
+            App{fun, arg, ..} => match &**fun {
+                Lambda{vname, body, ..} => Some(body.substitute(vname, arg)),
+                _ => None
+            },
+            _ => None
+        })
+    }
+
+    /// Contract the leftmost outermost eta-redex `x -> f x`, where `x` does not occur free in `f`, if any.
+    pub fn eta_step(&self) -> Option<Term> {
+        step(self, &|term| match term {
+            Lambda{vname, body, ..} => match &**body {
+                App{fun, arg, ..} if matches!(&**arg, Sym{vname:v, ..} if v == vname) && !fun.occurs_free(vname) => Some((**fun).clone()),
+                _ => None
+            },
+            _ => None
+        })
+    }
+}
+
+
+/// First name among `base`, `base1`, `base2`... which is not in `avoid`.
+pub fn fresh(base:&str, avoid:&HashSet<String>) -> String {
+    (0..)
+        .map(|i| if i == 0 { base.to_string() } else { format!("{}{}", base, i) })
+        .find(|n| !avoid.contains(n))
+        .unwrap()
+}
+
+
+/// Substitute in the `bodies` of the `binders`, renaming the binders which would capture a free variable of `value`.
+/// Give the new binders and the new bodies.
+fn substitute_under(binders:&[String], bodies:&[&Term], name:&str, value:&Term) -> (Vec<String>, Vec<Term>) {
+    let mut binders = binders.to_vec();
+    let mut bodies:Vec<Term> = bodies.iter().map(|body| (*body).clone()).collect();
+    // Shadowed, or nothing to substitute
+    if binders.iter().any(|b| b == name) || !bodies.iter().any(|body| body.occurs_free(name)) {
+        return (binders, bodies);
+    }
+    let captured = value.free_vars();
+    for i in 0..binders.len() {
+        // A binder shadowed by a later one is not referred to
+        let shadowed = binders[i+1..].contains(&binders[i]);
+        if shadowed || !captured.contains(&binders[i]) { continue; }
+        let mut avoid = captured.clone();
+        avoid.extend(binders.iter().cloned());
+        for body in &bodies { avoid.extend(body.free_vars()); }
+        let new = fresh(&binders[i], &avoid);
+        let var = Sym{vname:new.clone(), span:Span::default()};
+        bodies = bodies.iter().map(|body| body.substitute(&binders[i], &var)).collect();
+        binders[i] = new;
+    }
+    let bodies = bodies.iter().map(|body| body.substitute(name, value)).collect();
+    (binders, bodies)
+}
+
+
+/// `env` pairs the enclosing binders of both terms, the last ones being the innermost.
+fn alpha_eq_<'a>(left:&'a Term, right:&'a Term, env:&mut Vec<(&'a str, &'a str)>) -> bool {
+    match (left, right) {
+        (Sym{vname:l, ..}, Sym{vname:r, ..}) => {
+            let i = env.iter().rev().position(|(b, _)| b == l);
+            let j = env.iter().rev().position(|(_, b)| b == r);
+            match (i, j) {
+                (None, None) => l == r,
+                (i, j) => i == j
+            }
+        }
+        (Lambda{vname:l, body:lb, ..}, Lambda{vname:r, body:rb, ..}) => {
+            env.push((l, r));
+            let res = alpha_eq_(lb, rb, env);
+            env.pop();
+            res
+        }
+        (App{fun:lf, arg:la, ..}, App{fun:rf, arg:ra, ..}) => alpha_eq_(lf, rf, env) && alpha_eq_(la, ra, env),
+        (Num{value:l, ..}, Num{value:r, ..}) => l == r,
+        (Str{value:l, ..}, Str{value:r, ..}) => l == r,
+        (List{items:l, ..}, List{items:r, ..}) => all_alpha_eq(l, r, env),
+        (Rec{defs:ld, body:lb, ..}, Rec{defs:rd, body:rb, ..}) => {
+            if ld.len() != rd.len() { return false; }
+            let depth = env.len();
+            env.extend(ld.iter().zip(rd).map(|((l, _), (r, _))| (l.as_str(), r.as_str())));
+            let res = ld.iter().zip(rd).all(|((_, l), (_, r))| alpha_eq_(l, r, env)) && alpha_eq_(lb, rb, env);
+            env.truncate(depth);
+            res
+        }
+        (Case{scrutinee:ls, arms:la, ..}, Case{scrutinee:rs, arms:ra, ..}) => {
+            alpha_eq_(ls, rs, env) && la.len() == ra.len() && la.iter().zip(ra).all(|(l, r)| {
+                if l.ctor != r.ctor || l.binders.len() != r.binders.len() { return false; }
+                let depth = env.len();
+                env.extend(l.binders.iter().zip(&r.binders).map(|((l, _), (r, _))| (l.as_str(), r.as_str())));
+                let res = alpha_eq_(&l.body, &r.body, env);
+                env.truncate(depth);
+                res
+            })
+        }
+        (Annot{term:lt, ty:lty, ..}, Annot{term:rt, ty:rty, ..}) => lty == rty && alpha_eq_(lt, rt, env),
+        (Infix{operands:lo, operators:lops, ..}, Infix{operands:ro, operators:rops, ..}) =>
+            lops.iter().map(|(op, _)| op).eq(rops.iter().map(|(op, _)| op)) && all_alpha_eq(lo, ro, env),
+        _ => false
+    }
+}
+
+fn all_alpha_eq<'a>(left:&'a [Box<Term>], right:&'a [Box<Term>], env:&mut Vec<(&'a str, &'a str)>) -> bool {
+    left.len() == right.len() && left.iter().zip(right).all(|(l, r)| alpha_eq_(l, r, env))
+}
+
+
+/// Apply `rule` to the leftmost outermost subterm where it applies, if any.
+fn step(term:&Term, rule:&dyn Fn(&Term) -> Option<Term>) -> Option<Term> {
+    if let Some(res) = rule(term) {
+        return Some(res);
+    }
+    match term {
+        Sym{..} | Num{..} | Str{..} => None,
+        Lambda{vname, body, span} => step(body, rule).map(|body| Lambda{vname:vname.clone(), body:Box::new(body), span:*span}),
+        App{fun, arg, span} => step(fun, rule).map(|fun| App{fun:Box::new(fun), arg:arg.clone(), span:*span})
+            .or_else(|| step(arg, rule).map(|arg| App{fun:fun.clone(), arg:Box::new(arg), span:*span})),
+        List{items, span} => {
+            let mut items = items.clone();
+            step_first(&mut items, rule).then_some(List{items, span:*span})
+        }
+        Rec{defs, body, span} => {
+            let mut terms:Vec<Box<Term>> = defs.iter().map(|(_, def)| def.clone()).chain(Some(body.clone())).collect();
+            step_first(&mut terms, rule).then(|| {
+                let body = terms.pop().unwrap();
+                Rec{defs:defs.iter().map(|(vname, _)| vname.clone()).zip(terms).collect(), body, span:*span}
+            })
+        }
+        Case{scrutinee, arms, span} => match step(scrutinee, rule) {
+            Some(scrutinee) => Some(Case{scrutinee:Box::new(scrutinee), arms:arms.clone(), span:*span}),
+            None => {
+                let mut bodies:Vec<Box<Term>> = arms.iter().map(|arm| arm.body.clone()).collect();
+                step_first(&mut bodies, rule).then(|| Case{
+                    scrutinee:scrutinee.clone(),
+                    arms:arms.iter().zip(bodies).map(|(arm, body)| Arm{body, ..arm.clone()}).collect(),
+                    span:*span
+                })
+            }
+        },
+        Annot{term, ty, span} => step(term, rule).map(|term| Annot{term:Box::new(term), ty:ty.clone(), span:*span}),
+        Infix{operands, operators, span} => {
+            let mut operands = operands.clone();
+            step_first(&mut operands, rule).then(|| Infix{operands, operators:operators.clone(), span:*span})
+        }
+    }
+}
+
+/// Apply `step` to the first term of a sequence where the rule applies, in place. Return whether it applies.
+fn step_first(terms:&mut [Box<Term>], rule:&dyn Fn(&Term) -> Option<Term>) -> bool {
+    for term in terms.iter_mut() {
+        if let Some(res) = step(term, rule) {
+            **term = res;
+            return true;
+        }
+    }
+    false
+}
+
+
+
+// --- --- --- De Bruijn indices
+
+/// A pure lambda term with de Bruijn indices: a bound variable is the number of binders between it and its binder.
+/// `x -> y -> x y z` is `\ \ 1 0 z`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum DeBruijn {
+    // Bound variable
+    Var(usize),
+    // Free variable
+    Free(String),
+    // Abstraction
+    Lambda(Box<DeBruijn>),
+    // Application
+    App(Box<DeBruijn>, Box<DeBruijn>),
+}
+
+impl DeBruijn {
+    /// Convert back to a term with names, the binders being named canonically (see `Term::canonical`).
+    /// The free variables keep their names. The terms are not located: all the spans are `Span::default()`.
+    pub fn to_term(&self) -> Term {
+        // The binder at depth `d` is named `@d`, which is not a valid name:
+        // the canonical renaming then picks names that avoid the free variables.
+        self.to_term_(0).canonical()
+    }
+
+    fn to_term_(&self, depth:usize) -> Term {
+        let span = Span::default();
+        match self {
+            DeBruijn::Var(i) => Sym{vname:format!("@{}", depth - 1 - i), span},
+            DeBruijn::Free(name) => Sym{vname:name.clone(), span},
+            DeBruijn::Lambda(body) => Lambda{vname:format!("@{}", depth), body:Box::new(body.to_term_(depth+1)), span},
+            DeBruijn::App(fun, arg) => App{fun:Box::new(fun.to_term_(depth)), arg:Box::new(arg.to_term_(depth)), span},
+        }
+    }
+}
+
+impl fmt::Display for DeBruijn {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeBruijn::Var(i) => write!(f, "{}", i),
+            DeBruijn::Free(name) => write!(f, "{}", name),
+            DeBruijn::Lambda(body) => write!(f, "\\ {}", body),
+            DeBruijn::App(fun, arg) => {
+                match **fun {
+                    DeBruijn::Lambda(_) => write!(f, "({}) ", fun)?,
+                    _ => write!(f, "{} ", fun)?
+                }
+                match **arg {
+                    DeBruijn::Lambda(_) | DeBruijn::App(..) => write!(f, "({})", arg),
+                    _ => write!(f, "{}", arg)
+                }
+            }
+        }
+    }
+}
+
+/// `env` holds the enclosing binders, the last one being the innermost.
+fn to_de_bruijn_(term:&Term, env:&mut Vec<String>) -> Result<DeBruijn, Span> {
+    match term {
+        Sym{vname, ..} => Ok(match env.iter().rev().position(|b| b == vname) {
+            Some(i) => DeBruijn::Var(i),
+            None => DeBruijn::Free(vname.clone())
+        }),
+        Lambda{vname, body, ..} => {
+            env.push(vname.clone());
+            let body = to_de_bruijn_(body, env);
+            env.pop();
+            Ok(DeBruijn::Lambda(Box::new(body?)))
+        }
+        App{fun, arg, ..} => Ok(DeBruijn::App(Box::new(to_de_bruijn_(fun, env)?), Box::new(to_de_bruijn_(arg, env)?))),
+        Annot{term, ..} => to_de_bruijn_(term, env),
+        Num{span, ..} | List{span, ..} | Str{span, ..} | Rec{span, ..} | Case{span, ..} | Infix{span, ..} => Err(*span)
+    }
+}
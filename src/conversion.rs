@@ -5,6 +5,10 @@ use std::collections::HashMap;
 
 use eole_parser::ast::{*, Term::*};
 use crate::eole::{self, *, net::*, gc::GC};
+use crate::error::{self, EoleError, ErrorKind};
+use crate::encoding::{self, Encodings};
+use crate::dependency::Dependencies;
+use crate::import;
 
 use std::path::Path;
 use std::fs::{self, File};
@@ -24,79 +28,116 @@ enum VUsed {
 
 
 
-/// Convert a lambda expression into a network.
-//pub fn to_network<MyGC:GC>(sentences:&Vec<Sentence>, root:&Path) -> Net<MyGC> {
-pub fn to_network<MyGC:GC>(sentences:&Vec<Sentence>) -> Net<MyGC> {
-    let mut def:Vec<&SLet> = vec![];
-    let mut run:Vec<&SRun> = vec![];
-    let mut read:Vec<&SRead> = vec![];
+/// A sentence to evaluate, with the networks of its terms.
+pub enum Evaluation<'a, MyGC:GC> {
+    /// A term to run.
+    Run(&'a SRun, Net<MyGC>),
+    /// An assertion, with the networks of its left and right sides.
+    Assert(&'a SAssert, Net<MyGC>, Net<MyGC>),
+}
+
+
+/// Convert each term to run, and both sides of each assertion, into a network, in order.
+/// Each term is embedded under the definitions it needs (see `dependency::Dependencies`), and paired with its sentence in the result.
+/// The imports must have been resolved beforehand, see `import::Importer`,
+/// and the data types desugared, see `data::desugar`.
+/// All the unknown symbols and cyclic definitions are reported, including the ones in unused definitions.
+/// The literals are expanded with the given `encodings`.
+pub fn to_networks<MyGC:GC>(sentences:&[Sentence], encodings:Encodings) -> Result<Vec<Evaluation<'_, MyGC>>, Vec<EoleError>> {
+    let mut evaluated:Vec<&Sentence> = vec![];
 
     // Gather the sentences...
     for s in sentences {
         match s {
-            Sentence::Let(ldef) => def.push(ldef),
-            Sentence::Run(lrun) => run.push(lrun),
-            Sentence::Read(lread) => read.push(lread)
+            Sentence::Let(_) | Sentence::Rec(_) => {} // See the dependencies
+            Sentence::Run(_) | Sentence::Assert(_) => evaluated.push(s),
+            Sentence::Read(_) => {} // Resolved by the importer
+            Sentence::Data(_) => {} // Desugared into definitions, see data::desugar
+            Sentence::Infix(_) => {} // Resolved by the importer
+            Sentence::Pragma(_) => {} // Applied by the caller, see pragma::collect
         }
     };
 
-    // Read the imports
-    for r in &read {
-        match r {
-            // Import without prefix
-            SRead{path, name:None} => { unimplemented!(); }
+    // Check all the definitions once
+    let deps = Dependencies::new(sentences);
+    let mut errors = deps.cycles();
+    errors.extend(deps.check());
 
-            // Import with prefix
-            SRead{path, name:Some(n)} => { unimplemented!(); }
+    // Run
+    let mut nets = vec![];
+    for s in evaluated {
+        match s {
+            Sentence::Run(r) => if let Some(net) = to_network_program(&r.term, r.span, &deps, encodings, &mut errors) {
+                nets.push(Evaluation::Run(r, net));
+            },
+            Sentence::Assert(a) => {
+                let lhs = to_network_program(&a.lhs, a.span, &deps, encodings, &mut errors);
+                let rhs = to_network_program(&a.rhs, a.span, &deps, encodings, &mut errors);
+                if let (Some(lhs), Some(rhs)) = (lhs, rhs) {
+                    nets.push(Evaluation::Assert(a, lhs, rhs));
+                }
+            }
+            _ => {}
         }
-    };
+    }
 
+    if errors.is_empty() { Ok(nets) } else { Err(errors) }
+}
 
 
-    // Run
-    match run.first() {
-        None => Net::new(),
-        Some(SRun{term}) => {
-            // Create a new lambda term with all the definitions
-            let base = term.clone();
-            let lambda:Box<Term> = def.iter()
-                .rev()
-                .fold(base,
-                      |acc, d|{
-                          let SLet{vname, body} = d;
-                            Box::new(App{fun:Box::new(Lambda{vname:vname.clone(), body:acc}), arg:body.clone()})
-                      });
-            //println!("{}", &lambda);
-            // Convert
-            to_network1(&lambda)
+/// Convert a term of the sentence located at `span`, with the definitions it needs, into a network.
+/// Only report the errors located in the sentence: the errors in the definitions are reported once, by the caller.
+fn to_network_program<MyGC:GC>(term:&Term, span:Span, deps:&Dependencies, encodings:Encodings, errors:&mut Vec<EoleError>) -> Option<Net<MyGC>> {
+    // Check the term with all the definitions in scope, not only the ones it needs
+    let term_errors = deps.check_term(term);
+    if !term_errors.is_empty() {
+        errors.extend(term_errors);
+        return None;
+    }
+    // Create a new lambda term with the needed definitions
+    let lambda = deps.program(term);
+    // Convert
+    match to_network1(&lambda, encodings) {
+        Ok(net) => Some(net),
+        Err(e) => {
+            errors.extend(e.into_iter().filter(|e| e.span.is_some_and(|s| span.contains(s))));
+            None
         }
     }
-
 }
 
 
-fn to_network1<MyGC:GC>(term:&Box<Term>) -> Net<MyGC> {
+fn to_network1<MyGC:GC>(term:&Term, encodings:Encodings) -> Result<Net<MyGC>, Vec<EoleError>> {
     // Create a new empty net
     let mut n = Net::<MyGC>::new();
     let up = Net::<MyGC>::ROOT_VERTEX;
-    // TODO: Embed the rundef under all the definition
     // Convert:
     let mut env = HashMap::new();
-    to_network_(term, up, &mut n, &mut env);
-    n
+    let mut errors = vec![];
+    to_network_(term, up, &mut n, &mut env, encodings, &mut errors);
+    if errors.is_empty() { Ok(n) } else { Err(errors) }
 }
 
-fn to_network_<MyGC:GC>(term:&Box<Term>, up:Vertex, net: &mut Net<MyGC>, env: &mut HashMap<String, VUsed>) {
-    match **term {
+fn to_network_<MyGC:GC>(term:&Term, up:Vertex, net: &mut Net<MyGC>, env: &mut HashMap<String, VUsed>, encodings:Encodings, errors: &mut Vec<EoleError>) {
+    match *term {
 
         // --- Symbol
-        Sym {ref vname} => {
+        Sym {ref vname, span} => {
             // Check if the variable has been used.
-            match env.get(vname).expect(&format!("Symbol {} not found.", vname)) {
+            match env.get(vname) {
+
+                // The wildcard cannot be used
+                None if vname == "_" => errors.push(EoleError::new(ErrorKind::Wildcard, span)),
+
+                // Not found: record the error, suggesting a name in scope.
+                None => {
+                    let suggestion = error::suggest(vname, env.keys());
+                    errors.push(EoleError::new(ErrorKind::UnknownSymbol{name:vname.clone(), suggestion}, span));
+                }
 
                 // Never used before: link 'up->λ/Aux2' and update the environment.
                 // 'up' becomes the "user port" of the binder.
-                VUsed::Unused{binding_vertex} => {
+                Some(VUsed::Unused{binding_vertex}) => {
                     let p = *binding_vertex;
                     env.insert(vname.clone(), VUsed::Used{binding_vertex:p, user_vertex:up});
                     // Do the binding
@@ -107,7 +148,7 @@ fn to_network_<MyGC:GC>(term:&Box<Term>, up:Vertex, net: &mut Net<MyGC>, env: &m
                 //        Plug up on the fan                        : 'up -> Fan In/Aux1'
                 //        Plug the former user on the fan           : 'user_vertex -> Fan In/Aux2'
                 //        Update the environment: 'Fan In/Main' is the new user of the vertex.
-                VUsed::Used{binding_vertex, user_vertex} => {
+                Some(VUsed::Used{binding_vertex, user_vertex}) => {
                     let p = *binding_vertex;
                     let u = *user_vertex;
                     //let (user_v, user_p) = as_tuple(u);
@@ -123,17 +164,20 @@ fn to_network_<MyGC:GC>(term:&Box<Term>, up:Vertex, net: &mut Net<MyGC>, env: &m
             }
         }
 
+        // Wildcard abstraction: directly create an unused binder, without declaring anything
+        Lambda {ref vname, ref body, ..} if vname == "_" => {
+            let abs = net.new_abs(vname.clone(), false);
+            to_network_(body, mkv(abs, 1), net, env, encodings, errors);
+            net.create_edge(up, main(abs));
+        }
+
         // Abstraction
-        Lambda {ref vname, ref body} => {
-            // Checking and declaring
-            if env.contains_key(vname) {
-                panic!(format!("Variable {} already declared", vname));
-            }
+        Lambda {ref vname, ref body, ..} => {
             let abs = net.new_abs(vname.clone(), true); // Used by default
-            // Update the environment
-            env.insert(vname.clone(), VUsed::Unused{binding_vertex:mkv(abs, 2)});
+            // Update the environment, shadowing any variable with the same name
+            let shadowed = env.insert(vname.clone(), VUsed::Unused{binding_vertex:mkv(abs, 2)});
             // Go in the body with 'up = λ/Aux1'
-            to_network_(body, mkv(abs, 1), net, env);
+            to_network_(body, mkv(abs, 1), net, env, encodings, errors);
             // Check if the variable is used: mark it has unused if not.
             match env.get(vname).unwrap() {
                 VUsed::Used{..} => (),// Nothing to do
@@ -141,8 +185,8 @@ fn to_network_<MyGC:GC>(term:&Box<Term>, up:Vertex, net: &mut Net<MyGC>, env: &m
                     net.nodes[abs].0 = NodeKind::CstrK(CstrK::Abs(vname.clone(), false));
                 }
             };
-            // Clean the env
-            env.remove(vname);
+            // Clean the env, restoring the shadowed variable
+            restore(env, vname, shadowed);
             // Link the 'up->λ/Main'
             net.create_edge(up, main(abs));
         }
@@ -152,15 +196,89 @@ fn to_network_<MyGC:GC>(term:&Box<Term>, up:Vertex, net: &mut Net<MyGC>, env: &m
         //  'up     -> @/Aux1'
         //  '@/M    -> fun'
         //  '@/Aux2 -> arg'
-        App {ref fun, ref arg} => {
+        App {ref fun, ref arg, ..} => {
             // Create the node
             let app = net.new_app();
             // Update the linking
             net.create_edge(up, mkv(app, 1));
             // Launch recursively with the good 'up'
-            to_network_(fun, main(app), net, env);
-            to_network_(arg, mkv(app, 2), net, env);
+            to_network_(fun, main(app), net, env, encodings, errors);
+            to_network_(arg, mkv(app, 2), net, env, encodings, errors);
+        }
+
+        // Recursive definitions
+        // Convert '(λf1. ... λfn. body) f1 ... fn' where the definitions are visible in their own bodies:
+        // a definition used by the group is shared by a stem fan in, one of its users being the abstraction 'λfi'
+        // and the other one the recursive references. This creates a cycle in the network.
+        //  'up         -> @n/Aux1'
+        //  '@i+1/M     -> @i/Aux1'
+        //  '@1/M       -> λf1. ... λfn. body'
+        //  '@i/Aux2    -> Fan In i/Aux1'         (or directly the definition if it is not recursive)
+        //  'Fan In i/M -> definition i'
+        //  'uses of fi -> Fan In i/Aux2'
+        Rec {ref defs, ref body, span} => {
+            // Create the applications
+            let mut app_up = up;
+            let mut apps = vec![];
+            for _ in defs.iter().rev() {
+                let app = net.new_app();
+                net.create_edge(app_up, mkv(app, 1));
+                app_up = main(app);
+                apps.push(app);
+            }
+            apps.reverse();
+            // Abstract the body over the definitions
+            let lambda = defs.iter().rev().fold(body.clone(), |acc, (vname, _)| Box::new(Lambda{vname:vname.clone(), body:acc, span}));
+            to_network_(&lambda, app_up, net, env, encodings, errors);
+            // Share the recursive definitions, and declare them for the bodies
+            let mut ups = vec![];
+            let mut shadowed = vec![];
+            for ((vname, _), app) in defs.iter().zip(apps.iter()) {
+                if defs.iter().any(|(_, def)| def.occurs_free(vname)) {
+                    let fin = net.new_fin(FIStatus::Stem);
+                    net.create_edge(mkv(*app, 2), mkv(fin, 1));
+                    shadowed.push((vname, env.insert(vname.clone(), VUsed::Unused{binding_vertex:mkv(fin, 2)})));
+                    ups.push(main(fin));
+                } else {
+                    ups.push(mkv(*app, 2));
+                }
+            }
+            // Convert the definitions
+            for ((_, def), def_up) in defs.iter().zip(ups) {
+                to_network_(def, def_up, net, env, encodings, errors);
+            }
+            // Clean the env, restoring the shadowed variables
+            for (vname, status) in shadowed.into_iter().rev() { restore(env, vname, status); }
         }
+
+        // Numeric literal
+        // Expand the numeral, which is a closed term: convert it in its own environment.
+        Num {value, span} => {
+            let numeral = encoding::numeral(encodings.numerals, value, span);
+            to_network_(&numeral, up, net, &mut HashMap::new(), encodings, errors);
+        }
+
+        // List literal
+        // The items are converted in the current environment: the binders of the list do not capture them.
+        List {ref items, span} => {
+            let list = encoding::list(encodings.lists, items.clone(), span);
+            to_network_(&list, up, net, env, encodings, errors);
+        }
+
+        // String literal, a closed term
+        Str {ref value, span} => {
+            let string = encoding::string(encodings, value, span);
+            to_network_(&string, up, net, &mut HashMap::new(), encodings, errors);
+        }
+
+        // Type annotation, only used by the type inference
+        Annot {ref term, ..} => to_network_(term, up, net, env, encodings, errors),
+
+        // Pattern matching
+        Case {..} => unreachable!("case expressions are desugared before the conversion, see data::desugar"),
+
+        // Operators
+        Infix {span, ..} => import::unresolved(span),
     }
 
 }
@@ -176,26 +294,51 @@ fn to_network_<MyGC:GC>(term:&Box<Term>, up:Vertex, net: &mut Net<MyGC>, env: &m
 
 
 
+/// Remove a variable from the environment, restoring the variable it was shadowing (if any).
+fn restore(env:&mut HashMap<String, VUsed>, vname:&str, shadowed:Option<VUsed>) {
+    match shadowed {
+        None => { env.remove(vname); }
+        Some(status) => { env.insert(vname.to_string(), status); }
+    }
+}
+
+
+
+
+
+/// Reasons for which a network is not read back.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum ReadbackError {
+    /// The term is deeper than the lambda limit.
+    Limit,
+    /// The network still contains a cycle which was not unfolded, e.g. the one of a recursive definition (`rec`)
+    /// under a lazy strategy: the term is infinite. See `Cycles`.
+    Cyclic,
+}
+
 /// Public conversion function.
 /// Assume that the network is plugged on 'net_root', which is the case if you used the 'to_net' function.
-pub fn from_net<MyGC:GC>(net:&Net<MyGC>, limit:Option<usize>) -> Option<Box<Term>> {
+/// The variables are renamed canonically (see `Term::canonical`), so that the result does not depend on the layout of the network.
+pub fn from_net<MyGC:GC>(net:&Net<MyGC>, limit:Option<usize>) -> Result<Box<Term>, ReadbackError> {
     let mut history = vec![];
-    from_net_(net, &mut history, Net::<MyGC>::ROOT_VERTEX, limit)
+    let mut cycles = Cycles::default();
+    from_net_(net, &mut history, &mut cycles, Net::<MyGC>::ROOT_VERTEX, limit).map(|t| Box::new(t.canonical()))
 }
 
-/// Record the label of a crossed fan in and the port used to enterd it.
-type FanStack = Vec<(Label, Port)>;
-
-/// Get the port for a label in the stack of fan_in.
-/// If found, remove the item from the stack
-/// If not found, abort
-//fn lookup_port(stack:&mut FanStack, lb:Label) -> u8 {
-//    let idx:usize = stack.iter().position(|&itm| itm.0 == lb).expect("No matching label found");
-//    let res:u8 = stack.get(idx).unwrap().1;
-//    stack.remove(idx);
-//    res
-//}
+/// Detection of the infinite read backs.
+/// The read back of a vertex only depends on the labeled fan ins of the history not matched yet by a fan out,
+/// the pending ones: if the read back enters a fan in again, and none of the fan ins pending the first time
+/// has been matched since, it makes the same moves again, forever.
+#[derive(Default)]
+struct Cycles {
+    /// Indices in the history of the pending fan ins.
+    pending:Vec<usize>,
+    /// The fan ins entered on the way to the current vertex, with the pending fan ins at that time.
+    visits:Vec<(Vertex, Vec<usize>)>,
+}
 
+/// Record the label of a crossed fan in and the port by which it was entered.
+type FanStack = Vec<(Label, Port)>;
 
 /// Get the port for a label in the stack of fan_in.
 /// If found, remove the item from the stack
@@ -217,14 +360,14 @@ fn lookup_port(stack:&mut FanStack, lb:Label) -> Option<Port> {
 /// i.e. both the source and target vertex.
 /// The source vertex is the argument, and the target vertex (which represent the current node)
 /// is looked up in the graph.
-fn from_net_<MyGC:GC>(net:&Net<MyGC>, history:&mut Vec<(Vertex,NodeKind)>, src:Vertex, limit:Option<usize>) -> Option<Box<Term>> {
+fn from_net_<MyGC:GC>(net:&Net<MyGC>, history:&mut Vec<(Vertex,NodeKind)>, cycles:&mut Cycles, src:Vertex, limit:Option<usize>) -> Result<Box<Term>, ReadbackError> {
     // Check the limit
     let lim = match limit {
         None => Some(None),
         Some(l) => if l == 0 { None } else { Some(Some(l-1)) }
     };
     // Build the term if limit is acceptable
-    lim.and_then(|new_limit|{
+    lim.ok_or(ReadbackError::Limit).and_then(|new_limit|{
         // Access the target of src
         let tgt = net.follow(src);
         let (tgt_index, tgt_port) = tgt.as_tuple();
@@ -241,22 +384,26 @@ fn from_net_<MyGC:GC>(net:&Net<MyGC>, history:&mut Vec<(Vertex,NodeKind)>, src:V
                     let bname = String::from(vname) + &tgt_index.to_string();
                     if tgt_port.0 == 0 {
                         // Entering by 'up' (Main): analyse the body and create the abstraction.
-                        from_net_(net, history, mkv(tgt_index, 1), new_limit)
-                        .map(|body|{ Box::new(Lambda{vname:bname, body}) })
+                        from_net_(net, history, cycles, mkv(tgt_index, 1), new_limit)
+                        .map(|body|{ Box::new(Lambda{vname:bname, body, span:Span::default()}) })
                     } else {
-                        Some(Box::new(Sym{vname:bname}))
+                        Ok(Box::new(Sym{vname:bname, span:Span::default()}))
                     }
                 }
 
                 // Fan out: lookup the associated port and follow it.
                 CstrK::FanOut(label) => {
                     assert_eq!(tgt_port.0, 0, "Should not enter a fan out node through an auxiliary port");
-                    match reduce::get_matching_fan(net, *label, &history){
-                        None => Some(Box::new(Sym{vname:String::from("∆")})),
+                    match reduce::get_matching_fan(net, *label, history){
+                        None => Ok(Box::new(Sym{vname:String::from("∆"), span:Span::default()})),
                         Some(port) => {
+                            // The matched fan in is the last pending one with the label
+                            let matched = cycles.pending.iter().rposition(|&i| matches!(history[i].1, NodeKind::DstrK(DstrK::FanIn(FIStatus::Labeled(l))) if l == *label));
+                            let fan_in = matched.map(|idx| cycles.pending.remove(idx));
                             history.push((tgt, tgt_node.0.clone()));
-                            let res = from_net_(net, history, mkv(tgt_index, port.0), new_limit);
+                            let res = from_net_(net, history, cycles, mkv(tgt_index, port.0), new_limit);
                             history.pop();
+                            if let (Some(idx), Some(i)) = (matched, fan_in) { cycles.pending.insert(idx, i); }
                             res
                         }
                     }
@@ -270,10 +417,10 @@ fn from_net_<MyGC:GC>(net:&Net<MyGC>, history:&mut Vec<(Vertex,NodeKind)>, src:V
                 // Should not be entered by the port 1
                 DstrK::Apply => {
                     assert_eq!(tgt_port.0, 1, "Should enter an app through aux1 port");
-                    from_net_(net, history, main(tgt_index), new_limit)
+                    from_net_(net, history, cycles, main(tgt_index), new_limit)
                     .and_then(|fun|
-                        from_net_(net, history, mkv(tgt_index, 2), new_limit)
-                        .and_then(|arg| Some(Box::new(App{fun,arg})) )
+                        from_net_(net, history, cycles, mkv(tgt_index, 2), new_limit)
+                        .map(|arg| Box::new(App{fun, arg, span:Span::default()}))
                     )
                 }
 
@@ -281,17 +428,26 @@ fn from_net_<MyGC:GC>(net:&Net<MyGC>, history:&mut Vec<(Vertex,NodeKind)>, src:V
                 // Should not be entered by the main port
                 DstrK::FanIn(in_status) => {
                     assert_ne!(tgt_port.0, 0, "Should not enter a fan in node through the main port");
+                    let pending = &cycles.pending;
+                    if cycles.visits.iter().any(|(v, before)| *v == tgt && pending.starts_with(before)) {
+                        return Err(ReadbackError::Cyclic);
+                    }
+                    cycles.visits.push((tgt, pending.clone()));
                     // Record the pair label/port if it is a paired fan in
-                    match in_status {
+                    let res = match in_status {
                         FIStatus::Labeled(l) => {
+                            cycles.pending.push(history.len());
                             history.push((tgt, tgt_node.0.clone()));
                             // Exit by the main port
-                            let res = from_net_(net, history, main(tgt_index), new_limit);
+                            let res = from_net_(net, history, cycles, main(tgt_index), new_limit);
                             history.pop();
+                            cycles.pending.pop();
                             res
                         }
-                        FIStatus::Stem => from_net_(net, history, main(tgt_index), new_limit)
-                    }
+                        FIStatus::Stem => from_net_(net, history, cycles, main(tgt_index), new_limit)
+                    };
+                    cycles.visits.pop();
+                    res
                 }
             }// End of Destr(kind) => match kind
         }// End of match &tgtNode.0
@@ -308,6 +464,7 @@ fn from_net_<MyGC:GC>(net:&Net<MyGC>, history:&mut Vec<(Vertex,NodeKind)>, src:V
 
 
 
+
 pub fn to_graphviz<MyGC:GC>(net:&Net<MyGC>, output: &mut File, step:usize, as_string:String, extra:String){
 
     // Intro text
@@ -341,12 +498,12 @@ digraph graph{} {{
                 match &c {
                     CstrK::Abs(s,b) => {
                         let s = format!("{} λ{}{}", idx, String::from(if *b { "" } else {"●  "}), s);
-                        write!(output, "    {} [color=\"{}\", shape=egg, label=\"{}\"];\n", get_node_name(idx, &n.0),col, s);
+                        writeln!(output, "    {} [color=\"{}\", shape=egg, label=\"{}\"];", get_node_name(idx, &n.0),col, s);
                     }
 
                     CstrK::FanOut(l) => {
                         let s = format!("{} ▲ {}", idx, l);
-                        write!(output, "    {} [color=\"{}\", shape=septagon, label=\"{}\"];\n", get_node_name(idx, &n.0), col, s);
+                        writeln!(output, "    {} [color=\"{}\", shape=septagon, label=\"{}\"];", get_node_name(idx, &n.0), col, s);
                     }
                 }
             }
@@ -355,12 +512,12 @@ digraph graph{} {{
                 match &d {
                     DstrK::Apply => {
                         let s = format!("{} @", idx);
-                        write!(output, "    {} [color=\"{}\", shape=ellipse, label=\"{}\"];\n", get_node_name(idx, &n.0), col, s);
+                        writeln!(output, "    {} [color=\"{}\", shape=ellipse, label=\"{}\"];", get_node_name(idx, &n.0), col, s);
                     }
 
                     DstrK::FanIn(status) => {
-                        let s = format!("{} ▼ {}", idx, if let FIStatus::Labeled(l) = status { format!("{}", l)} else {format!("stem")} );
-                        write!(output, "    {} [color=\"{}\", shape=septagon, orientation=180, label=\"{}\"];\n", get_node_name(idx, &n.0), col, s);
+                        let s = format!("{} ▼ {}", idx, if let FIStatus::Labeled(l) = status { format!("{}", l)} else {"stem".to_string()} );
+                        writeln!(output, "    {} [color=\"{}\", shape=septagon, orientation=180, label=\"{}\"];", get_node_name(idx, &n.0), col, s);
                     }
                 }
             }
@@ -368,7 +525,7 @@ digraph graph{} {{
     }
 
     // Edges
-    write!(output, "\n    // --- --- --- Edges\n");
+    writeln!(output, "\n    // --- --- --- Edges");
     for (idx, n) in net.nodes.iter().enumerate() {
 
         let targets = n.1;
@@ -422,10 +579,10 @@ digraph graph{} {{
         }
     }
 
-    write!(output, "\n    // --- --- --- Extra\n");
+    writeln!(output, "\n    // --- --- --- Extra");
     write!(output, "{}", extra);
 
-    write!(output, "\n\n}}\n\n");
+    writeln!(output, "\n\n}}\n");
 }
 
 
@@ -530,14 +687,14 @@ fn write_edge<MyGC:GC>(output: &mut File, net: &Net<MyGC>, src:Vertex, tgt:Verte
 
     // Special case for identity
     if src_idx == tgt_idx {
-        write!(output, "    {}:s -> {}:s [{}];\n",
+        writeln!(output, "    {}:s -> {}:s [{}];",
             get_node_name(src_idx, &src_node.0),
             get_node_name(tgt_idx, &tgt_node.0),
             get_edge_attr(src, tgt, get_color(net.nb_special_nodes, src_idx, &src_node.0))
         );
 
     } else {
-        write!(output, "    {}{} -> {}{} [{}];\n",
+        writeln!(output, "    {}{} -> {}{} [{}];",
             get_node_name(src_idx, &src_node.0),
             get_compass(net, src),
             get_node_name(tgt_idx, &tgt_node.0),
@@ -0,0 +1,266 @@
+//! # Algebraic data types
+//! Desugar the data types and the `case` expressions into plain lambda terms, and decode the results.
+//!
+//! ## Scott encoding
+//! A value is a function taking one continuation per constructor of its type, and calling the one of its constructor with its fields.
+//! With `data List = Nil | Cons h t.`:
+//!   * `Nil = c1 c2 -> c1.`
+//!   * `Cons = x1 x2 -> c1 c2 -> c2 x1 x2.`
+//!
+//! Matching a value is then an application to the arms, in the order of the declaration:
+//! `case l of Cons h t -> b | Nil -> a` becomes `l a (h t -> b)`.
+//! A `_` arm stands for all the constructors without an arm, ignoring their fields.
+//!
+//! ## Decoding
+//! A result having the shape of a constructor applied to its fields is printed with the name of the constructor.
+//! Different types can share the same shapes (e.g. `Nil` and `True` in `data Bool = True | False.`):
+//! the first declared type wins.
+//!
+//! The numerals, lists and strings can also be decoded as literals, see `encoding`. They are decoded before the data types.
+//! A non-empty list of numerals which are all printable characters is printed as a string.
+
+use eole_parser::ast::{*, Term::*};
+use crate::error::{self, EoleError, ErrorKind};
+use crate::encoding::{self, Encodings};
+use crate::import;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+
+/// Get the data types declared by the sentences, in order.
+pub fn declarations(sentences:&[Sentence]) -> Vec<SData> {
+    sentences.iter()
+        .filter_map(|s| match s { Sentence::Data(d) => Some(d.clone()), _ => None })
+        .collect()
+}
+
+
+/// Replace the data types by the definitions of their constructors, and the `case` expressions by applications.
+/// The imports must have been resolved beforehand, so that all the data types are known.
+/// All the errors in the `case` expressions are reported.
+pub fn desugar(sentences:&[Sentence]) -> Result<Vec<Sentence>, Vec<EoleError>> {
+    let types = declarations(sentences);
+    let ctors:HashMap<&str, (&SData, usize)> = types.iter()
+        .flat_map(|d| d.ctors.iter().enumerate().map(move |(i, c)| (c.name.as_str(), (d, i))))
+        .collect();
+
+    let mut res = vec![];
+    let mut errors = vec![];
+    for s in sentences {
+        match s {
+            Sentence::Data(d) => res.extend(d.ctors.iter().enumerate().map(|(i, _)| Sentence::Let(constructor(d, i)))),
+            Sentence::Let(d) => res.push(Sentence::Let(desugar_def(d, &ctors, &mut errors))),
+            Sentence::Rec(SRec{defs, span}) => res.push(Sentence::Rec(SRec{
+                defs:defs.iter().map(|d| desugar_def(d, &ctors, &mut errors)).collect(),
+                span:*span
+            })),
+            Sentence::Run(r) => res.push(Sentence::Run(SRun{term:desugar_term(&r.term, &ctors, &mut errors), ..r.clone()})),
+            Sentence::Assert(a) => res.push(Sentence::Assert(SAssert{
+                lhs:desugar_term(&a.lhs, &ctors, &mut errors),
+                rhs:desugar_term(&a.rhs, &ctors, &mut errors),
+                span:a.span
+            })),
+            Sentence::Read(_) | Sentence::Infix(_) | Sentence::Pragma(_) => res.push(s.clone())
+        }
+    }
+
+    if errors.is_empty() { Ok(res) } else { Err(errors) }
+}
+
+
+/// Definition of the `i`-th constructor of a data type: `x1 ... xk -> c1 ... cn -> ci x1 ... xk`.
+/// The definition is a closed term: the generated names cannot capture anything.
+fn constructor(d:&SData, i:usize) -> SLet {
+    let ctor = &d.ctors[i];
+    let span = ctor.span;
+    let fields:Vec<String> = (1..=ctor.fields.len()).map(|k| format!("x{}", k)).collect();
+    let selectors:Vec<String> = (1..=d.ctors.len()).map(|k| format!("c{}", k)).collect();
+    let body = fields.iter().fold(sym(&selectors[i], span), |acc, f| Box::new(App{fun:acc, arg:sym(f, span), span}));
+    let body = fields.iter().chain(selectors.iter()).rev()
+        .fold(body, |acc, vname| Box::new(Lambda{vname:vname.clone(), body:acc, span}));
+    SLet{vname:ctor.name.clone(), body, span}
+}
+
+fn desugar_def(d:&SLet, ctors:&HashMap<&str, (&SData, usize)>, errors:&mut Vec<EoleError>) -> SLet {
+    SLet{body:desugar_term(&d.body, ctors, errors), ..d.clone()}
+}
+
+/// Replace the `case` expressions of a term by applications of their scrutinee to their arms.
+fn desugar_term(term:&Term, ctors:&HashMap<&str, (&SData, usize)>, errors:&mut Vec<EoleError>) -> Box<Term> {
+    Box::new(match term {
+        Sym{..} | Num{..} | Str{..} => term.clone(),
+
+        List{items, span} => List{items:items.iter().map(|item| desugar_term(item, ctors, errors)).collect(), span:*span},
+
+        Lambda{vname, body, span} => Lambda{vname:vname.clone(), body:desugar_term(body, ctors, errors), span:*span},
+
+        App{fun, arg, span} => App{
+            fun:desugar_term(fun, ctors, errors),
+            arg:desugar_term(arg, ctors, errors),
+            span:*span
+        },
+
+        Rec{defs, body, span} => Rec{
+            defs:defs.iter().map(|(vname, def)| (vname.clone(), desugar_term(def, ctors, errors))).collect(),
+            body:desugar_term(body, ctors, errors),
+            span:*span
+        },
+
+        Annot{term, ty, span} => Annot{term:desugar_term(term, ctors, errors), ty:ty.clone(), span:*span},
+
+        Infix{span, ..} => import::unresolved(*span),
+
+        Case{scrutinee, arms, span} => {
+            let scrutinee = desugar_term(scrutinee, ctors, errors);
+            // The type is given by the first constructor
+            let data = match arms.iter().find(|a| a.ctor != "_") {
+                Some(arm) => match ctors.get(arm.ctor.as_str()) {
+                    Some((data, _)) => *data,
+                    None => {
+                        let names:Vec<String> = ctors.keys().map(|c| c.to_string()).collect();
+                        let suggestion = error::suggest(&arm.ctor, &names);
+                        errors.push(EoleError::new(ErrorKind::UnknownConstructor{name:arm.ctor.clone(), suggestion}, arm.span));
+                        return scrutinee;
+                    }
+                },
+                // Only a wildcard: the scrutinee is not used
+                None => return desugar_term(&arms[0].body, ctors, errors)
+            };
+
+            // Sort the arms by constructor
+            let mut handlers:Vec<Option<Box<Term>>> = vec![None; data.ctors.len()];
+            let mut wildcard:Option<&Arm> = None;
+            for arm in arms {
+                if arm.ctor == "_" {
+                    if wildcard.is_some() {
+                        errors.push(EoleError::new(ErrorKind::DuplicateArm{name:arm.ctor.clone()}, arm.span));
+                    }
+                    wildcard = Some(arm);
+                    continue;
+                }
+                let i = match ctors.get(arm.ctor.as_str()) {
+                    Some((d, i)) if std::ptr::eq(*d, data) => *i,
+                    Some(_) => {
+                        errors.push(EoleError::new(ErrorKind::ConstructorMismatch{name:arm.ctor.clone(), data:data.name.clone()}, arm.span));
+                        continue;
+                    }
+                    None => {
+                        let suggestion = error::suggest(&arm.ctor, data.ctors.iter().map(|c| &c.name));
+                        errors.push(EoleError::new(ErrorKind::UnknownConstructor{name:arm.ctor.clone(), suggestion}, arm.span));
+                        continue;
+                    }
+                };
+                let expected = data.ctors[i].fields.len();
+                if handlers[i].is_some() {
+                    errors.push(EoleError::new(ErrorKind::DuplicateArm{name:arm.ctor.clone()}, arm.span));
+                    continue;
+                }
+                if arm.binders.len() != expected {
+                    errors.push(EoleError::new(ErrorKind::ConstructorArity{name:arm.ctor.clone(), expected, found:arm.binders.len()}, arm.span));
+                }
+                handlers[i] = Some(abstract_over(arm.binders.clone(), desugar_term(&arm.body, ctors, errors), arm.span.hi));
+            }
+
+            // Fill the missing arms with the wildcard, ignoring the fields
+            let mut missing = vec![];
+            for (handler, ctor) in handlers.iter_mut().zip(&data.ctors) {
+                if handler.is_some() { continue; }
+                match wildcard {
+                    Some(arm) => {
+                        let binders = vec![(String::from("_"), arm.span); ctor.fields.len()];
+                        *handler = Some(abstract_over(binders, desugar_term(&arm.body, ctors, errors), arm.span.hi));
+                    }
+                    None => missing.push(ctor.name.clone())
+                }
+            }
+            if !missing.is_empty() {
+                errors.push(EoleError::new(ErrorKind::MissingArms{data:data.name.clone(), missing}, *span));
+            }
+
+            return handlers.into_iter().flatten().fold(scrutinee, |acc, h| Box::new(App{fun:acc, arg:h, span:*span}));
+        }
+    })
+}
+
+
+/// Print the values of the data types with their constructors, e.g. `Cons a Nil` instead of `a->b->b a (c->d->c)`.
+/// With `literals`, the numerals, lists and strings of these encodings are printed as literals.
+/// The result is renamed canonically, see `Term::canonical`.
+pub fn decode(term:&Term, types:&[SData], literals:Option<Encodings>) -> Term {
+    decode_(term, types, literals).canonical()
+}
+
+fn decode_(term:&Term, types:&[SData], literals:Option<Encodings>) -> Term {
+    let span = term.span();
+    if let Some(encodings) = literals {
+        if let Some(value) = encoding::numeral_value(encodings.numerals, term) {
+            return Num{value, span};
+        }
+        if let Some(items) = encoding::list_items(encodings.lists, term) {
+            let items:Vec<Box<Term>> = items.into_iter().map(|item| Box::new(decode_(item, types, literals))).collect();
+            return match as_string(&items) {
+                Some(value) => Str{value, span},
+                None => List{items, span}
+            };
+        }
+    }
+    if let Some((ctor, fields)) = types.iter().find_map(|d| as_constructor(term, d)) {
+        return fields.into_iter().fold(Sym{vname:ctor.to_string(), span}, |acc, f| App{
+            fun:Box::new(acc),
+            arg:Box::new(decode_(f, types, literals)),
+            span
+        });
+    }
+    match term {
+        Lambda{vname, body, span} => Lambda{vname:vname.clone(), body:Box::new(decode_(body, types, literals)), span:*span},
+        App{fun, arg, span} => App{fun:Box::new(decode_(fun, types, literals)), arg:Box::new(decode_(arg, types, literals)), span:*span},
+        _ => term.clone()
+    }
+}
+
+/// Read a non-empty list of decoded numerals as a string, if they are all printable characters.
+fn as_string(items:&[Box<Term>]) -> Option<String> {
+    if items.is_empty() { return None; }
+    items.iter()
+        .map(|item| match **item {
+            Num{value, ..} => u32::try_from(value).ok()
+                .and_then(char::from_u32)
+                .filter(|c| !c.is_control() || *c == '\n' || *c == '\t'),
+            _ => None
+        })
+        .collect()
+}
+
+/// Check if a term is a value of the data type `d`: `c1 ... cn -> ci f1 ... fk`, where no `c` occurs in the fields.
+/// Return the constructor and the fields.
+fn as_constructor<'a, 'b>(term:&'a Term, d:&'b SData) -> Option<(&'b str, Vec<&'a Term>)> {
+    // The continuations
+    let mut selectors = vec![];
+    let mut body = term;
+    for _ in &d.ctors {
+        match body {
+            Lambda{vname, body:b, ..} => { selectors.push(vname); body = b; }
+            _ => return None
+        }
+    }
+    // The call to one of them
+    let mut fields = vec![];
+    while let App{fun, arg, ..} = body {
+        fields.push(&**arg);
+        body = fun;
+    }
+    fields.reverse();
+    let i = match body {
+        Sym{vname, ..} => selectors.iter().rposition(|s| *s == vname)?,
+        _ => return None
+    };
+    let ctor = &d.ctors[i];
+    let closed = fields.iter().all(|f| selectors.iter().all(|s| !f.occurs_free(s)));
+    if ctor.fields.len() == fields.len() && closed { Some((&ctor.name, fields)) } else { None }
+}
+
+
+fn sym(vname:&str, span:Span) -> Box<Term> {
+    Box::new(Sym{vname:vname.to_string(), span})
+}
@@ -0,0 +1,226 @@
+//! # Dependencies between definitions
+//! Build the dependency graph of the definitions, so that each term to run only includes the definitions it needs.
+//!
+//! The nodes of the graph are the definitions: a `let` definition, or a whole group of `rec` definitions.
+//! A definition depends on the definitions whose names occur free in its body (or bodies).
+//! Hence, definitions can be written in any order: a definition can use a definition written after it.
+//!
+//! The definitions needed by a term are sorted so that a definition comes after the ones it uses,
+//! keeping the source order otherwise. The unused definitions are left out of the term, and of its network.
+//!
+//! A cycle between definitions can only be built with `rec`: other cycles are reported as errors.
+
+use eole_parser::ast::*;
+use crate::error::{self, EoleError, ErrorKind};
+use crate::import;
+
+use std::collections::{HashMap, HashSet};
+
+
+/// Dependency graph of the definitions.
+pub struct Dependencies<'a> {
+    /// The definitions (`let` or `rec` sentences), in source order.
+    defs:Vec<&'a Sentence>,
+    /// Index of the definition of each name.
+    owner:HashMap<&'a str, usize>,
+    /// Definitions used by each definition, in source order.
+    uses:Vec<Vec<usize>>,
+}
+
+impl<'a> Dependencies<'a> {
+
+    /// Build the dependency graph of the definitions among the sentences.
+    pub fn new(sentences:&'a [Sentence]) -> Dependencies<'a> {
+        let defs:Vec<&Sentence> = sentences.iter()
+            .filter(|s| matches!(s, Sentence::Let(_) | Sentence::Rec(_)))
+            .collect();
+        let owner:HashMap<&str, usize> = defs.iter().enumerate()
+            .flat_map(|(i, s)| names(s).into_iter().map(move |n| (n, i)))
+            .collect();
+        let uses = defs.iter().enumerate()
+            .map(|(i, s)| {
+                let mut uses:Vec<usize> = bodies(s).iter()
+                    .flat_map(|b| b.free_vars())
+                    .filter_map(|n| owner.get(n.as_str()).copied())
+                    // The definitions of a 'rec' group can use each other
+                    .filter(|j| *j != i || matches!(s, Sentence::Let(_)))
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .collect();
+                uses.sort_unstable();
+                uses
+            })
+            .collect();
+        Dependencies{ defs, owner, uses }
+    }
+
+    /// Get the definitions needed by a term, directly or not.
+    /// A definition comes after the definitions it uses.
+    /// The graph must be free of cycles, see `cycles`.
+    pub fn needed(&self, term:&Term) -> Vec<&'a Sentence> {
+        let mut roots:Vec<usize> = term.free_vars().iter().filter_map(|n| self.owner.get(n.as_str()).copied()).collect();
+        roots.sort_unstable();
+        let mut visited = vec![false; self.defs.len()];
+        let mut order = vec![];
+        for r in roots { self.visit(r, &mut visited, &mut order); }
+        order.into_iter().map(|i| self.defs[i]).collect()
+    }
+
+    /// Build the program of a term: the term with the definitions it needs, as `(name -> term) body` or `rec`.
+    /// The graph must be free of cycles, see `cycles`.
+    pub fn program(&self, term:&Term) -> Box<Term> {
+        self.needed(term).into_iter()
+            .rev()
+            .fold(Box::new(term.clone()),
+                  |acc, d| match d {
+                      Sentence::Rec(SRec{defs, span}) => {
+                          let defs = defs.iter().map(|d| (d.vname.clone(), d.body.clone())).collect();
+                          Box::new(Term::Rec{defs, body:acc, span:*span})
+                      }
+                      Sentence::Let(SLet{vname, body, span}) => {
+                          Box::new(Term::App{fun:Box::new(Term::Lambda{vname:vname.clone(), body:acc, span:*span}), arg:body.clone(), span:*span})
+                      }
+                      _ => acc
+                  })
+    }
+
+    /// Get all the definitions, a definition coming after the ones it uses.
+    /// The graph must be free of cycles, see `cycles`.
+    pub fn sorted(&self) -> Vec<&'a Sentence> {
+        let mut visited = vec![false; self.defs.len()];
+        let mut order = vec![];
+        for i in 0..self.defs.len() { self.visit(i, &mut visited, &mut order); }
+        order.into_iter().map(|i| self.defs[i]).collect()
+    }
+
+    /// Depth first visit, adding a definition after the ones it uses.
+    fn visit(&self, i:usize, visited:&mut Vec<bool>, order:&mut Vec<usize>) {
+        if visited[i] { return; }
+        visited[i] = true;
+        for j in &self.uses[i] { self.visit(*j, visited, order); }
+        order.push(i);
+    }
+
+    /// Report the cycles between definitions, one error per cycle found.
+    /// Each error is located at the first definition of the cycle.
+    pub fn cycles(&self) -> Vec<EoleError> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark { New, Active, Done }
+        let mut marks = vec![Mark::New; self.defs.len()];
+        let mut errors = vec![];
+
+        // Iterative depth first search, 'stack' holding the active definitions and the next use to visit.
+        for root in 0..self.defs.len() {
+            if marks[root] != Mark::New { continue; }
+            let mut stack = vec![(root, 0)];
+            marks[root] = Mark::Active;
+            while let Some((i, next)) = stack.last_mut() {
+                let i = *i;
+                match self.uses[i].get(*next) {
+                    None => {
+                        marks[i] = Mark::Done;
+                        stack.pop();
+                    }
+                    Some(&j) => {
+                        *next += 1;
+                        match marks[j] {
+                            Mark::New => {
+                                marks[j] = Mark::Active;
+                                stack.push((j, 0));
+                            }
+                            Mark::Active => {
+                                let start = stack.iter().position(|(k, _)| *k == j).unwrap();
+                                let mut chain:Vec<String> = stack[start..].iter().map(|(k, _)| self.name(*k)).collect();
+                                chain.push(self.name(j));
+                                errors.push(EoleError::new(ErrorKind::CyclicDefinitions{chain}, self.defs[j].span()));
+                            }
+                            Mark::Done => {}
+                        }
+                    }
+                }
+            }
+        }
+        errors
+    }
+
+    /// Name of a definition, used in the errors: the first name of a `rec` group.
+    fn name(&self, i:usize) -> String {
+        names(self.defs[i])[0].to_string()
+    }
+
+    /// Report the unknown symbols and the uses of `_` in all the definitions, used or not.
+    /// All the definitions are in scope.
+    pub fn check(&self) -> Vec<EoleError> {
+        let mut errors = vec![];
+        for d in &self.defs {
+            for body in bodies(d) { errors.extend(self.check_term(body)); }
+        }
+        errors
+    }
+
+    /// Report the unknown symbols and the uses of `_` in a term, in which all the definitions are in scope.
+    pub fn check_term(&self, term:&Term) -> Vec<EoleError> {
+        let mut scope:Vec<String> = self.owner.keys().map(|n| n.to_string()).collect();
+        scope.sort_unstable();
+        let mut errors = vec![];
+        check(term, &mut scope, &mut errors);
+        errors
+    }
+}
+
+
+/// Report the unknown symbols and the uses of `_` in a term, `scope` being the names in scope.
+/// The errors are the same as the ones of the conversion.
+fn check(term:&Term, scope:&mut Vec<String>, errors:&mut Vec<EoleError>) {
+    use self::Term::*;
+    match term {
+        Sym{vname, span} => {
+            if vname == "_" {
+                errors.push(EoleError::new(ErrorKind::Wildcard, *span));
+            } else if !scope.contains(vname) {
+                let suggestion = error::suggest(vname, scope.iter());
+                errors.push(EoleError::new(ErrorKind::UnknownSymbol{name:vname.clone(), suggestion}, *span));
+            }
+        }
+        Lambda{vname, body, ..} => {
+            scope.push(vname.clone());
+            check(body, scope, errors);
+            scope.pop();
+        }
+        App{fun, arg, ..} => {
+            check(fun, scope, errors);
+            check(arg, scope, errors);
+        }
+        Num{..} | Str{..} => {}
+        List{items, ..} => for item in items { check(item, scope, errors); },
+        Annot{term, ..} => check(term, scope, errors),
+        Rec{defs, body, ..} => {
+            let depth = scope.len();
+            scope.extend(defs.iter().map(|(vname, _)| vname.clone()));
+            for (_, def) in defs { check(def, scope, errors); }
+            check(body, scope, errors);
+            scope.truncate(depth);
+        }
+        Case{..} => unreachable!("case expressions are desugared before the conversion, see data::desugar"),
+        Infix{span, ..} => import::unresolved(*span),
+    }
+}
+
+
+/// Names defined by a definition.
+fn names(s:&Sentence) -> Vec<&str> {
+    match s {
+        Sentence::Let(d) => vec![&d.vname],
+        Sentence::Rec(SRec{defs, ..}) => defs.iter().map(|d| d.vname.as_str()).collect(),
+        _ => vec![]
+    }
+}
+
+/// Bodies of a definition.
+fn bodies(s:&Sentence) -> Vec<&Term> {
+    match s {
+        Sentence::Let(d) => vec![&d.body],
+        Sentence::Rec(SRec{defs, ..}) => defs.iter().map(|d| &*d.body).collect(),
+        _ => vec![]
+    }
+}
@@ -0,0 +1,553 @@
+//! # Elementary affine logic
+//! Check if the terms to run are typable in elementary affine logic (EAL), and warn about the ones which are not,
+//! with `--eal` or `#pragma eal`.
+//!
+//! Éole reduces without oracle: the reduction is known to be correct for the terms typable in EAL,
+//! but not for all the terms (see the counterexample of the README). The result of a term outside EAL cannot be trusted.
+//!
+//! ## Types and boxes
+//! The types of EAL are the simple types with the modality `!`: `!A` is the type of a value in a box,
+//! which can be duplicated or erased, but not opened. A term is typed by boxing some of its subterms:
+//! the free variables of a box go through its doors (their type gets a `!`), and a duplicated variable must have a `!` type.
+//! The level of a subterm is the number of boxes around it.
+//!
+//! The analysis follows Coppola and Martini (Typing lambda terms in elementary logic with linear constraints, 2001):
+//! the simple type of the term is inferred, and decorated with `!` afterward.
+//! Each subterm and each node of the types is given a level (the number of boxes, plus the number of `!` in the type).
+//! The typing rules are then equalities and inequalities between the levels: `l >= m`, or `l >= m + 1` for a duplicated variable.
+//! They are solved on the graph of the constraints: the term is not typable if a cycle goes through a duplicated variable.
+//!
+//! The definitions used by a term are part of it (see `dependency`).
+//! They are polymorphic, as in second order EAL, so that a definition can be used at different types.
+//! Recursive definitions are outside EAL.
+
+use eole_parser::ast::{*, Term::*};
+use crate::dependency::Dependencies;
+use crate::encoding::{self, Encodings};
+use crate::error::{EoleError, ErrorKind, Sources};
+use crate::import;
+use crate::typing;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::panic;
+
+
+/// A type of elementary affine logic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EalType {
+    Var(String),
+    Arrow(Box<EalType>, Box<EalType>),
+    Bang(Box<EalType>),
+}
+
+impl fmt::Display for EalType {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match self {
+            EalType::Var(v) => write!(f, "{}", v),
+            EalType::Arrow(a, b) => match **a {
+                EalType::Arrow(..) => write!(f, "({}) -> {}", a, b),
+                _ => write!(f, "{} -> {}", a, b)
+            },
+            EalType::Bang(t) => match **t {
+                EalType::Arrow(..) => write!(f, "!({})", t),
+                _ => write!(f, "!{}", t)
+            }
+        }
+    }
+}
+
+
+/// Stratification of a term typable in EAL.
+#[derive(Clone, Debug)]
+pub struct Stratification {
+    /// Type of the term, with the fewest boxes.
+    pub ty:EalType,
+    /// Maximal number of nested boxes in the term.
+    pub depth:usize,
+}
+
+/// Reason why a term is not typable in EAL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reason {
+    /// The term uses a recursive definition.
+    Recursion,
+    /// The term has no simple type.
+    NotSimplyTyped,
+    /// A variable is duplicated, but cannot be boxed.
+    Duplication(String),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match self {
+            Reason::Recursion => write!(f, "recursive definitions are outside elementary affine logic"),
+            Reason::NotSimplyTyped => write!(f, "the term has no simple type, hence no elementary affine type"),
+            Reason::Duplication(x) => write!(f, "`{}` is duplicated, but cannot be boxed", x),
+        }
+    }
+}
+
+
+/// Check the terms to run with the definitions they need, identified by their name or else by their line (see `label`).
+/// Nothing is checked if the definitions contain errors: they are reported by the conversion.
+/// The terms whose analysis fails are left out: the warnings are advisory, and must not stop the run.
+/// The data types must have been desugared, see `data::desugar`.
+pub fn check(sentences:&[Sentence], encodings:Encodings, sources:&Sources) -> Vec<(String, Result<Stratification, EoleError>)> {
+    let deps = Dependencies::new(sentences);
+    if !deps.cycles().is_empty() || !deps.check().is_empty() { return vec![]; }
+    sentences.iter()
+        .filter_map(|s| match s { Sentence::Run(r) => Some(r), _ => None })
+        .filter(|r| deps.check_term(&r.term).is_empty())
+        .filter_map(|r| {
+            let label = label(r, sources);
+            let res = try_stratify(&deps.program(&r.term), encodings)?
+                .map_err(|(reason, span)| EoleError::new(ErrorKind::NotElementary{run:label.clone(), reason}, span));
+            Some((label, res))
+        })
+        .collect()
+}
+
+/// Designate a term to run in the messages: by its name, or by its line if it has none.
+fn label(r:&SRun, sources:&Sources) -> String {
+    match &r.name {
+        Some(name) => format!("`{}`", name),
+        None => format!("the term at line {}", sources.line_col(r.span.file, r.span.lo).0)
+    }
+}
+
+/// Type a term as `stratify`, or give `None` if the analysis panics, without printing the panic.
+fn try_stratify(term:&Term, encodings:Encodings) -> Option<Result<Stratification, (Reason, Span)>> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let res = panic::catch_unwind(|| stratify(term, encodings));
+    panic::set_hook(hook);
+    res.ok()
+}
+
+/// Type a closed term in EAL, or give the reason why it is not typable, located at the faulty subterm.
+pub fn stratify(term:&Term, encodings:Encodings) -> Result<Stratification, (Reason, Span)> {
+    let mut s = Solver{ nodes:vec![], node_levels:vec![], levels:vec![], positions:vec![], constraints:vec![], encodings };
+    let root = s.position(None);
+    let ty = s.infer(term, root, &mut vec![])?;
+    s.solve(root, ty)
+}
+
+
+/// Node of a simple type.
+#[derive(Clone, Copy, Debug)]
+enum Node {
+    Var,
+    Arrow(usize, usize),
+    /// Unified with another node.
+    Link(usize),
+}
+
+/// A variable in scope, with its type. A definition can have generic type variables.
+struct Binding {
+    vname:String,
+    node:usize,
+    generic:Vec<usize>,
+    uses:usize,
+}
+
+/// Constraint between two levels: `hi >= lo + 1` for a duplicated variable, `hi >= lo` otherwise.
+struct Constraint {
+    hi:usize,
+    lo:usize,
+    duplicated:Option<(String, Span)>,
+}
+
+/// State of the analysis: the simple types, and the constraints on the levels.
+struct Solver {
+    /// Nodes of the types.
+    nodes:Vec<Node>,
+    /// Level of each node.
+    node_levels:Vec<usize>,
+    /// Union-find of the equal levels.
+    levels:Vec<usize>,
+    /// Levels of the subterms.
+    positions:Vec<usize>,
+    constraints:Vec<Constraint>,
+    encodings:Encodings,
+}
+
+impl Solver {
+
+    /// New level.
+    fn level(&mut self) -> usize {
+        self.levels.push(self.levels.len());
+        self.levels.len() - 1
+    }
+
+    /// Level of a new subterm, inside (or at the level of) the enclosing subterm `parent`.
+    fn position(&mut self, parent:Option<usize>) -> usize {
+        let l = self.level();
+        if let Some(p) = parent { self.geq(l, p, None); }
+        self.positions.push(l);
+        l
+    }
+
+    fn geq(&mut self, hi:usize, lo:usize, duplicated:Option<(String, Span)>) {
+        self.constraints.push(Constraint{hi, lo, duplicated});
+    }
+
+    fn find_level(&mut self, l:usize) -> usize {
+        let mut root = l;
+        while self.levels[root] != root { root = self.levels[root]; }
+        let mut l = l;
+        while self.levels[l] != root {
+            let next = self.levels[l];
+            self.levels[l] = root;
+            l = next;
+        }
+        root
+    }
+
+    /// New type variable, at a new level.
+    fn var(&mut self) -> usize {
+        let l = self.level();
+        self.node(Node::Var, l)
+    }
+
+    /// New arrow type at the level `l`. Its arguments have at least as many boxes as itself.
+    fn arrow(&mut self, a:usize, b:usize, l:usize) -> usize {
+        let (la, lb) = (self.node_levels[a], self.node_levels[b]);
+        self.geq(la, l, None);
+        self.geq(lb, l, None);
+        self.node(Node::Arrow(a, b), l)
+    }
+
+    fn node(&mut self, node:Node, l:usize) -> usize {
+        self.nodes.push(node);
+        self.node_levels.push(l);
+        self.nodes.len() - 1
+    }
+
+    /// Representative of a node, compressing the links on the way.
+    fn find(&mut self, n:usize) -> usize {
+        let mut root = n;
+        while let Node::Link(m) = self.nodes[root] { root = m; }
+        let mut n = n;
+        while let Node::Link(m) = self.nodes[n] {
+            self.nodes[n] = Node::Link(root);
+            n = m;
+        }
+        root
+    }
+
+    /// Nodes of the type `n` in postorder, each one once: the types share their nodes, and can be deep.
+    fn subterms(&mut self, n:usize) -> Vec<usize> {
+        let mut res = vec![];
+        let mut seen = HashSet::new();
+        let mut todo = vec![(n, false)];
+        while let Some((n, visited)) = todo.pop() {
+            let n = self.find(n);
+            if visited {
+                res.push(n);
+                continue;
+            }
+            if !seen.insert(n) { continue; }
+            todo.push((n, true));
+            if let Node::Arrow(a, b) = self.nodes[n] {
+                todo.push((b, false));
+                todo.push((a, false));
+            }
+        }
+        res
+    }
+
+    /// Check if the node `v` occurs in the type `n`.
+    fn occurs(&mut self, v:usize, n:usize) -> bool {
+        self.subterms(n).contains(&v)
+    }
+
+    /// Unify two types, and their levels: they are the same type, with the same boxes.
+    /// Two arrows are linked once their arguments and results are unified, so that the occurs check never meets a cycle.
+    fn unify(&mut self, a:usize, b:usize) -> Result<(), ()> {
+        let mut todo = vec![(a, b, false)];
+        while let Some((a, b, unified)) = todo.pop() {
+            let (a, b) = (self.find(a), self.find(b));
+            if a == b { continue; }
+            if unified {
+                self.nodes[a] = Node::Link(b);
+                continue;
+            }
+            let (la, lb) = (self.find_level(self.node_levels[a]), self.find_level(self.node_levels[b]));
+            self.levels[la] = lb;
+            match (self.nodes[a], self.nodes[b]) {
+                (Node::Var, _) => {
+                    if self.occurs(a, b) { return Err(()); }
+                    self.nodes[a] = Node::Link(b);
+                }
+                (_, Node::Var) => {
+                    if self.occurs(b, a) { return Err(()); }
+                    self.nodes[b] = Node::Link(a);
+                }
+                (Node::Arrow(a1, r1), Node::Arrow(a2, r2)) => {
+                    todo.push((a, b, true));
+                    todo.push((r1, r2, false));
+                    todo.push((a1, a2, false));
+                }
+                _ => unreachable!()
+            }
+        }
+        Ok(())
+    }
+
+    /// Type variables of a type.
+    fn vars(&mut self, n:usize) -> Vec<usize> {
+        let mut res = self.subterms(n);
+        res.retain(|v| matches!(self.nodes[*v], Node::Var));
+        res
+    }
+
+    /// Type variables of a definition which do not occur in the enclosing variables.
+    fn generic(&mut self, n:usize, env:&[Binding]) -> Vec<usize> {
+        let mut bound = HashSet::new();
+        for b in env {
+            let vars = self.vars(b.node);
+            bound.extend(vars.into_iter().filter(|v| !b.generic.contains(v)));
+        }
+        let mut vars = self.vars(n);
+        vars.retain(|v| !bound.contains(v));
+        vars
+    }
+
+    /// Instantiate the type of a definition: the generic variables are replaced by new types, and the arrows by new arrows,
+    /// all at new levels which can have more boxes than the definition, so that each use has its own boxes.
+    /// The other variables are shared.
+    fn instantiate(&mut self, n:usize, generic:&[usize]) -> usize {
+        let mut copies:HashMap<usize, usize> = HashMap::new();
+        // The arguments and the results are copied before their arrow
+        for m in self.subterms(n) {
+            let c = match self.nodes[m] {
+                Node::Arrow(a, b) => {
+                    let (ca, cb) = (copies[&self.find(a)], copies[&self.find(b)]);
+                    let l = self.level();
+                    self.geq(l, self.node_levels[m], None);
+                    self.arrow(ca, cb, l)
+                }
+                _ if generic.contains(&m) => {
+                    let v = self.var();
+                    self.geq(self.node_levels[v], self.node_levels[m], None);
+                    v
+                }
+                _ => m
+            };
+            copies.insert(m, c);
+        }
+        copies[&self.find(n)]
+    }
+
+    /// Infer the type of a term at the level `l`, `env` being the variables in scope (the last one being the closest).
+    /// The type has at least as many boxes as the term.
+    fn infer(&mut self, term:&Term, l:usize, env:&mut Vec<Binding>) -> Result<usize, (Reason, Span)> {
+        let ty = self.infer_(term, l, env)?;
+        self.geq(self.node_levels[ty], l, None);
+        Ok(ty)
+    }
+
+    fn infer_(&mut self, term:&Term, l:usize, env:&mut Vec<Binding>) -> Result<usize, (Reason, Span)> {
+        match term {
+            Sym{vname, ..} => match env.iter_mut().rev().find(|b| b.vname == *vname) {
+                Some(b) => {
+                    b.uses += 1;
+                    let (node, generic) = (b.node, b.generic.clone());
+                    Ok(if generic.is_empty() { node } else { self.instantiate(node, &generic) })
+                }
+                // Unknown symbols are reported by the conversion
+                None => Ok(self.var())
+            },
+
+            Lambda{vname, body, span} => {
+                let x = self.var();
+                let lb = self.position(Some(l));
+                env.push(Binding{vname:vname.clone(), node:x, generic:vec![], uses:0});
+                let b = self.infer(body, lb, env);
+                let binding = env.pop().unwrap();
+                let b = b?;
+                if binding.uses > 1 {
+                    let lx = self.node_levels[x];
+                    self.duplicate(lx, l, vname, *span);
+                }
+                Ok(self.arrow(x, b, l))
+            }
+
+            // A definition, or 'with': polymorphic
+            App{fun, arg, span} if matches!(**fun, Lambda{..}) => {
+                let (vname, body) = match &**fun { Lambda{vname, body, ..} => (vname, body), _ => unreachable!() };
+                let la = self.position(Some(l));
+                let a = self.infer(arg, la, env)?;
+                let generic = self.generic(a, env);
+                let lb = self.position(Some(l));
+                env.push(Binding{vname:vname.clone(), node:a, generic, uses:0});
+                let b = self.infer(body, lb, env);
+                let binding = env.pop().unwrap();
+                let b = b?;
+                if binding.uses > 1 {
+                    let lx = self.node_levels[a];
+                    self.duplicate(lx, l, vname, *span);
+                }
+                Ok(b)
+            }
+
+            // The function is at the level of the application, without box
+            App{fun, arg, span} => {
+                let f = self.infer(fun, l, env)?;
+                let la = self.position(Some(l));
+                let a = self.infer(arg, la, env)?;
+                let r = self.var();
+                let arrow = self.arrow(a, r, l);
+                self.unify(f, arrow).map_err(|_| (Reason::NotSimplyTyped, *span))?;
+                Ok(r)
+            }
+
+            // Literals: as their encoding
+            Num{value, span} => {
+                let numeral = encoding::numeral(self.encodings.numerals, *value, *span);
+                self.infer_(&numeral, l, &mut vec![])
+            }
+            List{items, span} => {
+                let list = encoding::list(self.encodings.lists, items.clone(), *span);
+                self.infer_(&list, l, env)
+            }
+            Str{value, span} => {
+                let string = encoding::string(self.encodings, value, *span);
+                self.infer_(&string, l, &mut vec![])
+            }
+
+            Rec{span, ..} => Err((Reason::Recursion, *span)),
+
+            Annot{term, ..} => self.infer_(term, l, env),
+
+            Case{..} => unreachable!("case expressions are desugared before the analysis, see data::desugar"),
+            Infix{span, ..} => import::unresolved(*span),
+        }
+    }
+
+    /// A variable of level `lx`, bound at the level `l`, is duplicated: its type must be boxed.
+    fn duplicate(&mut self, lx:usize, l:usize, vname:&str, span:Span) {
+        self.geq(lx, l, Some((vname.to_string(), span)));
+    }
+
+    /// Solve the constraints with the fewest boxes, and give the type of the term of level `root` and type `ty`.
+    fn solve(&mut self, root:usize, ty:usize) -> Result<Stratification, (Reason, Span)> {
+        // Graph of the constraints between the classes of equal levels: an edge from 'lo' to 'hi'
+        let n = self.levels.len();
+        let classes:Vec<usize> = (0..n).map(|l| self.find_level(l)).collect();
+        let mut succ = vec![vec![]; n];
+        let mut pred = vec![vec![]; n];
+        for (i, c) in self.constraints.iter().enumerate() {
+            succ[classes[c.lo]].push((classes[c.hi], i));
+            pred[classes[c.hi]].push(classes[c.lo]);
+        }
+
+        // Strongly connected components (Kosaraju), in topological order
+        let order = postorder(n, |v| succ[v].iter().map(|(w, _)| *w).collect());
+        let mut component = vec![usize::MAX; n];
+        let mut components = vec![];
+        for &v in order.iter().rev() {
+            if component[v] != usize::MAX { continue; }
+            let c = components.len();
+            let mut members = vec![];
+            let mut stack = vec![v];
+            component[v] = c;
+            while let Some(u) = stack.pop() {
+                members.push(u);
+                for &w in &pred[u] {
+                    if component[w] == usize::MAX { component[w] = c; stack.push(w); }
+                }
+            }
+            components.push(members);
+        }
+
+        // A duplicated variable in a cycle cannot be boxed: blame the outermost one, constrained last
+        for c in self.constraints.iter().rev() {
+            if let Some((vname, span)) = &c.duplicated {
+                if component[classes[c.hi]] == component[classes[c.lo]] {
+                    return Err((Reason::Duplication(vname.clone()), *span));
+                }
+            }
+        }
+
+        // Fewest boxes: longest paths
+        let mut component_values = vec![0; components.len()];
+        for (c, members) in components.iter().enumerate() {
+            for &v in members {
+                for &(w, i) in &succ[v] {
+                    let inc = if self.constraints[i].duplicated.is_some() { 1 } else { 0 };
+                    if component[w] != c {
+                        component_values[component[w]] = component_values[component[w]].max(component_values[c] + inc);
+                    }
+                }
+            }
+        }
+        let values:Vec<usize> = (0..n).map(|v| component_values[component[v]]).collect();
+
+        let base = values[classes[root]];
+        let depth = self.positions.iter().map(|p| values[classes[*p]] - base).max().unwrap_or(0);
+        let ty = self.eal_type(ty, base, &values, &classes);
+        Ok(Stratification{ty, depth})
+    }
+
+    /// Type of EAL of a node, in a context of level `base`.
+    fn eal_type(&mut self, n:usize, base:usize, values:&[usize], classes:&[usize]) -> EalType {
+        let mut names = HashMap::new();
+        // The nodes to type, in the context of a level, with their arrows once their arguments and results are typed
+        let mut todo = vec![(n, base, false)];
+        let mut typed = vec![];
+        while let Some((n, base, ready)) = todo.pop() {
+            let n = self.find(n);
+            let level = values[classes[self.node_levels[n]]];
+            let ty = match self.nodes[n] {
+                Node::Arrow(a, b) if !ready => {
+                    todo.push((n, base, true));
+                    todo.push((b, level, false));
+                    todo.push((a, level, false));
+                    continue;
+                }
+                Node::Arrow(..) => {
+                    let b = typed.pop().expect("Typing the result of an arrow");
+                    let a = typed.pop().expect("Typing the argument of an arrow");
+                    EalType::Arrow(Box::new(a), Box::new(b))
+                }
+                _ => {
+                    let next = names.len();
+                    EalType::Var(names.entry(n).or_insert_with(|| typing::var_name(next)).clone())
+                }
+            };
+            typed.push((base..level).fold(ty, |acc, _| EalType::Bang(Box::new(acc))));
+        }
+        typed.pop().expect("Typing a node")
+    }
+}
+
+
+/// Iterative depth first search of a graph of `n` vertices, giving the vertices in postorder.
+fn postorder<F:Fn(usize) -> Vec<usize>>(n:usize, succ:F) -> Vec<usize> {
+    let mut visited = vec![false; n];
+    let mut order = vec![];
+    for root in 0..n {
+        if visited[root] { continue; }
+        visited[root] = true;
+        let mut stack = vec![(root, succ(root), 0)];
+        while let Some((v, next, i)) = stack.last_mut() {
+            match next.get(*i) {
+                Some(&w) => {
+                    *i += 1;
+                    if !visited[w] {
+                        visited[w] = true;
+                        let s = succ(w);
+                        stack.push((w, s, 0));
+                    }
+                }
+                None => {
+                    order.push(*v);
+                    stack.pop();
+                }
+            }
+        }
+    }
+    order
+}
@@ -0,0 +1,321 @@
+//! # Encodings
+//! Encode the literals of the source files as lambda terms.
+//!
+//! ## Numerals
+//! A numeric literal `n` is expanded into a closed term, according to the chosen encoding:
+//!   * Church: `f->x->f (f ... (f x))`, with `n` applications of `f`.
+//!   * Scott: `z->s->z` for 0, `z->s->s p` for `p+1`.
+//!   * Binary: the bits of `n`, least significant first, built with three constructors:
+//!     `e->o->i->e` for the end of the bits, `e->o->i->o b` for a 0 followed by the bits `b`,
+//!     and `e->o->i->i b` for a 1 followed by the bits `b`. 0 is the empty sequence of bits.
+//!
+//! ## Lists
+//! A list literal `[a, b]` is expanded according to the chosen encoding:
+//!   * Scott: `n->c->n` for the empty list, `n->c->c h t` for the head `h` followed by the tail `t`.
+//!     This is the encoding of `data List = Nil | Cons h t.`
+//!   * Church: the right fold of the list, `c->n->c a (c b n)`.
+//!
+//! The binders of a list are renamed if needed, so that they do not capture the free variables of the items.
+//!
+//! ## Strings
+//! A string literal is the list of its characters, each one being the numeral of its code point.
+//!
+//! ## Limit
+//! The passes on the terms (conversion, type inference, ...) are recursive: the encoding of a literal cannot be deeper
+//! than `MAX_DEPTH` nested terms, see `check`. It allows Church's numerals up to about 12000, and Scott's up to about 4000.
+//! The binary numerals are only as deep as the number of bits.
+//!
+//! ## Decoding
+//! The numerals and lists can be decoded back, e.g. to print the results as literals.
+
+use eole_parser::ast::{Sentence, SRec, Span, Term, Term::*};
+use eole_parser::term::fresh;
+use crate::error::{EoleError, ErrorKind};
+use crate::import;
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
+
+/// Encoding of the numeric literals.
+#[derive(Copy,Clone,Debug,Default,PartialEq,Eq)]
+pub enum Numerals {
+    #[default]
+    Church,
+    Scott,
+    Binary,
+}
+
+
+/// Encoding of the list literals.
+#[derive(Copy,Clone,Debug,Default,PartialEq,Eq)]
+pub enum Lists {
+    #[default]
+    Scott,
+    Church,
+}
+
+/// Encodings of all the literals.
+#[derive(Copy,Clone,Debug,Default,PartialEq,Eq)]
+pub struct Encodings {
+    pub numerals:Numerals,
+    pub lists:Lists,
+}
+
+
+/// Maximal depth of the encoding of a literal.
+pub const MAX_DEPTH:usize = 12_000;
+
+
+/// Check that the encodings of the literals of the sentences are not deeper than `MAX_DEPTH`.
+pub fn check(sentences:&[Sentence], encodings:Encodings) -> Result<(), Vec<EoleError>> {
+    let mut errors = vec![];
+    for s in sentences {
+        match s {
+            Sentence::Let(d) => check_term(&d.body, encodings, &mut errors),
+            Sentence::Rec(SRec{defs, ..}) => for d in defs { check_term(&d.body, encodings, &mut errors); },
+            Sentence::Run(r) => check_term(&r.term, encodings, &mut errors),
+            Sentence::Assert(a) => {
+                check_term(&a.lhs, encodings, &mut errors);
+                check_term(&a.rhs, encodings, &mut errors);
+            }
+            Sentence::Read(_) | Sentence::Data(_) | Sentence::Infix(_) | Sentence::Pragma(_) => {}
+        }
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+fn check_term(term:&Term, encodings:Encodings, errors:&mut Vec<EoleError>) {
+    let too_deep = |depth:usize, span:Span, errors:&mut Vec<EoleError>| if depth > MAX_DEPTH {
+        errors.push(EoleError::new(ErrorKind::LiteralTooLarge{depth, max:MAX_DEPTH}, span));
+    };
+    match term {
+        Sym{..} => {}
+        Num{value, span} => too_deep(numeral_depth(encodings.numerals, *value), *span, errors),
+        Str{value, span} => {
+            let chars = value.chars().map(|c| numeral_depth(encodings.numerals, c as u64)).max().unwrap_or(0);
+            too_deep(list_depth(encodings.lists, value.chars().count()).saturating_add(chars), *span, errors)
+        }
+        List{items, span} => {
+            too_deep(list_depth(encodings.lists, items.len()), *span, errors);
+            for item in items { check_term(item, encodings, errors); }
+        }
+        Lambda{body, ..} => check_term(body, encodings, errors),
+        App{fun, arg, ..} => {
+            check_term(fun, encodings, errors);
+            check_term(arg, encodings, errors);
+        }
+        Rec{defs, body, ..} => {
+            for (_, def) in defs { check_term(def, encodings, errors); }
+            check_term(body, encodings, errors);
+        }
+        Case{scrutinee, arms, ..} => {
+            check_term(scrutinee, encodings, errors);
+            for arm in arms { check_term(&arm.body, encodings, errors); }
+        }
+        Annot{term, ..} => check_term(term, encodings, errors),
+        Infix{span, ..} => import::unresolved(*span),
+    }
+}
+
+/// Depth of the encoding of the number `n`, without building it.
+fn numeral_depth(encoding:Numerals, n:u64) -> usize {
+    let n = usize::try_from(n).unwrap_or(usize::MAX);
+    match encoding {
+        Numerals::Church => n.saturating_add(3),
+        Numerals::Scott => n.saturating_mul(3).saturating_add(3),
+        Numerals::Binary => 4 * (usize::BITS - n.leading_zeros()) as usize + 4,
+    }
+}
+
+/// Depth of the encoding of a list of `length` items, without the depth of the items.
+fn list_depth(encoding:Lists, length:usize) -> usize {
+    match encoding {
+        Lists::Scott => length.saturating_mul(3).saturating_add(4),
+        Lists::Church => length.saturating_add(4),
+    }
+}
+
+
+/// Encode the number `n` as a closed term. All the nodes of the term are located at `span`.
+pub fn numeral(encoding:Numerals, n:u64, span:Span) -> Box<Term> {
+    match encoding {
+        Numerals::Church => church(n, span),
+        Numerals::Scott => scott(n, span),
+        Numerals::Binary => binary(n, span),
+    }
+}
+
+
+/// `f->x->f (f ... (f x))`
+fn church(n:u64, span:Span) -> Box<Term> {
+    let body = (0..n).fold(sym("x", span), |acc, _| app(sym("f", span), acc, span));
+    lambdas(&["f", "x"], body, span)
+}
+
+/// `z->s->z` or `z->s->s p`
+fn scott(n:u64, span:Span) -> Box<Term> {
+    let body = if n == 0 {
+        sym("z", span)
+    } else {
+        app(sym("s", span), scott(n-1, span), span)
+    };
+    lambdas(&["z", "s"], body, span)
+}
+
+/// `e->o->i->e`, `e->o->i->o b` or `e->o->i->i b`
+fn binary(n:u64, span:Span) -> Box<Term> {
+    let body = if n == 0 {
+        sym("e", span)
+    } else {
+        let bit = if n & 1 == 0 { "o" } else { "i" };
+        app(sym(bit, span), binary(n/2, span), span)
+    };
+    lambdas(&["e", "o", "i"], body, span)
+}
+
+
+/// Encode a list of terms. All the nodes of the list are located at `span`.
+pub fn list(encoding:Lists, items:Vec<Box<Term>>, span:Span) -> Box<Term> {
+    let free:HashSet<String> = items.iter().flat_map(|item| item.free_vars()).collect();
+    let n = fresh("n", &free);
+    let c = fresh("c", &free);
+    let cons = |item, acc| app(app(sym(&c, span), item, span), acc, span);
+    match encoding {
+        // 'n->c->c a (n->c->c b (n->c->n))'
+        Lists::Scott => items.into_iter().rev()
+            .fold(lambdas(&[&n, &c], sym(&n, span), span), |acc, item| lambdas(&[&n, &c], cons(item, acc), span)),
+        // 'c->n->c a (c b n)'
+        Lists::Church => {
+            let body = items.into_iter().rev().fold(sym(&n, span), |acc, item| cons(item, acc));
+            lambdas(&[&c, &n], body, span)
+        }
+    }
+}
+
+/// Encode a string as the list of the numerals of its characters.
+pub fn string(encodings:Encodings, value:&str, span:Span) -> Box<Term> {
+    let chars = value.chars().map(|c| numeral(encodings.numerals, c as u64, span)).collect();
+    list(encodings.lists, chars, span)
+}
+
+
+/// Decode a numeral: the inverse of `numeral`.
+pub fn numeral_value(encoding:Numerals, term:&Term) -> Option<u64> {
+    match encoding {
+        Numerals::Church => {
+            let (bs, mut body) = binders(term, 2)?;
+            let mut n:u64 = 0;
+            while let App{fun, arg, ..} = body {
+                if !is_var(fun, bs[0]) { return None; }
+                n = n.checked_add(1)?;
+                body = arg;
+            }
+            if is_var(body, bs[1]) { Some(n) } else { None }
+        }
+        Numerals::Scott => {
+            let mut n:u64 = 0;
+            let mut term = term;
+            loop {
+                let (bs, body) = binders(term, 2)?;
+                match body {
+                    _ if is_var(body, bs[0]) => return Some(n),
+                    App{fun, arg, ..} if is_var(fun, bs[1]) => {
+                        n = n.checked_add(1)?;
+                        term = arg;
+                    }
+                    _ => return None
+                }
+            }
+        }
+        Numerals::Binary => {
+            let mut n:u64 = 0;
+            let mut bit = 0;
+            let mut term = term;
+            loop {
+                let (bs, body) = binders(term, 3)?;
+                match body {
+                    _ if is_var(body, bs[0]) => return Some(n),
+                    App{fun, arg, ..} if bit < 64 && (is_var(fun, bs[1]) || is_var(fun, bs[2])) => {
+                        if is_var(fun, bs[2]) { n |= 1 << bit; }
+                        bit += 1;
+                        term = arg;
+                    }
+                    _ => return None
+                }
+            }
+        }
+    }
+}
+
+/// Decode a list: the inverse of `list`, giving the items.
+/// The items must not refer to the binders of the list.
+pub fn list_items(encoding:Lists, term:&Term) -> Option<Vec<&Term>> {
+    let mut items = vec![];
+    match encoding {
+        Lists::Scott => {
+            let mut term = term;
+            loop {
+                let (bs, body) = binders(term, 2)?;
+                if is_var(body, bs[0]) { return Some(items); }
+                let (item, tail) = as_cons(body, &bs, bs[1])?;
+                items.push(item);
+                term = tail;
+            }
+        }
+        Lists::Church => {
+            let (bs, mut body) = binders(term, 2)?;
+            while !is_var(body, bs[1]) {
+                let (item, rest) = as_cons(body, &bs, bs[0])?;
+                items.push(item);
+                body = rest;
+            }
+            Some(items)
+        }
+    }
+}
+
+
+// --- --- --- Helpers
+
+fn sym(vname:&str, span:Span) -> Box<Term> {
+    Box::new(Sym{vname:vname.to_string(), span})
+}
+
+fn app(fun:Box<Term>, arg:Box<Term>, span:Span) -> Box<Term> {
+    Box::new(App{fun, arg, span})
+}
+
+/// Abstract `body` over the binders, the first one being the outermost.
+fn lambdas(binders:&[&str], body:Box<Term>, span:Span) -> Box<Term> {
+    binders.iter().rev().fold(body, |acc, vname| Box::new(Lambda{vname:vname.to_string(), body:acc, span}))
+}
+
+/// Get the `k` outermost binders of a term, and the body under them.
+fn binders(term:&Term, k:usize) -> Option<(Vec<&str>, &Term)> {
+    let mut res = vec![];
+    let mut body = term;
+    for _ in 0..k {
+        match body {
+            Lambda{vname, body:b, ..} => { res.push(vname.as_str()); body = b; }
+            _ => return None
+        }
+    }
+    Some((res, body))
+}
+
+/// Check if a term is the variable `vname`.
+fn is_var(term:&Term, vname:&str) -> bool {
+    matches!(term, Sym{vname:v, ..} if v == vname)
+}
+
+/// Read `c item rest`, where `item` does not refer to the `bound` variables.
+fn as_cons<'a>(term:&'a Term, bound:&[&str], c:&str) -> Option<(&'a Term, &'a Term)> {
+    match term {
+        App{fun, arg:rest, ..} => match &**fun {
+            App{fun:head, arg:item, ..} if is_var(head, c) && bound.iter().all(|b| !item.occurs_free(b)) => Some((item, rest)),
+            _ => None
+        },
+        _ => None
+    }
+}
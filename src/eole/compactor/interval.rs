@@ -5,7 +5,7 @@ use super::Compactor;
 
 /// Compactor working with interval.
 /// Contains a table create from the `net.available_indexes'
-/// ```
+/// ```text
 ///     ( > x, offset_x)
 ///     ( > y, offset_y)
 ///     ...
@@ -14,7 +14,7 @@ use super::Compactor;
 /// With x > y > z, and offset_x > offset_y > offset_z
 /// The first column represents a cutoff: index above this value must be offseted by -offset
 /// With net.available_indexes = [0, 1, 2, 4, 5], we have the:
-/// ```
+/// ```text
 ///     ( >5, 5)
 ///     ( >2, 3)
 /// ```
@@ -28,15 +28,11 @@ impl Compactor for Mapped {
         for i in (0..net.nodes.len()){
             match head {
                 None => { self.0.insert(i, i-offset); }
-                Some(to_skip) => {
-                    if i == to_skip {
-                        offset +=1;
-                        head = net.available_indexes.pop();
-                    }
-                    else {
-                        self.0.insert(i, i-offset);
-                    }
+                Some(to_skip) if i == to_skip => {
+                    offset +=1;
+                    head = net.available_indexes.pop();
                 }
+                Some(_) => { self.0.insert(i, i-offset); }
             }
         }
     }
@@ -315,13 +315,13 @@ impl GC for EraSinkGC {
     fn get_stats(&self) -> String {
         let mut res = String::new();
 
-        write!(&mut res, "GC Details           :\n");
-        write!(&mut res, "    Removed              : {}\n", self.nb_remove_erase+self.nb_remove_sink);
-        write!(&mut res, "        Erase                : {}\n", self.nb_remove_erase);
-        write!(&mut res, "        Sink                 : {}\n", self.nb_remove_sink);
-        write!(&mut res, "    Calls                : {}\n", self.nb_erase+self.nb_sink);
-        write!(&mut res, "        Erase                : {}\n", self.nb_erase);
-        write!(&mut res, "        Sink                 : {}\n", self.nb_sink);
+        writeln!(&mut res, "GC Details           :");
+        writeln!(&mut res, "    Removed              : {}", self.nb_remove_erase+self.nb_remove_sink);
+        writeln!(&mut res, "        Erase                : {}", self.nb_remove_erase);
+        writeln!(&mut res, "        Sink                 : {}", self.nb_remove_sink);
+        writeln!(&mut res, "    Calls                : {}", self.nb_erase+self.nb_sink);
+        writeln!(&mut res, "        Erase                : {}", self.nb_erase);
+        writeln!(&mut res, "        Sink                 : {}", self.nb_sink);
 
         res
     }
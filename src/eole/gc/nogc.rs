@@ -38,7 +38,7 @@ impl GC for NoGC {
     #[inline]
     fn get_stats(&self) -> String {
         let mut res = String::new();
-        write!(&mut res, "GC Details           : NO GC\n");
+        writeln!(&mut res, "GC Details           : NO GC");
         res
     }
 
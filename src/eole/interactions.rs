@@ -0,0 +1,168 @@
+//! # Step by step reduction
+//! `Interactions` reduces a net one interaction at a time, following a strategy,
+//! and describes each interaction: the nodes involved and the rule that fired.
+//! ```ignore
+//! for i in Interactions::new(&mut net, Strategy::Full) {
+//!     println!("{}", i);
+//! }
+//! ```
+//! The nodes are not compacted during the reduction: the indices of the records remain valid.
+
+use super::compactor::Interval;
+use super::gc::GC;
+use super::net::*;
+use super::reduce::{self, Limits, ReductionOutcome, Reducer, Strategy};
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+
+/// Interaction rules, named as in the statistics of the net (see `Net::print_stats`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// `Apply >< Abs`: a beta-reduction
+    AppLambda,
+    /// `Apply >< FanOut`: duplication of the application
+    AppFou,
+    /// `FanIn >< Abs`: duplication of the abstraction
+    FinLambda,
+    /// `FanIn >< FanOut` with different labels: duplication of the fans
+    FinFouDup,
+    /// `FanIn >< FanOut` with matching labels: annihilation of the fans
+    FinFouAnn,
+}
+
+impl Rule {
+    /// Rule of the interaction between a destructor and a constructor.
+    pub fn of(dkind:DstrK, ckind:&CstrK) -> Rule {
+        match (dkind, ckind) {
+            (DstrK::Apply, CstrK::Abs(..)) => Rule::AppLambda,
+            (DstrK::Apply, CstrK::FanOut(_)) => Rule::AppFou,
+            (DstrK::FanIn(_), CstrK::Abs(..)) => Rule::FinLambda,
+            (DstrK::FanIn(status), CstrK::FanOut(label)) =>
+                if status.is_matching(*label) { Rule::FinFouAnn } else { Rule::FinFouDup },
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::AppLambda => write!(f, "APP-LAMBDA"),
+            Rule::AppFou => write!(f, "APP-FOU"),
+            Rule::FinLambda => write!(f, "FIN-LAMBDA"),
+            Rule::FinFouDup => write!(f, "FIN-FOU (dup)"),
+            Rule::FinFouAnn => write!(f, "FIN-FOU (ann)"),
+        }
+    }
+}
+
+
+/// An interaction, as it was about to happen.
+#[derive(Clone, Debug)]
+pub struct Interaction {
+    /// Index of the destructor
+    pub destructor:usize,
+    pub dkind:DstrK,
+    /// Index of the constructor
+    pub constructor:usize,
+    pub ckind:CstrK,
+    pub rule:Rule,
+}
+
+impl Interaction {
+    /// Describe the interaction of a destructor with the constructor on its main port.
+    fn of<MyGC:GC>(net:&Net<MyGC>, destructor:usize) -> Interaction {
+        let dkind = match net.get_node(destructor).0 {
+            NodeKind::DstrK(d) => d,
+            NodeKind::CstrK(_) => panic!("Interacting with a constructor as destructor")
+        };
+        let constructor = net.follow(main(destructor)).get_index();
+        let ckind = match &net.get_node(constructor).0 {
+            NodeKind::CstrK(c) => c.clone(),
+            NodeKind::DstrK(_) => panic!("Interacting with a destructor as constructor")
+        };
+        let rule = Rule::of(dkind, &ckind);
+        Interaction{destructor, dkind, constructor, ckind, rule}
+    }
+}
+
+impl fmt::Display for Interaction {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} {:?} >< {} {:?}", self.rule, self.destructor, self.dkind, self.constructor, self.ckind)
+    }
+}
+
+
+/// Iterator over the interactions of the reduction of a net: each call to `next` performs one interaction.
+/// The iteration stops when the reduction does, see `outcome`.
+pub struct Interactions<'a, MyGC:GC> {
+    net:&'a mut Net<MyGC>,
+    reducer:Reducer<'a, MyGC>,
+    /// Interaction performed by the last call to the reducer, recorded by its action.
+    last:Rc<RefCell<Option<Interaction>>>,
+    outcome:Option<ReductionOutcome>,
+}
+
+/// The nets are never compacted: the indices of the interactions must remain valid.
+fn never<MyGC:GC>(_:&Net<MyGC>) -> bool { false }
+
+impl<'a, MyGC:GC + 'a> Interactions<'a, MyGC> {
+    /// Prepare the reduction of a net with a strategy.
+    pub fn new(net:&'a mut Net<MyGC>, strategy:Strategy) -> Interactions<'a, MyGC> {
+        Interactions::with_limits(net, strategy, Limits::default())
+    }
+
+    /// Prepare the reduction of a net with a strategy, stopping the iteration when a limit is reached.
+    pub fn with_limits(net:&'a mut Net<MyGC>, strategy:Strategy, limits:Limits) -> Interactions<'a, MyGC> {
+        let last = Rc::new(RefCell::new(None));
+        let record = last.clone();
+        let reducer:Reducer<'a, MyGC> = match strategy {
+            Strategy::Lazy => {
+                let action:reduce::LazyAction<'a, MyGC> = Box::new(move |net:&Net<MyGC>, ((index, _), _)| {
+                    record.replace(Some(Interaction::of(net, index)));
+                });
+                Box::new(reduce::get_reducer_lazy::<MyGC, Interval>(&never::<MyGC>, action, limits))
+            }
+            _ => {
+                let action:reduce::FullAction<'a, MyGC> = Box::new(move |net:&Net<MyGC>, (index, _)| {
+                    record.replace(Some(Interaction::of(net, index)));
+                });
+                match strategy {
+                    Strategy::Head => Box::new(reduce::get_reducer_head::<MyGC, Interval>(&never::<MyGC>, action, limits)),
+                    Strategy::Cbv => Box::new(reduce::get_reducer_cbv::<MyGC, Interval>(&never::<MyGC>, action, limits)),
+                    Strategy::Applicative => Box::new(reduce::get_reducer_applicative::<MyGC, Interval>(&never::<MyGC>, action, limits)),
+                    _ => Box::new(reduce::get_reducer_full::<MyGC, Interval>(&never::<MyGC>, action, limits)),
+                }
+            }
+        };
+        Interactions{net, reducer, last, outcome:None}
+    }
+
+    /// Why the reduction stopped, once the iteration is over.
+    pub fn outcome(&self) -> Option<&ReductionOutcome> {
+        self.outcome.as_ref()
+    }
+
+    /// The net being reduced, e.g. to print it between two interactions.
+    pub fn net(&self) -> &Net<MyGC> {
+        self.net
+    }
+}
+
+impl<'a, MyGC:GC + 'a> Iterator for Interactions<'a, MyGC> {
+    type Item = Interaction;
+
+    fn next(&mut self) -> Option<Interaction> {
+        if self.outcome.is_some() {
+            return None;
+        }
+        // With a credit of 1, the reducer stops before the second interaction, or at the end of the reduction
+        let outcome = (self.reducer)(self.net, true, 1);
+        if outcome != ReductionOutcome::CreditExhausted {
+            self.outcome = Some(outcome);
+        }
+        self.last.replace(None)
+    }
+}
@@ -3,10 +3,12 @@ pub mod net;
 pub mod gc;
 pub mod compactor;
 pub mod reduce;
+pub mod interactions;
 
 // Re-export
 pub use net::*;
 pub use gc::*;
 pub use compactor::*;
 pub use reduce::*;
+pub use interactions::*;
 
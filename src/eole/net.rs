@@ -319,35 +319,35 @@ impl<MyGC:GC> Net<MyGC> {
 
         let mut res = String::new();
 
-        write!(&mut res, "* * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * *\n");
-        write!(&mut res, "Reductions           : {}\n", steps);
-        write!(&mut res, "    APP-LAMBDA           : {}\n", self.nb_appabs);
-        write!(&mut res, "    FAN                  : {}\n", fansteps);
-        write!(&mut res, "        APP-FOU              : {}\n", self.nb_appfou);
-        write!(&mut res, "        FIN-LAMBDA           : {}\n", self.nb_finabs);
-        write!(&mut res, "        FIN-FOU (dup)        : {}\n", self.nb_finfou_d);
-        write!(&mut res, "        FIN-FOU (ann)        : {}\n", self.nb_finfou_a);
-        write!(&mut res, "\n");
-
-        write!(&mut res, "* * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * *\n");
-        write!(&mut res, "Nodes used           : {}\n", self.nb_used);
-        write!(&mut res, "    Max created          : {} ~ {}Mo {}Ko\n", self.max_node_len, created_size_mo, created_size_ko);
-        write!(&mut res, "    Max allocation       : {} ~ {}Mo {}Ko\n", self.max_node_capacity, max_size_mo, max_size_ko);
-        write!(&mut res, "    End allocation       : {} ~ {}Mo {}Ko\n", self.nodes.capacity(), size_mo, size_ko);
-        write!(&mut res, "        Nodes in use         : {}\n", self.nodes.len()-self.available_indexes.len());
-        write!(&mut res, "        Nodes available      : {}\n", self.available_indexes.len());
-        write!(&mut res, "    Reused               : {}\n", self.nb_reused);
-        write!(&mut res, "\n");
-
-        write!(&mut res, "* * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * *\n");
-        write!(&mut res, "Nodes removed        : {}\n", total_remove);
-        write!(&mut res, "    Interactions         : {}\n", self.nb_remove_inter);
-        write!(&mut res, "    GC                   : {}\n", self.gc.get_nb_collected());
-        write!(&mut res, "\n");
-
-        write!(&mut res, "* * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * *\n");
+        writeln!(&mut res, "* * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * *");
+        writeln!(&mut res, "Reductions           : {}", steps);
+        writeln!(&mut res, "    APP-LAMBDA           : {}", self.nb_appabs);
+        writeln!(&mut res, "    FAN                  : {}", fansteps);
+        writeln!(&mut res, "        APP-FOU              : {}", self.nb_appfou);
+        writeln!(&mut res, "        FIN-LAMBDA           : {}", self.nb_finabs);
+        writeln!(&mut res, "        FIN-FOU (dup)        : {}", self.nb_finfou_d);
+        writeln!(&mut res, "        FIN-FOU (ann)        : {}", self.nb_finfou_a);
+        writeln!(&mut res);
+
+        writeln!(&mut res, "* * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * *");
+        writeln!(&mut res, "Nodes used           : {}", self.nb_used);
+        writeln!(&mut res, "    Max created          : {} ~ {}Mo {}Ko", self.max_node_len, created_size_mo, created_size_ko);
+        writeln!(&mut res, "    Max allocation       : {} ~ {}Mo {}Ko", self.max_node_capacity, max_size_mo, max_size_ko);
+        writeln!(&mut res, "    End allocation       : {} ~ {}Mo {}Ko", self.nodes.capacity(), size_mo, size_ko);
+        writeln!(&mut res, "        Nodes in use         : {}", self.nodes.len()-self.available_indexes.len());
+        writeln!(&mut res, "        Nodes available      : {}", self.available_indexes.len());
+        writeln!(&mut res, "    Reused               : {}", self.nb_reused);
+        writeln!(&mut res);
+
+        writeln!(&mut res, "* * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * *");
+        writeln!(&mut res, "Nodes removed        : {}", total_remove);
+        writeln!(&mut res, "    Interactions         : {}", self.nb_remove_inter);
+        writeln!(&mut res, "    GC                   : {}", self.gc.get_nb_collected());
+        writeln!(&mut res);
+
+        writeln!(&mut res, "* * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * * *");
         res.push_str(&self.gc.get_stats());
-        write!(&mut res, "\n");
+        writeln!(&mut res);
 
         res
     }
@@ -386,7 +386,7 @@ impl<MyGC:GC> Net<MyGC> {
                 self.nodes.push(nn);
                 self.nodes.len()-1
                     */
-        let res = match self.available_indexes.pop() {
+        match self.available_indexes.pop() {
             Some(idx) => {
                 self.nb_reused+=1;
                 self.nodes[idx] = nn;
@@ -396,8 +396,7 @@ impl<MyGC:GC> Net<MyGC> {
                 self.nodes.push(nn);
                 self.nodes.len()-1
             }
-        };
-        res
+        }
     }
 
     /// Create a new abstraction node.
@@ -508,7 +507,7 @@ impl<MyGC:GC> Net<MyGC> {
             let mut iter = self.available_indexes.iter();
             match (iter.find(|&&x| x==index)) {
                 None => (),
-                Some(a) => assert!(false, "pointing to a deleted node {}", a)
+                Some(a) => panic!("pointing to a deleted node {}", a)
             }
         }
     }
@@ -742,3 +741,10 @@ impl<MyGC:GC> Net<MyGC> {
     }  // End of fn interact
 
 }
+
+
+impl<MyGC:GC> Default for Net<MyGC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
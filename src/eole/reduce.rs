@@ -8,6 +8,124 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::marker::PhantomData;
 use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+
+/// Why a reducer stopped.
+/// A reducer keeps its state between its calls: after `CreditExhausted`, calling it again on the same net
+/// resumes the reduction where it stopped, e.g. to reduce in slices.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReductionOutcome {
+    /// The term is in normal form (full and strict reducers).
+    Normal,
+    /// The term is in head normal form (head reducer).
+    HeadNormal,
+    /// The term is in weak head normal form (lazy reducer).
+    WeakHeadNormal,
+    /// The credit ran out before the next interaction.
+    CreditExhausted,
+    /// The cancellation token was set before the next interaction, see `Limits`.
+    Cancelled,
+    /// The deadline passed before the next interaction, see `Limits`.
+    TimeLimit,
+    /// The net had too many live nodes before the next interaction, see `Limits`.
+    NodeLimit,
+    /// The net is in a state the reducer cannot handle.
+    Error(String),
+}
+
+/// A flag shared between a reduction and the outside (e.g. another thread), to stop the reduction.
+/// The clones of a token share the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Ask the reductions using the token to stop before their next interaction.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Limits of a reduction, checked by the reducers before each interaction, on top of their credit.
+/// When a limit is reached, the reducer stops with the matching outcome and keeps its state, as with the credit.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    cancel: Option<CancellationToken>,
+    deadline: Option<Instant>,
+    max_nodes: Option<usize>,
+    /// Number of checks, to look at the clock only from time to time.
+    ticks: usize,
+}
+
+impl Limits {
+    /// Number of checks between two looks at the clock.
+    const CLOCK_PERIOD:usize = 256;
+
+    /// Stop when the token is cancelled.
+    pub fn with_cancel(mut self, token:CancellationToken) -> Limits {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Stop once the deadline is passed. The clock is only checked every few interactions.
+    pub fn with_deadline(mut self, deadline:Instant) -> Limits {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Stop when the net has more than `max_nodes` live nodes, i.e. `net.nodes.len() - net.available_indexes.len()`.
+    pub fn with_max_nodes(mut self, max_nodes:usize) -> Limits {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    /// Check the limits before an interaction. Return the outcome to stop with, if any.
+    fn check<MyGC:GC>(&mut self, net:&Net<MyGC>) -> Option<ReductionOutcome> {
+        if let Some(token) = &self.cancel {
+            if token.is_cancelled() { return Some(ReductionOutcome::Cancelled); }
+        }
+        if let Some(max) = self.max_nodes {
+            if net.nodes.len() - net.available_indexes.len() > max { return Some(ReductionOutcome::NodeLimit); }
+        }
+        if let Some(deadline) = self.deadline {
+            self.ticks += 1;
+            if self.ticks % Limits::CLOCK_PERIOD == 1 && Instant::now() >= deadline { return Some(ReductionOutcome::TimeLimit); }
+        }
+        None
+    }
+}
+
+/// Reduction strategies, see the `get_reducer_*` functions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    Lazy,
+    Full,
+    Head,
+    Cbv,
+    Applicative,
+}
+
+/// A reducer, as returned by the `get_reducer_*` functions:
+/// called with the net, if the credit is used, and the credit (the maximal number of interactions).
+pub type Reducer<'a, MyGC> = Box<dyn FnMut(&mut Net<MyGC>, bool, usize) -> ReductionOutcome + 'a>;
+
+/// Action of the lazy reducer, called before each interaction with the net,
+/// the destructor about to interact and the stack of pending destructors.
+pub type LazyAction<'a, MyGC> = Box<dyn FnMut(&Net<MyGC>, ((usize,net::DstrK), &Vec<(usize, net::DstrK)>)) + 'a>;
+
+/// Action of the full reducer, called before each interaction with the net,
+/// the index of the destructor about to interact and the history.
+pub type FullAction<'a, MyGC> = Box<dyn FnMut(&Net<MyGC>, (usize, &Vec<(Vertex, net::NodeKind)>)) + 'a>;
 
 
 // --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- ---
@@ -20,45 +138,47 @@ use std::path::Path;
 /// is met. In other words, if the term is a lambda, it does not analyse its body.
 ///
 /// The reduction function maintains an internal stack of destructor to be applied:
-///     * If the stack is empty, we look at the root term.
+/// * If the stack is empty, we look at the root term.
 ///
-///         * If it is a constructor, the reduction is over
+///   * If it is a constructor, the reduction is over
 ///
-///         * Else, we push the destructor on the stack and start again the process with a non
-///           empty stack.
+///   * Else, we push the destructor on the stack and start again the process with a non
+///     empty stack.
 ///
-///     * Else, if the stack is non empty, we pop the top, which is a destructor.
-///       We test the main port of the destructor:
+/// * Else, if the stack is non empty, we pop the top, which is a destructor.
+///   We test the main port of the destructor:
 ///
-///         * Linked to the main port of a constructor: interaction! The next iteration of the loop
-///           will either deal with a previously stacked destructor or reach the empty stack case
-///           above. This mechanism is enough for inner constructors to "rise" towards their
-///           destructors.
+///   * Linked to the main port of a constructor: interaction! The next iteration of the loop
+///     will either deal with a previously stacked destructor or reach the empty stack case
+///     above. This mechanism is enough for inner constructors to "rise" towards their
+///     destructors.
 ///
-///         * Linked to an auxiliary port of a constructor: end of the reduction process.
-///           Poping the stack of destructor is useless as we know (see below) that their main
-///           port is linked to an other destructor, hence cannot interact.
-///           Note: This case is only acceptable for the aux port of an abstraction.
-///                 Reaching a fan out by an aux port is an error.
+///   * Linked to an auxiliary port of a constructor: end of the reduction process.
+///     Poping the stack of destructor is useless as we know (see below) that their main
+///     port is linked to an other destructor, hence cannot interact.
+///     Note: This case is only acceptable for the aux port of an abstraction,
+///     reaching a fan out by an aux port is an error.
 ///
-///         * Linked to an auxiliary port of an other destructor:
-///           Push again the current destructor, then push the reached destructor.
-///           The reached destructor will be the current one of the next iteration.
-///           Note: Reaching a destructor by its main port is an error.
+///   * Linked to an auxiliary port of an other destructor:
+///     Push again the current destructor, then push the reached destructor.
+///     The reached destructor will be the current one of the next iteration.
+///     Note: Reaching a destructor by its main port is an error.
+///
+/// The stack is kept when the credit runs out, or when a limit is reached: calling the reducer again resumes the reduction.
 pub fn get_reducer_lazy<'a, MyGC: GC, MyCPTR: Compactor>(
     should_compact: &'a dyn Fn(&Net<MyGC>)->bool,
-    mut action: Box<dyn FnMut(&Net<MyGC>, ((usize,net::DstrK), &Vec<(usize, net::DstrK)>)) + 'a>
-    ) ->  impl FnMut(&mut Net<MyGC>, bool, usize) + 'a {
+    mut action: LazyAction<'a, MyGC>,
+    mut limits: Limits
+    ) ->  impl FnMut(&mut Net<MyGC>, bool, usize) -> ReductionOutcome + 'a {
+
+    // Reduction stack: made of destructors.
+    // contains the index and the destructor's info
+    // Kept between the calls, to resume the reduction.
+    let mut stack: Vec<(usize, net::DstrK)> = vec![];
 
     move |net:&mut Net<MyGC>, test_credit:bool, mut credit:usize|
     {
-        // Reduction stack: made of destructors.
-        // contains the index and the destructor's info
-        let mut stack: Vec<(usize, net::DstrK)> = vec![];
-
         loop {
-            if test_credit && credit == 0 { break; }
-
             // DEBUG
             if cfg!(debug_assertions) {
                 let mut iter = stack.iter();
@@ -73,7 +193,7 @@ pub fn get_reducer_lazy<'a, MyGC: GC, MyCPTR: Compactor>(
                     let index = net.follow(Net::<MyGC>::ROOT_VERTEX).get_index();
                     match net.get_node(index).0 {
                         NodeKind::CstrK(_) => {
-                            break;
+                            return ReductionOutcome::WeakHeadNormal;
                         } // Constructor: stop
                         NodeKind::DstrK(d) => {
                             // Push the index of the destructor and its kind, loop.
@@ -93,9 +213,20 @@ pub fn get_reducer_lazy<'a, MyGC: GC, MyCPTR: Compactor>(
                         NodeKind::CstrK(c) => {
                             if tgt_port.0 == 0 {
                                 // Manage the credit
-                                if test_credit {credit-=1;}
+                                if test_credit {
+                                    if credit == 0 {
+                                        stack.push(head);
+                                        return ReductionOutcome::CreditExhausted;
+                                    }
+                                    credit-=1;
+                                }
+                                // Manage the limits
+                                if let Some(outcome) = limits.check(net) {
+                                    stack.push(head);
+                                    return outcome;
+                                }
                                 // Action (Graph printing)
-                                action(&net, (head, &stack));
+                                action(net, (head, &stack));
                                 // We must use clone() as c may contains a String (Abs case).
                                 let c = c.clone();
                                 net.interact(destr_index, destr_kind, tgt_index, c);
@@ -107,7 +238,11 @@ pub fn get_reducer_lazy<'a, MyGC: GC, MyCPTR: Compactor>(
                                     stack = stack .iter() .map(|&x| (cptr.adjust_i(x.0), x.1)) .collect::<Vec<_>>();
                                 }
                             } else {
-                                break;
+                                stack.clear();
+                                if let CstrK::FanOut(_) = c {
+                                    return ReductionOutcome::Error(String::from("Reaching a fan out by an aux port"));
+                                }
+                                return ReductionOutcome::WeakHeadNormal;
                             }
                         }
 
@@ -137,105 +272,290 @@ pub fn get_reducer_lazy<'a, MyGC: GC, MyCPTR: Compactor>(
 /// in a function call is not reduced before the function.
 pub fn get_reducer_full<'a, MyGC: GC, MyCPTR: Compactor>(
     should_compact: &'a dyn Fn(&Net<MyGC>)->bool,
-    mut action: Box<dyn FnMut(&Net<MyGC>, (usize, &Vec<(Vertex, net::NodeKind)>)) + 'a>
-    ) ->  impl FnMut(&mut Net<MyGC>, bool, usize) + 'a {
+    action: FullAction<'a, MyGC>,
+    limits: Limits
+    ) ->  impl FnMut(&mut Net<MyGC>, bool, usize) -> ReductionOutcome + 'a {
+    get_reducer_spine::<MyGC, MyCPTR>(should_compact, action, limits, Spine::Full)
+}
 
-    move |net:&mut Net<MyGC>, test_credit:bool, mut credit:usize|{
 
-        // History stack
-        let mut history: Vec<(Vertex, net::NodeKind)> = vec![];
 
-        // Main loop
-        loop {
-            if test_credit && credit == 0 { break; }
+// --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- ---
+// HEAD REDUCER
+// --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- ---
 
-            // Check the history of nodes:
-            match history.pop(){
-                // Empty: locate the next destructor starting from the root
-                None => {
-                    match locate_next_destructor(net, &mut history, Net::<MyGC>::ROOT_VERTEX) {
-                        None => { return; }
-                        Some(vert_kind) => { history.push(vert_kind); }
-                    }
-                }
-                // We have something
-                Some(head) => {
-                    let (vertex, kind) = &head;
-                    let (index, port) = vertex.as_tuple();
-                    assert!(net.get_node(index).1!=[Net::<MyGC>::NULL; 3], "Corrupted history: contains a null node. [main loop, history.pop()]");
-                    match kind {
-                        NodeKind::CstrK(CstrK::Abs(_,_)) => { /* */ }
+/// Return a function implementing a head reducer, stopping on a head normal form `x1->...->xn->y M1 ... Mk`.
+/// Like the full reducer, it goes under the abstractions along the spine of the term,
+/// but it stops as soon as the head variable `y` is reached: the arguments `M1 ... Mk` are not reduced.
+/// Hence, it terminates on the terms without normal form but with a head normal form,
+/// e.g. `x->x (delta delta)`, and allows to explore their Böhm tree level by level.
+pub fn get_reducer_head<'a, MyGC: GC, MyCPTR: Compactor>(
+    should_compact: &'a dyn Fn(&Net<MyGC>)->bool,
+    action: FullAction<'a, MyGC>,
+    limits: Limits
+    ) ->  impl FnMut(&mut Net<MyGC>, bool, usize) -> ReductionOutcome + 'a {
+    get_reducer_spine::<MyGC, MyCPTR>(should_compact, action, limits, Spine::Head)
+}
 
-                        NodeKind::CstrK(CstrK::FanOut(l)) => { /* */ }
 
-                        // Destructor: follow main
-                        NodeKind::DstrK(d) => {
-                            let target_v = net.follow(main(index));
-                            let (target_i, target_p) = target_v.as_tuple();
-                            assert!(net.get_node(target_i).1!=[Net::<MyGC>::NULL; 3], "Reaching a null node while checking a destructor's main port");
-                            match &net.get_node(target_i).0 {
-                                // Target Constructor
-                                NodeKind::CstrK(c) => {
-                                    if target_p.0 == 0 {
-                                        // Manage the credit
-                                        if test_credit {credit-=1;}
-
-                                        // If reaching the target of the main port, *must* be a constructor.
-                                        // Action (graph printing)
-                                        action(net, (index, &history) );
-
-                                        // Interaction.
-                                        let c = c.clone();
-                                        net.interact(index, *d, target_i, c);
-                                        // GC and compaction
-                                        MyGC::do_gc(net);
-                                        if (should_compact)(net) {
-                                            let mut cptr = MyCPTR::new();
-                                            cptr.init(net);
-                                            cptr.compact(net);
-                                            history.iter_mut().for_each(|x|{ x.0 = (cptr.adjust_v(x.0)); });
-                                        }
 
-                                    } else {
-                                        // No interaction. Must be an abstraction on port 2
-                                        if let CstrK::FanOut(_) = c { panic!("Reaching a fan out by an aux port"); }
-                                        assert!(target_p.0 == 2, "Reaching an Abstraction by the body");
-                                        // Backtrack until we find an application;
-                                        // visit its argument
-                                        history.push(head); // Must be done to take care of the current node
-                                        loop {
-                                            match history.pop(){
-                                                None => {
-                                                    // dprintln!("Stop with empty history");
-                                                    return;
-                                                } // End of the process
-                                                Some((v,k)) => {
-                                                    let hl = history.len();
-                                                    let (i,p) = v.as_tuple();
-                                                    assert!(net.get_node(i).1!=[Net::<MyGC>::NULL; 3], "Corrupted history: contains a null node. [backtrack loop, history.pop()]");
-                                                    match k {
-                                                        NodeKind::DstrK(DstrK::Apply) => {
-                                                            match locate_next_destructor(&net, &mut history, mkv(i,2)) {
-                                                                None => { history.truncate(hl); } // loop. Remove items added by locate_next_destructor
-                                                                Some(c) => {
-                                                                    history.push(c);
-                                                                    break;
-                                                                }
-                                                            }
-                                                        }
-                                                        _ => {} // Loop
+// --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- ---
+// STRICT REDUCERS
+// --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- ---
+
+/// Return a function implementing a call by value reducer: a full reducer which, before an `Apply >< Abs`
+/// interaction, reduces the argument of the application (behind its port 2) to a weak head normal form.
+/// It does useless work, and is only meant as a baseline for the full reducer:
+/// compare the number of interactions of both strategies.
+pub fn get_reducer_cbv<'a, MyGC: GC, MyCPTR: Compactor>(
+    should_compact: &'a dyn Fn(&Net<MyGC>)->bool,
+    action: FullAction<'a, MyGC>,
+    limits: Limits
+    ) ->  impl FnMut(&mut Net<MyGC>, bool, usize) -> ReductionOutcome + 'a {
+    get_reducer_spine::<MyGC, MyCPTR>(should_compact, action, limits, Spine::Value)
+}
+
+/// Return a function implementing an applicative order reducer: a full reducer which, before an `Apply >< Abs`
+/// interaction, reduces the argument of the application (behind its port 2) to a normal form.
+/// Like the call by value reducer, it is only meant as a baseline.
+pub fn get_reducer_applicative<'a, MyGC: GC, MyCPTR: Compactor>(
+    should_compact: &'a dyn Fn(&Net<MyGC>)->bool,
+    action: FullAction<'a, MyGC>,
+    limits: Limits
+    ) ->  impl FnMut(&mut Net<MyGC>, bool, usize) -> ReductionOutcome + 'a {
+    get_reducer_spine::<MyGC, MyCPTR>(should_compact, action, limits, Spine::Applicative)
+}
+
+
+
+// --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- ---
+// SPINE REDUCER
+// --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- --- ---
+
+/// Variants of the spine reducer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Spine {
+    /// Go back to the last application and reduce its argument when the head variable is reached.
+    Full,
+    /// Stop when the head variable is reached.
+    Head,
+    /// As `Full`, reducing the arguments to a weak head normal form before the `Apply >< Abs` interactions.
+    Value,
+    /// As `Full`, reducing the arguments to a normal form before the `Apply >< Abs` interactions.
+    Applicative,
+}
+
+/// Reduce along the spine of the term, going under the abstractions, until the head variable is reached.
+/// Then, depending on `spine`, stop or go back to the last application and reduce its argument.
+/// The history is kept when the credit runs out, or when a limit is reached: calling the reducer again resumes the reduction.
+///
+/// While the argument of an application is visited, the history holds a marker of the application: its argument vertex
+/// (the other entries of the applications are their vertex on the port 1). When a reduction in the argument pops the marker,
+/// the visit resumes from the argument: the arguments visited before, which are normal, are not walked again.
+///
+/// The strict variants keep the applications waiting for their argument in the history, with their index in `waiting`.
+/// An application is fired when its argument has no destructor to reduce any more (`ready`):
+///   * for a weak head normal form, when the argument is a constructor, or when its reduction reaches a variable;
+///   * for a normal form, when the backtracking pops the marker of the application.
+///
+/// Hence, the applicative variant fires the innermost interaction first: the one found in the argument of the innermost
+/// waiting application.
+fn get_reducer_spine<'a, MyGC: GC, MyCPTR: Compactor>(
+    should_compact: &'a dyn Fn(&Net<MyGC>)->bool,
+    mut action: FullAction<'a, MyGC>,
+    mut limits: Limits,
+    spine: Spine
+    ) ->  impl FnMut(&mut Net<MyGC>, bool, usize) -> ReductionOutcome + 'a {
+
+    // Outcome once the term is reduced
+    let done = if spine == Spine::Head { ReductionOutcome::HeadNormal } else { ReductionOutcome::Normal };
+
+    // History stack
+    // Kept between the calls, to resume the reduction, with the state of the strict variants.
+    let mut history: Vec<(Vertex, net::NodeKind)> = vec![];
+    // Applications waiting for their argument (strict variants), the last one being the innermost.
+    let mut waiting: Vec<usize> = vec![];
+    // Is the argument of the last waiting application reduced?
+    let mut ready = false;
+
+    move |net:&mut Net<MyGC>, test_credit:bool, mut credit:usize|{
+        let outcome = reduce_spine::<MyGC, MyCPTR>(net, test_credit, credit, should_compact, &mut action, spine, (&mut history, &mut waiting, &mut ready, &mut limits));
+        match outcome {
+            Ok(ReductionOutcome::Normal) => {
+                history.clear();
+                waiting.clear();
+                ready = false;
+                done.clone()
+            }
+            Ok(stopped) => stopped,
+            Err(e) => {
+                history.clear();
+                waiting.clear();
+                ready = false;
+                ReductionOutcome::Error(e)
+            }
+        }
+    }
+}
+
+/// State of the spine reducer: the history, the waiting applications, their readiness and the limits.
+type SpineState<'s> = (&'s mut Vec<(Vertex, net::NodeKind)>, &'s mut Vec<usize>, &'s mut bool, &'s mut Limits);
+
+/// Run the spine reducer from its state, see `get_reducer_spine`.
+/// Stop with `Ok(CreditExhausted)` when the credit runs out, with the outcome of a limit when it is reached,
+/// else with `Ok(Normal)` when the term is reduced.
+fn reduce_spine<MyGC: GC, MyCPTR: Compactor>(
+    net:&mut Net<MyGC>,
+    test_credit:bool,
+    mut credit:usize,
+    should_compact: &dyn Fn(&Net<MyGC>)->bool,
+    action: &mut FullAction<MyGC>,
+    spine: Spine,
+    (history, waiting, ready, limits): SpineState
+    ) -> Result<ReductionOutcome, String> {
+    // Main loop
+    loop {
+        // Check the history of nodes:
+        match history.pop(){
+            // Empty: locate the next destructor starting from the root
+            None => {
+                match locate_next_destructor(net, history, Net::<MyGC>::ROOT_VERTEX)? {
+                    None => { return Ok(ReductionOutcome::Normal); }
+                    Some(vert_kind) => { history.push(vert_kind); }
+                }
+            }
+            // We have something
+            Some(head) => {
+                let (vertex, kind) = &head;
+                let (index, port) = vertex.as_tuple();
+                assert!(net.get_node(index).1!=[Net::<MyGC>::NULL; 3], "Corrupted history: contains a null node. [main loop, history.pop()]");
+                match kind {
+                    NodeKind::CstrK(CstrK::Abs(_,_)) => { /* */ }
+
+                    // Marker of an application: resume the visit of its argument
+                    NodeKind::DstrK(DstrK::Apply) if port == Port::AUX2 => {
+                        history.push(head.clone());
+                        let hl = history.len();
+                        match locate_next_destructor(net, history, *vertex)? {
+                            Some(c) => { history.push(c); }
+                            None => {
+                                history.truncate(hl);
+                                if !backtrack(net, history, waiting, ready)? { return Ok(ReductionOutcome::Normal); }
+                            }
+                        }
+                    }
+
+                    NodeKind::CstrK(CstrK::FanOut(l)) => { /* */ }
+
+                    // Destructor: follow main
+                    NodeKind::DstrK(d) => {
+                        let target_v = net.follow(main(index));
+                        let (target_i, target_p) = target_v.as_tuple();
+                        assert!(net.get_node(target_i).1!=[Net::<MyGC>::NULL; 3], "Reaching a null node while checking a destructor's main port");
+                        match &net.get_node(target_i).0 {
+                            // Target Constructor
+                            NodeKind::CstrK(c) => {
+                                let strict = spine == Spine::Value || spine == Spine::Applicative;
+                                if target_p.0 == 0 {
+                                    // Strict variants: reduce the argument first
+                                    if let (true, DstrK::Apply, CstrK::Abs(..)) = (strict, d, c) {
+                                        if waiting.last() == Some(&index) && *ready {
+                                            waiting.pop();
+                                            *ready = false;
+                                        } else {
+                                            let hl = history.len();
+                                            history.push(head.clone());
+                                            let next = match spine {
+                                                Spine::Value => {
+                                                    let arg_v = net.follow(mkv(index, 2));
+                                                    match net.get_node(arg_v.get_index()).0 {
+                                                        NodeKind::DstrK(arg_d) => Some((arg_v, NodeKind::DstrK(arg_d))),
+                                                        NodeKind::CstrK(_) => None
                                                     }
                                                 }
+                                                _ => {
+                                                    // Visit the argument, behind its marker
+                                                    history.push((mkv(index, 2), NodeKind::DstrK(DstrK::Apply)));
+                                                    locate_next_destructor(net, history, mkv(index, 2))?
+                                                }
+                                            };
+                                            match next {
+                                                Some(c) => {
+                                                    if waiting.last() != Some(&index) { waiting.push(index); }
+                                                    history.push(c);
+                                                    continue;
+                                                }
+                                                None => {
+                                                    history.truncate(hl);
+                                                    if waiting.last() == Some(&index) { waiting.pop(); }
+                                                }
                                             }
                                         }
                                     }
+
+                                    // Manage the credit
+                                    if test_credit {
+                                        if credit == 0 {
+                                            history.push(head);
+                                            return Ok(ReductionOutcome::CreditExhausted);
+                                        }
+                                        credit-=1;
+                                    }
+                                    // Manage the limits
+                                    if let Some(outcome) = limits.check(net) {
+                                        history.push(head);
+                                        return Ok(outcome);
+                                    }
+
+                                    // If reaching the target of the main port, *must* be a constructor.
+                                    // Action (graph printing)
+                                    action(net, (index, history) );
+
+                                    // Interaction.
+                                    net.interact(index, *d, target_i, c.clone());
+                                    // GC and compaction
+                                    MyGC::do_gc(net);
+                                    // The strict variants reduce arguments whose nodes may be in the history, through the sharing:
+                                    // e.g. a stem fan in losing one of its copies, or an application shared by the copies of the argument
+                                    if strict {
+                                        forget_collected(net, history, waiting);
+                                    }
+                                    if (should_compact)(net) {
+                                        let mut cptr = MyCPTR::new();
+                                        cptr.init(net);
+                                        cptr.compact(net);
+                                        history.iter_mut().for_each(|x|{ x.0 = (cptr.adjust_v(x.0)); });
+                                        waiting.iter_mut().for_each(|x|{ *x = cptr.adjust_i(*x); });
+                                    }
+
+                                } else {
+                                    // No interaction. Must be an abstraction on port 2
+                                    if let CstrK::FanOut(_) = c { return Err(String::from("Reaching a fan out by an aux port")); }
+                                    assert!(target_p.0 == 2, "Reaching an Abstraction by the body");
+                                    // Head variable reached: the head reducer is done
+                                    if spine == Spine::Head { return Ok(ReductionOutcome::HeadNormal); }
+                                    // Weak head normal form of a waiting argument reached: go back to its application
+                                    if let (Spine::Value, Some(&w)) = (spine, waiting.last()) {
+                                        while let Some(entry) = history.pop() {
+                                            if entry.0.get_index() == w {
+                                                history.push(entry);
+                                                break;
+                                            }
+                                        }
+                                        *ready = true;
+                                        continue;
+                                    }
+                                    // Backtrack until we find an application;
+                                    // visit its argument
+                                    history.push(head); // Must be done to take care of the current node
+                                    if !backtrack(net, history, waiting, ready)? { return Ok(ReductionOutcome::Normal); }
                                 }
-                                // Target Destructor
-                                // Destructor: stack and relaunch
-                                NodeKind::DstrK(d) => {
-                                    history.push(head);
-                                    history.push((target_v, NodeKind::DstrK(*d)));
-                                }
+                            }
+                            // Target Destructor
+                            // Destructor: stack and relaunch
+                            NodeKind::DstrK(d) => {
+                                history.push(head);
+                                history.push((target_v, NodeKind::DstrK(*d)));
                             }
                         }
                     }
@@ -245,13 +565,76 @@ pub fn get_reducer_full<'a, MyGC: GC, MyCPTR: Compactor>(
     }
 }
 
+/// Backtrack until an application whose argument is not visited yet, and locate the next destructor in its argument.
+/// Popping the marker of a waiting application means that its argument is normal: it is ready to be fired.
+/// Return `Ok(false)` when the history is empty, i.e. when the term is reduced.
+fn backtrack<MyGC:GC>(
+    net:&Net::<MyGC>, history:&mut Vec<(Vertex, net::NodeKind)>, waiting:&[usize], ready:&mut bool
+    ) -> Result<bool, String> {
+    while let Some((v,k)) = history.pop() {
+        let (i,p) = v.as_tuple();
+        assert!(net.get_node(i).1!=[Net::<MyGC>::NULL; 3], "Corrupted history: contains a null node. [backtrack loop, history.pop()]");
+        if let NodeKind::DstrK(DstrK::Apply) = k {
+            if p == Port::AUX2 {
+                // Normal form of a waiting argument reached: fire its application
+                if waiting.last() == Some(&i) {
+                    *ready = true;
+                    return Ok(true);
+                }
+            } else if waiting.last() == Some(&i) {
+                // Same, for a waiting application whose marker is forgotten
+                history.push((v, k));
+                *ready = true;
+                return Ok(true);
+            } else {
+                // Visit the argument, behind its marker
+                history.push((mkv(i, 2), k));
+                let hl = history.len();
+                match locate_next_destructor(net, history, mkv(i,2))? {
+                    None => { history.truncate(hl-1); } // loop. Remove items added by locate_next_destructor, and the marker
+                    Some(c) => {
+                        history.push(c);
+                        return Ok(true);
+                    }
+                }
+            }
+        } // Else: loop
+    }
+    // dprintln!("Stop with empty history");
+    Ok(false)
+}
+
+/// Forget the entries of the history whose nodes were removed by an interaction or collected.
+/// A stem fan in losing one of its copies is replaced by a wire: the path through it is still valid, only its entry is dropped.
+/// Any other removed node cuts the path: the history is forgotten from it, and the visit resumes from the last marker left.
+fn forget_collected<MyGC:GC>(net:&Net::<MyGC>, history:&mut Vec<(Vertex, net::NodeKind)>, waiting:&mut Vec<usize>) {
+    let alive = |x:&(Vertex, net::NodeKind)| net.get_node(x.0.get_index()).1 != [Net::<MyGC>::NULL; 3];
+    // Before their indexes are reused
+    waiting.retain(|w| net.get_node(*w).1 != [Net::<MyGC>::NULL; 3]);
+    if let Some(first) = history.iter().position(|x| !alive(x)) {
+        let mut kept = first;
+        for i in first..history.len() {
+            if alive(&history[i]) {
+                history.swap(kept, i);
+                kept += 1;
+            } else if !matches!(history[i].1, NodeKind::DstrK(DstrK::FanIn(FIStatus::Stem))) {
+                break;
+            }
+        }
+        history.truncate(kept);
+        let visited:HashSet<usize> = history.iter().map(|x| x.0.get_index()).collect();
+        waiting.retain(|w| visited.contains(w));
+    }
+}
+
 /// Get the "next" destructor following base.
 /// Also update the history as it go down the graph.
+/// Fail if the net is not in a state the spine reducer can handle.
 /// On failure, the history must be restored (i.e. truncated) back to its original length.
 #[inline]
 fn locate_next_destructor<MyGC:GC>(
     net:&Net::<MyGC>, history:&mut Vec<(Vertex, net::NodeKind)>, mut base:Vertex
-    ) -> Option<(Vertex, net::NodeKind)> {
+    ) -> Result<Option<(Vertex, net::NodeKind)>, String> {
     loop {
         let next_v = net.follow(base);
         let (next_i, next_p) = next_v.as_tuple();
@@ -264,8 +647,8 @@ fn locate_next_destructor<MyGC:GC>(
                     history.push((next_v, next_n.0.clone()));
                     base = mkv(next_i, 1);
                 }
-                2 => {return None;}
-                _ => {panic!("Reaching an abstraction by the body");}
+                2 => {return Ok(None);}
+                _ => {return Err(String::from("Reaching an abstraction by the body"));}
             }
             NodeKind::CstrK(CstrK::FanOut(l)) => {
                 assert!(next_p.0 == 0, "Fan out must be entered by the main port");
@@ -273,7 +656,7 @@ fn locate_next_destructor<MyGC:GC>(
                     None => {
                         let path = Path::new("generated");
                         conversion::do_graph(net, path, 999999);
-                        panic!("Cannot pair fan out {:?}\n{:?}",(next_i, l), history);
+                        return Err(format!("Cannot pair fan out {:?}\n{:?}",(next_i, l), history));
                     }
                     Some(p) => {
                         history.push((next_v, next_n.0.clone()));
@@ -283,7 +666,7 @@ fn locate_next_destructor<MyGC:GC>(
 
             }
             NodeKind::DstrK(d) => {
-                return Some((next_v, NodeKind::DstrK(*d)));
+                return Ok(Some((next_v, NodeKind::DstrK(*d))));
             }
         }
     }
@@ -291,7 +674,7 @@ fn locate_next_destructor<MyGC:GC>(
 
 
 #[inline]
-pub fn get_matching_fan<MyGC:GC>(net:&Net::<MyGC>, fan_out_l:u64, history:&Vec<(Vertex, net::NodeKind)>) -> Option<Port> {
+pub fn get_matching_fan<MyGC:GC>(net:&Net::<MyGC>, fan_out_l:u64, history:&[(Vertex, net::NodeKind)]) -> Option<Port> {
     let mut lab_skip:HashMap<u64, i64> = HashMap::new();
 
     for (v, k) in (history.iter()).rev() {
@@ -320,5 +703,5 @@ pub fn get_matching_fan<MyGC:GC>(net:&Net::<MyGC>, fan_out_l:u64, history:&Vec<(
     }
 
     // Not found
-    return None;
+    None
 }
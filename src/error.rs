@@ -0,0 +1,383 @@
+//! # Errors
+//! Errors raised while reading, resolving and converting éole files.
+//!
+//! Errors carry the location (`Span`) of the faulty item.
+//! They are rendered with a snippet of the source and a caret, using the files recorded in `Sources`:
+//! ```text
+//! error: unknown symbol `idd`
+//!  --> tests/00_def.eole:12:1
+//!    |
+//! 12 | idd.
+//!    | ^^^
+//!    = help: did you mean `id`?
+//! ```
+
+use eole_parser::{ast::Span, blc::BlcError, infix::{InfixError, InfixErrorKind}, ml::MlError, lexer::Tok, syntax::SyntaxError, ParseError, ErrorRecovery};
+use crate::eal::Reason;
+
+use std::fmt::{self, Write};
+use std::io;
+use std::path::{Path, PathBuf};
+
+
+/// Kind of errors.
+#[derive(Debug)]
+pub enum ErrorKind {
+    /// A file could not be read.
+    Io{path:PathBuf, error:io::Error},
+    /// A file could not be parsed.
+    Parse{message:String},
+    /// A `read` path was not found, neither relatively to the importing file nor in the search paths.
+    NotFound{path:String},
+    /// A file (indirectly) imports itself. Record the chain of imports, ending with the repeated file.
+    Cycle(Vec<PathBuf>),
+    /// Definitions use each other in a cycle, outside of a `rec` group. Record the names along the cycle.
+    CyclicDefinitions{chain:Vec<String>},
+    /// A name is defined more than once. Record the location of the first definition.
+    Duplicate{name:String, first:Span},
+    /// A symbol is not bound. Record a close name in scope, if any.
+    UnknownSymbol{name:String, suggestion:Option<String>},
+    /// An operator is not declared. Record a close operator, if any.
+    UnknownOperator{name:String, suggestion:Option<String>},
+    /// Operators of the same precedence cannot be grouped, see `eole_parser::infix`.
+    AmbiguousOperators{left:String, right:String},
+    /// A pragma is not known. Record a close pragma, if any.
+    UnknownPragma{name:String, suggestion:Option<String>},
+    /// A pragma is given a wrong value. Record a description of the expected value.
+    InvalidPragma{name:String, expected:String},
+    /// The normal forms of the sides of an assertion are not alpha-equivalent. Record both normal forms.
+    AssertionFailed{lhs:String, rhs:String},
+    /// The encoding of a literal is too deep for the passes on the terms, see `encoding::MAX_DEPTH`.
+    LiteralTooLarge{depth:usize, max:usize},
+    /// The wildcard `_` is used as a variable.
+    Wildcard,
+    /// An arm of a case matches an unknown constructor. Record a close constructor, if any.
+    UnknownConstructor{name:String, suggestion:Option<String>},
+    /// An arm of a case matches a constructor of another data type than the first arm.
+    ConstructorMismatch{name:String, data:String},
+    /// An arm of a case binds a wrong number of fields.
+    ConstructorArity{name:String, expected:usize, found:usize},
+    /// Two arms of a case match the same constructor (or are both `_`).
+    DuplicateArm{name:String},
+    /// A case does not match all the constructors of its data type. Record the missing ones.
+    MissingArms{data:String, missing:Vec<String>},
+    /// A term cannot be typed: the type variable `var` would be equal to the type `ty`, in which it occurs.
+    InfiniteType{var:String, ty:String},
+    /// A term to run, designated by its name or its line, is not typable in elementary affine logic: its result may be wrong.
+    NotElementary{run:String, reason:Reason},
+    /// Mini-ML: an expression or a pattern does not have the expected type.
+    TypeMismatch{expected:String, found:String},
+    /// Mini-ML: an expression which is not a function is applied.
+    NotAFunction{ty:String},
+    /// Mini-ML: a type is not declared. Record a close type, if any.
+    UnknownType{name:String, suggestion:Option<String>},
+    /// Mini-ML: a type is given a wrong number of arguments.
+    TypeArity{name:String, expected:usize, found:usize},
+    /// Mini-ML: a constructor is given a wrong number of arguments.
+    ConstructorArguments{name:String, expected:usize, found:usize},
+    /// Mini-ML: a pattern matching does not match all the values. Record a value which is not matched.
+    NonExhaustive{missing:String},
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        use self::ErrorKind::*;
+        match self {
+            Io{path, error} => write!(f, "cannot read {}: {}", path.display(), error),
+            Parse{message} => write!(f, "{}", message),
+            NotFound{path} => write!(f, "cannot find `{}`", path),
+            Cycle(chain) => {
+                let chain:Vec<String> = chain.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "import cycle: {}", chain.join(" -> "))
+            }
+            CyclicDefinitions{chain} => write!(f, "cyclic definitions: {}", chain.join(" -> ")),
+            Duplicate{name, ..} => write!(f, "`{}` is defined more than once", name),
+            UnknownSymbol{name, ..} => write!(f, "unknown symbol `{}`", name),
+            UnknownOperator{name, ..} => write!(f, "unknown operator `{}`", name),
+            AmbiguousOperators{left, right} =>
+                write!(f, "`{}` and `{}` have the same precedence, and cannot be grouped", left, right),
+            UnknownPragma{name, ..} => write!(f, "unknown pragma `{}`", name),
+            InvalidPragma{name, expected} => write!(f, "`#pragma {}` expects {}", name, expected),
+            AssertionFailed{..} => write!(f, "assertion failed: the normal forms differ"),
+            LiteralTooLarge{depth, max} => write!(f, "literal too large: its encoding is {} terms deep, the maximum is {}", depth, max),
+            Wildcard => write!(f, "`_` can only be used as a binder"),
+            UnknownConstructor{name, ..} => write!(f, "unknown constructor `{}`", name),
+            ConstructorMismatch{name, data} => write!(f, "`{}` is not a constructor of `{}`", name, data),
+            ConstructorArity{name, expected, found} =>
+                write!(f, "`{}` has {} field(s), but the arm binds {}", name, expected, found),
+            DuplicateArm{name} => write!(f, "`{}` is matched more than once", name),
+            MissingArms{data, missing} => {
+                let missing:Vec<String> = missing.iter().map(|c| format!("`{}`", c)).collect();
+                write!(f, "non-exhaustive case on `{}`: {} not matched", data, missing.join(", "))
+            }
+            NotElementary{run, reason} => write!(f, "the result of {} may be wrong: {}", run, reason),
+            InfiniteType{var, ty} => write!(f, "cannot type this term: `{}` would be the infinite type `{}`", var, ty),
+            TypeMismatch{expected, found} => write!(f, "mismatched types: expected `{}`, found `{}`", expected, found),
+            NotAFunction{ty} => write!(f, "this expression has type `{}`, it is not a function and cannot be applied", ty),
+            UnknownType{name, ..} => write!(f, "unknown type `{}`", name),
+            TypeArity{name, expected, found} =>
+                write!(f, "the type `{}` expects {} argument(s), but is given {}", name, expected, found),
+            ConstructorArguments{name, expected, found} =>
+                write!(f, "the constructor `{}` expects {} argument(s), but is given {}", name, expected, found),
+            NonExhaustive{missing} => write!(f, "non-exhaustive pattern matching: `{}` is not matched", missing),
+        }
+    }
+}
+
+
+
+/// An error, with its location if it comes from a source file.
+#[derive(Debug)]
+pub struct EoleError {
+    pub kind:ErrorKind,
+    pub span:Option<Span>
+}
+
+impl EoleError {
+
+    /// Create a new error located at `span`.
+    pub fn new(kind:ErrorKind, span:Span) -> EoleError {
+        EoleError{kind, span:Some(span)}
+    }
+
+    /// Create a new error without location.
+    pub fn unlocated(kind:ErrorKind) -> EoleError {
+        EoleError{kind, span:None}
+    }
+
+    /// Create an error from an error of the parser, in the file `file`.
+    pub fn from_parse(file:usize, e:ParseError<usize, Tok, SyntaxError>) -> EoleError {
+        let (message, lo, hi) = match e {
+            ParseError::InvalidToken{location} =>
+                (String::from("invalid token"), location, location+1),
+            ParseError::UnrecognizedEOF{location, expected} =>
+                (format!("unexpected end of file{}", expecting(&expected)), location, location),
+            ParseError::UnrecognizedToken{token:(lo, t, hi), expected} =>
+                (format!("unexpected `{}`{}", t, expecting(&expected)), lo, hi),
+            ParseError::ExtraToken{token:(lo, t, hi)} =>
+                (format!("extra token `{}`", t), lo, hi),
+            ParseError::User{error} =>
+                (error.to_string(), error.lo, error.hi),
+        };
+        EoleError::new(ErrorKind::Parse{message}, Span::new(file, lo, hi))
+    }
+
+    /// Create an error from an error the parser recovered from, in the file `file`.
+    pub fn from_recovery(file:usize, e:ErrorRecovery<usize, Tok, SyntaxError>) -> EoleError {
+        Self::from_parse(file, e.error)
+    }
+
+    /// Create an error from an error while reading binary lambda calculus, in the file `file`.
+    pub fn from_blc(file:usize, e:BlcError) -> EoleError {
+        EoleError::new(ErrorKind::Parse{message:e.to_string()}, Span::new(file, e.lo, e.hi))
+    }
+
+    /// Create an error from a syntax error of Mini-ML, in the file `file`.
+    pub fn from_ml(file:usize, e:MlError) -> EoleError {
+        EoleError::new(ErrorKind::Parse{message:e.to_string()}, Span::new(file, e.lo, e.hi))
+    }
+
+    /// Create an error from an error while resolving the operators, suggesting one of the declared `operators`.
+    pub fn from_infix<'a, I:IntoIterator<Item=&'a String>>(e:InfixError, operators:I) -> EoleError {
+        let kind = match e.kind {
+            InfixErrorKind::Unknown(name) => {
+                let suggestion = suggest(&name, operators);
+                ErrorKind::UnknownOperator{name, suggestion}
+            }
+            InfixErrorKind::Ambiguous(left, right) => ErrorKind::AmbiguousOperators{left, right},
+        };
+        EoleError::new(kind, e.span)
+    }
+
+    /// Get the help message of the error, if any.
+    pub fn help(&self) -> Option<String> {
+        match &self.kind {
+            ErrorKind::UnknownSymbol{suggestion:Some(s), ..} |
+            ErrorKind::UnknownConstructor{suggestion:Some(s), ..} |
+            ErrorKind::UnknownType{suggestion:Some(s), ..} |
+            ErrorKind::UnknownOperator{suggestion:Some(s), ..} |
+            ErrorKind::UnknownPragma{suggestion:Some(s), ..} => Some(format!("did you mean `{}`?", s)),
+            ErrorKind::UnknownOperator{..} => Some(String::from("declare it, e.g. `infixl 6 + = plus.`")),
+            ErrorKind::AmbiguousOperators{..} => Some(String::from("add parenthesis")),
+            ErrorKind::CyclicDefinitions{..} => Some(String::from("recursive definitions must be introduced with `rec`")),
+            ErrorKind::LiteralTooLarge{..} => Some(String::from("use a smaller literal, or the binary numerals (`--numerals binary`)")),
+            ErrorKind::MissingArms{..} => Some(String::from("add the missing arms, or a `_` arm")),
+            ErrorKind::NonExhaustive{..} => Some(String::from("add an arm matching this value, or a `_` arm")),
+            ErrorKind::NotElementary{..} =>
+                Some(String::from("the reduction without oracle is only known to be correct for the terms typable in elementary affine logic")),
+            ErrorKind::InfiniteType{..} => Some(String::from("types are finite: self-applications such as `x -> x x`, and Scott-encoded recursive data types, cannot be typed")),
+            _ => None
+        }
+    }
+
+    /// Render the error with a snippet of the source.
+    pub fn render(&self, sources:&Sources) -> String {
+        self.render_as("error", sources)
+    }
+
+    /// Render the error as a warning, with a snippet of the source.
+    pub fn render_warning(&self, sources:&Sources) -> String {
+        self.render_as("warning", sources)
+    }
+
+    fn render_as(&self, severity:&str, sources:&Sources) -> String {
+        let mut res = String::new();
+        let _ = writeln!(&mut res, "{}: {}", severity, self.kind);
+        if let Some(span) = self.span {
+            res.push_str(&sources.snippet(span));
+        }
+        if let Some(help) = self.help() {
+            let _ = writeln!(&mut res, "{} = help: {}", sources.margin(self.span), help);
+        }
+        if let ErrorKind::AssertionFailed{lhs, rhs} = &self.kind {
+            let margin = sources.margin(self.span);
+            let _ = writeln!(&mut res, "{} = left:  {}", margin, lhs);
+            let _ = writeln!(&mut res, "{} = right: {}", margin, rhs);
+        }
+        if let ErrorKind::Duplicate{first, ..} = &self.kind {
+            let _ = writeln!(&mut res, "note: first defined here");
+            res.push_str(&sources.snippet(*first));
+        }
+        res
+    }
+}
+
+impl fmt::Display for EoleError {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        self.kind.fmt(f)
+    }
+}
+
+impl std::error::Error for EoleError {}
+
+
+/// Sort errors by location, unlocated errors first, and render them.
+pub fn render_all(errors:&mut [EoleError], sources:&Sources) -> String {
+    errors.sort_by_key(|e| e.span.map(|s| (s.file, s.lo)));
+    errors.iter().map(|e| e.render(sources)).collect::<Vec<_>>().join("\n")
+}
+
+
+/// Sort warnings by location, unlocated warnings first, and render them.
+pub fn render_warnings(warnings:&mut [EoleError], sources:&Sources) -> String {
+    warnings.sort_by_key(|e| e.span.map(|s| (s.file, s.lo)));
+    warnings.iter().map(|e| e.render_warning(sources)).collect::<Vec<_>>().join("\n")
+}
+
+
+/// Format a list of expected tokens.
+/// The parser gives them quoted: keywords and symbols are put in backquotes, other tokens (e.g. `<identifier>`) are left as they are.
+fn expecting(expected:&[String]) -> String {
+    let expected:Vec<String> = expected.iter()
+        .map(|t| t.trim_matches('"'))
+        .map(|t| if t.starts_with('<') { t.to_string() } else { format!("`{}`", t) })
+        .collect();
+    match expected.len() {
+        0 => String::new(),
+        1 => format!(", expected {}", expected[0]),
+        _ => format!(", expected one of {}", expected.join(", "))
+    }
+}
+
+
+/// Get the closest name (in the Levenshtein distance sense) among candidates.
+/// Names too far away from `name` (more than a third of its length) are not considered.
+pub fn suggest<'a, I:IntoIterator<Item=&'a String>>(name:&str, candidates:I) -> Option<String> {
+    let max = name.chars().count()/3;
+    candidates.into_iter()
+        .map(|c| (levenshtein(name, c), c))
+        .filter(|(d, _)| *d <= max)
+        .min()
+        .map(|(_, c)| c.clone())
+}
+
+/// Levenshtein distance between two strings.
+fn levenshtein(a:&str, b:&str) -> usize {
+    let b:Vec<char> = b.chars().collect();
+    let mut row:Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i+1;
+        for (j, cb) in b.iter().enumerate() {
+            let up = row[j+1];
+            row[j+1] = if ca == *cb { diag } else { 1 + diag.min(up).min(row[j]) };
+            diag = up;
+        }
+    }
+    row[b.len()]
+}
+
+
+
+/// Record the source files, identified by their index.
+#[derive(Debug, Default)]
+pub struct Sources {
+    files:Vec<(PathBuf, String)>
+}
+
+impl Sources {
+
+    /// Create an empty source map.
+    pub fn new() -> Sources {
+        Sources{ files:vec![] }
+    }
+
+    /// Record a new file and returns its identifier.
+    pub fn add(&mut self, path:&Path, text:String) -> usize {
+        self.files.push((path.to_path_buf(), text));
+        self.files.len()-1
+    }
+
+    /// Get the path of a file.
+    pub fn path(&self, file:usize) -> &Path {
+        &self.files[file].0
+    }
+
+    /// Get the content of a file.
+    pub fn text(&self, file:usize) -> &str {
+        &self.files[file].1
+    }
+
+    /// Get the line and the column (both starting at 1, the column being counted in characters)
+    /// of an offset in a file.
+    pub fn line_col(&self, file:usize, offset:usize) -> (usize, usize) {
+        let text = self.text(file);
+        let before = &text[..text.floor_char_boundary(offset)];
+        let line = before.matches('\n').count() + 1;
+        let col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        (line, col)
+    }
+
+    /// Blank margin used to align the lines of a snippet with the line number of the span.
+    pub fn margin(&self, span:Option<Span>) -> String {
+        match span {
+            Some(span) if span.file < self.files.len() => {
+                let (line, _) = self.line_col(span.file, span.lo);
+                " ".repeat(line.to_string().len())
+            }
+            _ => String::new()
+        }
+    }
+
+    /// Render the location of a span with the first line it covers, underlined by carets.
+    pub fn snippet(&self, span:Span) -> String {
+        if span.file >= self.files.len() {
+            return String::new();
+        }
+        let text = self.text(span.file);
+        let (line, col) = self.line_col(span.file, span.lo);
+        let src = text.lines().nth(line-1).unwrap_or("");
+        // Underline up to the end of the span or of the line
+        let (lo, hi) = (text.floor_char_boundary(span.lo), text.floor_char_boundary(span.hi));
+        let width = text[lo..hi.max(lo)]
+            .lines().next().map(|l| l.chars().count()).unwrap_or(0).max(1);
+        let margin = self.margin(Some(span));
+
+        let mut res = String::new();
+        let _ = writeln!(&mut res, "{}--> {}:{}:{}", margin, self.path(span.file).display(), line, col);
+        let _ = writeln!(&mut res, "{} |", margin);
+        let _ = writeln!(&mut res, "{} | {}", line, src);
+        let _ = writeln!(&mut res, "{} | {}{}", margin, " ".repeat(col-1), "^".repeat(width));
+        res
+    }
+}
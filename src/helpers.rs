@@ -5,8 +5,9 @@
 /// Print on stderr when in debug mode.
 /// Works like `eprint!'
 /// ```no_run
+/// # #[macro_use] extern crate eolelib;
 /// let a = 5;
-/// dprint("value of a is {}\n", a);
+/// dprint!("value of a is {}\n", a);
 /// ```
 #[macro_export]
 macro_rules! dprint {
@@ -18,8 +19,9 @@ macro_rules! dprint {
 /// Print on stderr when in debug mode.
 /// Works like `eprintln!'
 /// ```no_run
+/// # #[macro_use] extern crate eolelib;
 /// let a = 5;
-/// dprintln("value of a is {}\n", a);
+/// dprintln!("value of a is {}", a);
 /// ```
 #[macro_export]
 macro_rules! dprintln {
@@ -0,0 +1,401 @@
+//! # Import resolution
+//! Resolve the `read` sentences of a text, producing a flat list of sentences without any import.
+//!
+//! ## Paths
+//! A path is first looked up relative to the directory of the importing file.
+//! If it cannot be found there, the search paths are tried in order.
+//! The search paths come from the command line and from the `EOLE_PATH` environment variable,
+//! which works like `PATH` (e.g. `EOLE_PATH=/usr/share/eole:~/eole`).
+//!
+//! ## Namespaces
+//! `read ./file.eole` adds the definitions of `file.eole` as they are.
+//! `read ./file.eole as other` prefixes them with `other::`, so `id` becomes `other::id`.
+//! The references made inside `file.eole` are renamed accordingly.
+//! Only the definitions and the data types are imported: the terms to run, the assertions and the pragmas found in an imported file are ignored.
+//! The constructors of the data types are definitions: they are prefixed like the other ones.
+//!
+//! ## Operators
+//! The operators declared in a file (`infixl 6 + = plus.`) can be used in the whole file, and in the files reading it.
+//! With a namespace, they stand for the prefixed definitions: `+` stands for `other::plus`.
+//! The operator expressions are resolved once the imports are, see `eole_parser::infix`.
+//!
+//! ## Binary lambda calculus
+//! Files with the `.blc` (bits form) and `.Blc` (bytes form) extensions are read as binary lambda calculus, see `eole_parser::blc`.
+//! Such a file contains a single term to run, and no definition: reading it from another file imports nothing.
+//!
+//! ## Errors
+//! Import cycles, and definitions or operators declared more than once, are reported as errors.
+
+use eole_parser::{ast::{*, Term::*}, blc, infix::{self, Fixities}, lexer::Lexer, parser, syntax::SyntaxError};
+use crate::error::{EoleError, ErrorKind, Sources};
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+
+/// Name of the environment variable containing the search paths.
+pub const EOLE_PATH:&str = "EOLE_PATH";
+
+
+
+/// Resolve the imports, starting from a root file.
+pub struct Importer {
+    /// Directories where the files are looked up when not found relatively to the importing file.
+    search_paths:Vec<PathBuf>,
+    /// Files being imported, the last one being the current file. Used to detect cycles.
+    stack:Vec<PathBuf>,
+    /// Files read so far, used to render the errors.
+    sources:Sources,
+}
+
+impl Importer {
+
+    /// Create a new importer with the given search paths.
+    pub fn new(search_paths:Vec<PathBuf>) -> Importer {
+        Importer{ search_paths, stack:vec![], sources:Sources::new() }
+    }
+
+    /// Get the search paths from the `EOLE_PATH` environment variable.
+    pub fn env_search_paths() -> Vec<PathBuf> {
+        match env::var_os(EOLE_PATH) {
+            None => vec![],
+            Some(paths) => env::split_paths(&paths).collect()
+        }
+    }
+
+    /// Get the files read so far.
+    pub fn sources(&self) -> &Sources {
+        &self.sources
+    }
+
+    /// Get the files read so far, to record more files.
+    pub fn sources_mut(&mut self) -> &mut Sources {
+        &mut self.sources
+    }
+
+    /// Read, parse and resolve a file.
+    /// The resulting sentences do not contain any `read`, and their operators are resolved.
+    pub fn load(&mut self, path:&Path) -> Result<Vec<Sentence>, Vec<EoleError>> {
+        self.load_(path, None)
+    }
+
+    /// Read, parse and resolve a file, read by the sentence located at `from` (if any).
+    fn load_(&mut self, path:&Path, from:Option<Span>) -> Result<Vec<Sentence>, Vec<EoleError>> {
+        let io_error = |error| vec![EoleError{kind:ErrorKind::Io{path:path.to_path_buf(), error}, span:from}];
+        match path.extension().and_then(|e| e.to_str()) {
+            // Binary lambda calculus, bytes form
+            Some(blc::BYTES_EXTENSION) => {
+                let bytes = fs::read(path).map_err(io_error)?;
+                let file = self.sources.add(path, blc::bytes_as_bits(&bytes));
+                let term = blc::from_bytes(file, &bytes).map_err(|e| vec![EoleError::from_blc(file, e)])?;
+                Ok(vec![blc_run(term)])
+            }
+            // Binary lambda calculus, bits form
+            Some(blc::BITS_EXTENSION) => {
+                let source = fs::read_to_string(path).map_err(io_error)?;
+                let file = self.sources.add(path, source);
+                let term = blc::from_bits(file, self.sources.text(file)).map_err(|e| vec![EoleError::from_blc(file, e)])?;
+                Ok(vec![blc_run(term)])
+            }
+            // Éole
+            _ => {
+                let source = fs::read_to_string(path).map_err(io_error)?;
+                let file = self.sources.add(path, source);
+                let text = parse(file, self.sources.text(file))?;
+                self.resolve(&text, path, from)
+            }
+        }
+    }
+
+    /// Resolve the imports of sentences read from `path`, itself read by the sentence located at `from` (if any).
+    /// The resulting sentences do not contain any `read`, and their operators are resolved.
+    pub fn resolve(&mut self, sentences:&[Sentence], path:&Path, from:Option<Span>) -> Result<Vec<Sentence>, Vec<EoleError>> {
+        let key = canonical(path);
+        if let Some(pos) = self.stack.iter().position(|p| *p == key) {
+            let mut chain = self.stack[pos..].to_vec();
+            chain.push(key);
+            return Err(vec![EoleError{kind:ErrorKind::Cycle(chain), span:from}]);
+        }
+        self.stack.push(key);
+        let res = self.resolve_(sentences, path);
+        self.stack.pop();
+        res
+    }
+
+    fn resolve_(&mut self, sentences:&[Sentence], path:&Path) -> Result<Vec<Sentence>, Vec<EoleError>> {
+        let mut res = vec![];
+        let mut errors = vec![];
+        let mut defined = HashMap::new();
+        let mut operators = HashMap::new();
+        let mut fixities = Fixities::new();
+
+        for s in sentences {
+            match s {
+                Sentence::Let(d) => {
+                    if let Err(e) = declare(&mut defined, &d.vname, d.span) { errors.push(e); }
+                    res.push(s.clone());
+                }
+
+                Sentence::Run(_) | Sentence::Assert(_) | Sentence::Pragma(_) => res.push(s.clone()),
+
+                Sentence::Infix(d) => {
+                    match declare(&mut operators, &d.op, d.span) {
+                        Ok(()) => { fixities.insert(d.op.clone(), d.clone()); }
+                        Err(e) => errors.push(e)
+                    }
+                    res.push(s.clone());
+                }
+
+                Sentence::Read(SRead{path:rpath, name, span}) => {
+                    let file = match self.locate(rpath, path) {
+                        Some(file) => file,
+                        None => {
+                            errors.push(EoleError::new(ErrorKind::NotFound{path:rpath.clone()}, *span));
+                            continue;
+                        }
+                    };
+                    // Only keep the definitions and the operators of the imported file
+                    let defs:Vec<Sentence> = match self.load_(&file, Some(*span)) {
+                        Ok(text) => text.into_iter()
+                            .filter(|s| matches!(s, Sentence::Let(_) | Sentence::Rec(_) | Sentence::Data(_) | Sentence::Infix(_)))
+                            .collect(),
+                        Err(mut e) => {
+                            errors.append(&mut e);
+                            continue;
+                        }
+                    };
+                    // Prefix the definitions (and their uses) with the namespace
+                    let defs = match name {
+                        None => defs,
+                        Some(ns) => namespace(defs, ns)
+                    };
+                    // Imported definitions are located at the 'read' sentence
+                    for s in defs {
+                        for d in definitions(&s) {
+                            if let Err(e) = declare(&mut defined, d, *span) { errors.push(e); }
+                        }
+                        if let Sentence::Infix(d) = &s {
+                            match declare(&mut operators, &d.op, *span) {
+                                Ok(()) => { fixities.insert(d.op.clone(), d.clone()); }
+                                Err(e) => errors.push(e)
+                            }
+                        }
+                        res.push(s);
+                    }
+                }
+
+                Sentence::Rec(SRec{defs, ..}) => {
+                    for d in defs {
+                        if let Err(e) = declare(&mut defined, &d.vname, d.span) { errors.push(e); }
+                    }
+                    res.push(s.clone());
+                }
+
+                Sentence::Data(SData{ctors, ..}) => {
+                    for c in ctors {
+                        if let Err(e) = declare(&mut defined, &c.name, c.span) { errors.push(e); }
+                    }
+                    res.push(s.clone());
+                }
+            }
+        }
+
+        // All the operators are known: resolve them
+        let res = res.into_iter().map(|s| resolve_operators(s, &fixities, &mut errors)).collect();
+
+        if errors.is_empty() { Ok(res) } else { Err(errors) }
+    }
+
+    /// Find the file targeted by a `read` in the file `from`.
+    fn locate(&self, rpath:&str, from:&Path) -> Option<PathBuf> {
+        let target = Path::new(rpath);
+        if target.is_absolute() {
+            if target.is_file() { return Some(target.to_path_buf()); }
+        } else {
+            // Relative to the importing file, then in the search paths
+            let base = from.parent().unwrap_or_else(|| Path::new(""));
+            let relative = target.strip_prefix(".").unwrap_or(target);
+            let candidates = std::iter::once(base).chain(self.search_paths.iter().map(|p| p.as_path()));
+            for dir in candidates {
+                let candidate = dir.join(relative);
+                if candidate.is_file() { return Some(candidate); }
+            }
+        }
+        None
+    }
+}
+
+
+/// Parse a text, registered as `file` in the sources.
+/// Report all the errors the parser could recover from.
+pub fn parse(file:usize, text:&str) -> Result<Vec<Sentence>, Vec<EoleError>> {
+    let mut recovered = vec![];
+    let tokens = Lexer::new(text).map(|t| t.map_err(SyntaxError::from));
+    let res = parser::TextParser::new().parse(file, &mut recovered, tokens);
+    let mut errors:Vec<EoleError> = recovered.into_iter().map(|e| EoleError::from_recovery(file, e)).collect();
+    match res {
+        Ok(text) if errors.is_empty() => Ok(text),
+        Ok(_) => Err(errors),
+        Err(e) => {
+            errors.push(EoleError::from_parse(file, e));
+            Err(errors)
+        }
+    }
+}
+
+/// The sentences given by the importer do not contain any operator expression: the passes after it call this
+/// on the `Infix` terms, which cannot be met.
+pub fn unresolved(span:Span) -> ! {
+    unreachable!("operator expression at {:?} left by the importer, see import::resolve_operators", span)
+}
+
+/// Resolve the operators of a sentence according to `fixities`, see `infix::resolve`.
+fn resolve_operators(s:Sentence, fixities:&Fixities, errors:&mut Vec<EoleError>) -> Sentence {
+    let mut resolve = |term:&Term| match infix::resolve(term, fixities) {
+        Ok(term) => term,
+        Err(e) => {
+            errors.push(EoleError::from_infix(e, fixities.keys()));
+            Box::new(term.clone())
+        }
+    };
+    match s {
+        Sentence::Let(d) => Sentence::Let(SLet{body:resolve(&d.body), ..d}),
+        Sentence::Rec(SRec{defs, span}) => Sentence::Rec(SRec{
+            defs:defs.into_iter().map(|d| SLet{body:resolve(&d.body), ..d}).collect(),
+            span
+        }),
+        Sentence::Run(r) => Sentence::Run(SRun{term:resolve(&r.term), ..r}),
+        Sentence::Assert(a) => Sentence::Assert(SAssert{lhs:resolve(&a.lhs), rhs:resolve(&a.rhs), span:a.span}),
+        s => s
+    }
+}
+
+/// A BLC file contains a single term to run.
+fn blc_run(term:Box<Term>) -> Sentence {
+    let span = term.span();
+    Sentence::Run(SRun{name:None, term, span})
+}
+
+/// Canonical form of a path, used to compare files. Falls back on the path itself.
+fn canonical(path:&Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Record a definition located at `span`, failing if it already exists.
+fn declare(defined:&mut HashMap<String, Span>, name:&str, span:Span) -> Result<(), EoleError> {
+    match defined.get(name) {
+        None => {
+            defined.insert(name.to_string(), span);
+            Ok(())
+        }
+        Some(first) => Err(EoleError::new(ErrorKind::Duplicate{name:name.to_string(), first:*first}, span))
+    }
+}
+
+/// Get the names defined by a sentence.
+fn definitions(s:&Sentence) -> Vec<&String> {
+    match s {
+        Sentence::Let(d) => vec![&d.vname],
+        Sentence::Rec(SRec{defs, ..}) => defs.iter().map(|d| &d.vname).collect(),
+        Sentence::Data(SData{ctors, ..}) => ctors.iter().map(|c| &c.name).collect(),
+        _ => vec![]
+    }
+}
+
+/// Prefix the definitions and the data types with the namespace `ns`, as well as their uses in the definitions.
+pub fn namespace(defs:Vec<Sentence>, ns:&str) -> Vec<Sentence> {
+    let names:HashSet<String> = defs.iter().flat_map(definitions).cloned().collect();
+    defs.into_iter()
+        .map(|s| match s {
+            Sentence::Rec(SRec{defs, span}) => Sentence::Rec(SRec{
+                defs:defs.iter().map(|d| qualify_def(d, ns, &names)).collect(),
+                span
+            }),
+            Sentence::Let(d) => Sentence::Let(qualify_def(&d, ns, &names)),
+            Sentence::Data(d) => Sentence::Data(qualify_data(&d, ns)),
+            Sentence::Infix(d) if names.contains(&d.vname) => Sentence::Infix(SInfix{vname:format!("{}::{}", ns, d.vname), ..d}),
+            s => s
+        })
+        .collect()
+}
+
+/// Prefix a definition and the uses of `names` in its body with the namespace `ns`.
+fn qualify_def(d:&SLet, ns:&str, names:&HashSet<String>) -> SLet {
+    SLet{
+        vname:format!("{}::{}", ns, d.vname),
+        body:qualify(&d.body, ns, names, &mut vec![]),
+        span:d.span
+    }
+}
+
+/// Prefix a data type and its constructors with the namespace `ns`.
+fn qualify_data(d:&SData, ns:&str) -> SData {
+    SData{
+        name:format!("{}::{}", ns, d.name),
+        ctors:d.ctors.iter().map(|c| Ctor{name:format!("{}::{}", ns, c.name), ..c.clone()}).collect(),
+        span:d.span
+    }
+}
+
+/// Prefix the free occurrences of `names` with the namespace `ns`.
+/// `bound` records the variables bound by the enclosing abstractions.
+fn qualify(term:&Term, ns:&str, names:&HashSet<String>, bound:&mut Vec<String>) -> Box<Term> {
+    Box::new(match term {
+        Sym{vname, span} => {
+            if names.contains(vname) && !bound.contains(vname) {
+                Sym{vname:format!("{}::{}", ns, vname), span:*span}
+            } else {
+                term.clone()
+            }
+        }
+
+        Lambda{vname, body, span} => {
+            bound.push(vname.clone());
+            let body = qualify(body, ns, names, bound);
+            bound.pop();
+            Lambda{vname:vname.clone(), body, span:*span}
+        }
+
+        App{fun, arg, span} => App{
+            fun:qualify(fun, ns, names, bound),
+            arg:qualify(arg, ns, names, bound),
+            span:*span
+        },
+
+        Num{..} | Str{..} => term.clone(),
+
+        Annot{term, ty, span} => Annot{term:qualify(term, ns, names, bound), ty:ty.clone(), span:*span},
+
+        Infix{operands, operators, span} => Infix{
+            operands:operands.iter().map(|operand| qualify(operand, ns, names, bound)).collect(),
+            operators:operators.clone(),
+            span:*span
+        },
+
+        List{items, span} => List{items:items.iter().map(|item| qualify(item, ns, names, bound)).collect(), span:*span},
+
+        Rec{defs, body, span} => {
+            let depth = bound.len();
+            bound.extend(defs.iter().map(|(vname, _)| vname.clone()));
+            let defs = defs.iter().map(|(vname, def)| (vname.clone(), qualify(def, ns, names, bound))).collect();
+            let body = qualify(body, ns, names, bound);
+            bound.truncate(depth);
+            Rec{defs, body, span:*span}
+        }
+
+        Case{scrutinee, arms, span} => Case{
+            scrutinee:qualify(scrutinee, ns, names, bound),
+            arms:arms.iter().map(|arm| {
+                let ctor = if names.contains(&arm.ctor) { format!("{}::{}", ns, arm.ctor) } else { arm.ctor.clone() };
+                let depth = bound.len();
+                bound.extend(arm.binders.iter().map(|(vname, _)| vname.clone()));
+                let body = qualify(&arm.body, ns, names, bound);
+                bound.truncate(depth);
+                Arm{ctor, binders:arm.binders.clone(), body, span:arm.span}
+            }).collect(),
+            span:*span
+        },
+    })
+}
@@ -11,3 +11,30 @@ pub mod eole;
 
 // Conversion module
 pub mod conversion;
+
+// Encoding of the literals
+pub mod encoding;
+
+// Algebraic data types
+pub mod data;
+
+// Dependencies between definitions
+pub mod dependency;
+
+// Type inference
+pub mod typing;
+
+// Elementary affine logic
+pub mod eal;
+
+// Mini-ML front end
+pub mod ml;
+
+// Errors
+pub mod error;
+
+// Import resolution
+pub mod import;
+
+// Options set by the files
+pub mod pragma;
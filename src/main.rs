@@ -1,6 +1,7 @@
 // Dev: remove dead code warning at the create level
 #![allow(dead_code)]
 #![allow(unused)]
+#![allow(clippy::upper_case_acronyms)]
 
 // --- --- --- Command line
 // Command line tool
@@ -23,6 +24,15 @@ fn get_cli<'a, 'b>() -> clap::App<'a, 'b> {
              .value_name("input files")
              .help("Input files.")
         )
+        // Search paths for the imports
+        .arg(Arg::with_name("INCLUDE")
+             .short("I")
+             .long("include")
+             .multiple(true)
+             .number_of_values(1)
+             .value_name("directory")
+             .help("Add a directory to the search paths of 'read' (searched before the EOLE_PATH environment variable)")
+        )
         // Verbose mode
         .arg(Arg::with_name("VERBOSE")
              .short("v")
@@ -49,6 +59,30 @@ fn get_cli<'a, 'b>() -> clap::App<'a, 'b> {
                 .validator(as_usize)
                 .help("Max number of reduction steps")
         )
+        .arg(
+            Arg::with_name("LIMIT_T")
+                .long("time-limit")
+                .max_values(1)
+                .value_name("seconds")
+                .validator(as_usize)
+                .help("Max duration of each reduction, in seconds")
+        )
+        .arg(
+            Arg::with_name("LIMIT_N")
+                .long("max-nodes")
+                .max_values(1)
+                .value_name("nodes")
+                .validator(as_usize)
+                .help("Max number of live nodes in the net during a reduction")
+        )
+        .arg(
+            Arg::with_name("LIMIT_M")
+                .long("max-memory")
+                .max_values(1)
+                .value_name("MiB")
+                .validator(as_mebibytes)
+                .help("Max size of the live nodes during a reduction, in MiB (approximate: converted into a number of nodes)")
+        )
         // Create an initial and a final graph
         .arg(
             Arg::with_name("GRAPH_FILA")
@@ -73,7 +107,7 @@ fn get_cli<'a, 'b>() -> clap::App<'a, 'b> {
             Arg::with_name("GC_MODE")
                 .short("m")
                 .max_values(1)
-                .possible_values(&["erasink", "none"])
+                .possible_values(pragma::GCS)
                 .value_name("GC name")
                 .help("Memory option: Set the garbage collector to use (defaults to erasink)")
         )
@@ -86,12 +120,58 @@ fn get_cli<'a, 'b>() -> clap::App<'a, 'b> {
                 .value_name("Compactor ratio")
                 .help("Memory option: Set the ratio (in power of 2) of free nodes triggering the compactor. 0 to disable (default).")
         )
+        // Encoding of the numeric literals
+        .arg(
+            Arg::with_name("NUMERALS")
+                .long("numerals")
+                .takes_value(true)
+                .possible_values(pragma::NUMERALS)
+                .value_name("encoding")
+                .help("Encoding of the numeric literals (defaults to church)")
+        )
+        // Encoding of the list and string literals
+        .arg(
+            Arg::with_name("LISTS")
+                .long("lists")
+                .takes_value(true)
+                .possible_values(pragma::LISTS)
+                .value_name("encoding")
+                .help("Encoding of the list and string literals (defaults to scott)")
+        )
+        // Decoding of the results
+        .arg(
+            Arg::with_name("DECODE")
+                .long("decode")
+                .help("Print the numerals, lists and strings of the results as literals")
+        )
+        // Elementary affine logic
+        .arg(
+            Arg::with_name("EAL")
+                .long("eal")
+                .help("Warn about the terms to run which are not typable in elementary affine logic: their result may be wrong")
+        )
+        // Type inference
+        .arg(
+            Arg::with_name("TYPECHECK")
+                .long("typecheck")
+                .help("Print the principal type of the definitions and of the terms to run, and stop on type errors")
+        )
+        // Output format
+        .arg(
+            Arg::with_name("OUTPUT")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .possible_values(&["lambda", "blc", "Blc"])
+                .value_name("format")
+                .help("Format of the results: lambda terms (default), binary lambda calculus as bits (blc) or as bytes written as is (Blc)")
+        )
         // Reduction mode
         .arg(
             Arg::with_name("RED_STRATEGY")
                 .short("s")
                 .max_values(1)
-                .possible_values(&["lazy", "full"])
+                .possible_values(pragma::STRATEGIES)
                 .value_name("strategy name")
                 .help("Reduction strategy option: Set reduction strategy (default to full).")
         )
@@ -100,49 +180,72 @@ fn get_cli<'a, 'b>() -> clap::App<'a, 'b> {
 // Validators
 fn as_usize(v: String) -> Result<(), String> {
     match v.parse::<usize>() {
-        Err(_) => Err(format!("The value must be a positive integer")),
+        Err(_) => Err(String::from("The value must be a positive integer")),
+        _ => Ok(()),
+    }
+}
+
+fn as_mebibytes(v: String) -> Result<(), String> {
+    match pragma::to_bytes(&v) {
+        None => Err(format!("The value must be a positive integer of at most {} MiB", usize::MAX >> 20)),
         _ => Ok(()),
     }
 }
 
 #[inline]
-fn get_cli_usize(matches: &clap::ArgMatches, name: &str) -> Option<usize> {
-    matches.value_of(name).map(|s| s.parse::<usize>().unwrap())
+fn get_cli_usize(matches: &clap::ArgMatches, name: &str, pragmas: &Pragmas, pragma: &str) -> Option<usize> {
+    get_setting(matches, name, pragmas, pragma).map(|s| s.parse::<usize>().unwrap())
+}
+
+/// Get the value of an option: from the command line if given, else from the pragmas of the file.
+/// Both are already checked.
+#[inline]
+fn get_setting<'a>(matches: &'a clap::ArgMatches, name: &str, pragmas: &'a Pragmas, pragma: &str) -> Option<&'a str> {
+    matches.value_of(name).or_else(|| pragmas.value(pragma))
 }
 
 // Import: eolelib
+use eole_parser::{ast::{SAssert, SData, SRun, Term}, blc};
 use eolelib::{
-    conversion,
+    conversion::{self, Evaluation, ReadbackError},
+    data,
+    eal,
+    encoding::{self, Encodings, Lists, Numerals},
+    error::{self, EoleError, ErrorKind, Sources},
+    import::Importer,
+    ml,
+    pragma::{self, Pragmas},
+    typing,
     eole::{
         compactor::{self, Compactor},
         gc::{self, GC},
         net::{self, Net},
-        reduce,
+        reduce::{self, Limits, ReductionOutcome, Reducer, Strategy},
     },
 };
 
-use eole_parser::parser;
-
 // Import: standard lib
 use std::fs::{self, File};
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant}; // Use for benchmarking
 
-/// Option: reduction mode
-#[derive(Debug)]
-enum RedOpt {
-    LAZY,
-    FULL,
-}
-
 /// Option: graph mode
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum GraphOpt<'a> {
     NONE,
     FILAS(&'a Path),
     ALL(&'a Path),
 }
 
+/// Option: output format
+#[derive(Debug, Clone, Copy)]
+enum OutOpt {
+    LAMBDA,
+    BLCBITS,
+    BLCBYTES,
+}
+
 /// Option: GC mode
 #[derive(Debug)]
 enum GCOpt {
@@ -150,8 +253,30 @@ enum GCOpt {
     ERASINK,
 }
 
+/// Options of the runs
+#[derive(Debug, Clone, Copy)]
+struct RunOpts<'a> {
+    red_opt: Strategy,
+    graph_opt: GraphOpt<'a>,
+    cptr_opt: CptrOpt,
+    out_opt: OutOpt,
+    limit_reduce: Option<usize>,
+    limit_lambda: Option<usize>,
+    /// Limits of each reduction, see `reduce::Limits`
+    limit_time: Option<Duration>,
+    limit_nodes: Option<usize>,
+    /// Data types and literals, used to decode the results
+    types: &'a [SData],
+    literals: Option<Encodings>,
+    /// Mini-ML program, whose results are decoded according to their types
+    ml: Option<&'a ml::Program>,
+}
+
+/// "Should compact" function, deciding when the compactor runs.
+type ShouldCompact<MyGC> = Box<dyn Fn(&Net<MyGC>) -> bool>;
+
 /// Option: Compactor mode
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum CptrOpt {
     NONE,
     FACTOR(usize),
@@ -163,8 +288,40 @@ pub fn main() {
     let matches = get_cli().get_matches();
     let input_paths: Vec<&str> = matches.values_of("INPUTS").map(|i| i.collect()).unwrap();
     let is_verbose = matches.is_present("VERBOSE");
-    let limit_lambda = get_cli_usize(&matches, "LIMIT_L");
-    let limit_reduce = get_cli_usize(&matches, "LIMIT_R");
+
+    // --- --- --- File Parsing
+    let path = Path::new(input_paths.first().unwrap());
+    let is_ml = path.extension().and_then(|e| e.to_str()) == Some(ml::EXTENSION);
+
+    // Get the search paths: command line first, then environment
+    let mut search_paths:Vec<PathBuf> = matches.values_of("INCLUDE")
+        .map(|i| i.map(PathBuf::from).collect())
+        .unwrap_or_default();
+    search_paths.extend(Importer::env_search_paths());
+
+    let mut importer = Importer::new(search_paths);
+    let program = if is_ml {
+        Some(ml::load(path, importer.sources_mut()).unwrap_or_else(|e| fail(e, importer.sources())))
+    } else {
+        None
+    };
+    let text = match &program {
+        Some(p) => p.sentences.clone(),
+        None => importer.load(path).unwrap_or_else(|e| fail(e, importer.sources()))
+    };
+
+    // Options set by the file, overridden by the command line
+    let pragmas = pragma::collect(&text).unwrap_or_else(|e| fail(e, importer.sources()));
+    let limit_lambda = get_cli_usize(&matches, "LIMIT_L", &pragmas, "lambda-limit");
+    let limit_reduce = get_cli_usize(&matches, "LIMIT_R", &pragmas, "reduction-limit");
+    let limit_time = get_cli_usize(&matches, "LIMIT_T", &pragmas, "time-limit").map(|s| Duration::from_secs(s as u64));
+    // The memory limit is converted into a number of nodes: the names of the abstractions and the spare capacity of the net are not counted.
+    let limit_memory = get_setting(&matches, "LIMIT_M", &pragmas, "max-memory")
+        .map(|mib| pragma::to_bytes(mib).unwrap() / std::mem::size_of::<net::Node>());
+    let limit_nodes = match (get_cli_usize(&matches, "LIMIT_N", &pragmas, "max-nodes"), limit_memory) {
+        (Some(n), Some(m)) => Some(n.min(m)),
+        (n, m) => n.or(m)
+    };
 
     // Create a closure for the verbose
     let vprint: &dyn Fn(String) = if is_verbose {
@@ -201,14 +358,14 @@ pub fn main() {
     };
 
     // Get the GC option
-    let gc_opt = match matches.value_of("GC_MODE").unwrap_or("erasink") {
+    let gc_opt = match get_setting(&matches, "GC_MODE", &pragmas, "gc").unwrap_or("erasink") {
         "none" => GCOpt::NOGC,
         "erasink" => GCOpt::ERASINK,
         _ => panic!("Should not happen"),
     };
 
     // Get the compactor option
-    let cptr_opt = if let Some(v) = matches.value_of("CPTR_MODE") {
+    let cptr_opt = if let Some(v) = get_setting(&matches, "CPTR_MODE", &pragmas, "compactor") {
         let v = v.parse::<usize>().unwrap();
         if v == 0 {
             CptrOpt::NONE
@@ -220,59 +377,231 @@ pub fn main() {
     };
 
     // Get the strategy option
-    let red_opt = match matches.value_of("RED_STRATEGY").unwrap_or("full") {
-        "lazy" => RedOpt::LAZY,
-        "full" => RedOpt::FULL,
+    let red_opt = match get_setting(&matches, "RED_STRATEGY", &pragmas, "strategy").unwrap_or("full") {
+        "lazy" => Strategy::Lazy,
+        "full" => Strategy::Full,
+        "head" => Strategy::Head,
+        "cbv" => Strategy::Cbv,
+        "applicative" => Strategy::Applicative,
+        _ => panic!("Should not happen"),
+    };
+
+    // Get the output option
+    let out_opt = match matches.value_of("OUTPUT").unwrap_or("lambda") {
+        "lambda" => OutOpt::LAMBDA,
+        "blc" => OutOpt::BLCBITS,
+        "Blc" => OutOpt::BLCBYTES,
+        _ => panic!("Should not happen"),
+    };
+
+    // Get the numerals option
+    let numerals = match get_setting(&matches, "NUMERALS", &pragmas, "numerals").unwrap_or("church") {
+        "church" => Numerals::Church,
+        "scott" => Numerals::Scott,
+        "binary" => Numerals::Binary,
         _ => panic!("Should not happen"),
     };
 
-    // --- --- --- File Parsing and converting
-    let path = input_paths.first().unwrap();
-    let source = std::fs::read_to_string(path).unwrap();
-    let text = parser::TextParser::new().parse(&source).unwrap();
+    // Get the lists option
+    let lists = match get_setting(&matches, "LISTS", &pragmas, "lists").unwrap_or("scott") {
+        "scott" => Lists::Scott,
+        "church" => Lists::Church,
+        _ => panic!("Should not happen"),
+    };
+    // Mini-ML uses the default encodings
+    let encodings = if is_ml { Encodings::default() } else { Encodings{ numerals, lists } };
+    let literals = if matches.is_present("DECODE") || pragmas.is_set("decode") { Some(encodings) } else { None };
+    encoding::check(&text, encodings).unwrap_or_else(|e| fail(e, importer.sources()));
+    // --- --- --- Converting
+    let types = data::declarations(&text);
+    let text = data::desugar(&text).unwrap_or_else(|e| fail(e, importer.sources()));
+    let sources = importer.sources();
+    if matches.is_present("TYPECHECK") {
+        match &program {
+            // Already typed: print the types of Mini-ML
+            Some(p) => for (name, ty) in &p.types { println!("{} : {}", name, ty); },
+            None => {
+                let (types, errors) = typing::infer(&text, encodings);
+                for (name, ty) in types { println!("{} : {}", name, ty); }
+                if !errors.is_empty() { fail(errors, sources); }
+            }
+        }
+    }
+
+    // Elementary affine logic: warn about the results which cannot be trusted
+    if matches.is_present("EAL") || pragmas.is_set("eal") {
+        let mut warnings = vec![];
+        for (label, res) in eal::check(&text, encodings, sources) {
+            match res {
+                Ok(s) => vprint(format!("The elementary affine type of {} is {} (depth {})", label, s.ty, s.depth)),
+                Err(e) => warnings.push(e)
+            }
+        }
+        if !warnings.is_empty() {
+            eprintln!("{}", error::render_warnings(&mut warnings, sources));
+        }
+    }
 
     // --- --- --- Do the work
+    let opts = RunOpts{ red_opt, graph_opt, cptr_opt, out_opt, limit_reduce, limit_lambda, limit_time, limit_nodes, types:&types, literals, ml:program.as_ref() };
     // For now, we keep the net in the main...
     type MyCPTR = compactor::Interval;
-    let res = match gc_opt {
+    let failed = match gc_opt {
         GCOpt::ERASINK => {
             type MyGC = gc::EraSinkGC;
-            type MyNet = net::Net<MyGC>;
-
-            let mut net = conversion::to_network::<MyGC>(&text);
-            file_run::<MyGC, MyCPTR>(red_opt, graph_opt, cptr_opt, limit_reduce, vprint, &mut net);
-            conversion::from_net(&net, limit_lambda)
+            let nets = conversion::to_networks::<MyGC>(&text, encodings).unwrap_or_else(|e| fail(e, sources));
+            file_runs::<MyGC, MyCPTR>(nets, opts, vprint)
         }
 
         GCOpt::NOGC => {
             type MyGC = gc::NoGC;
-            type MyNet = net::Net<MyGC>;
-
-            let mut net = conversion::to_network::<MyGC>(&text);
-            file_run::<MyGC, MyCPTR>(red_opt, graph_opt, cptr_opt, limit_reduce, vprint, &mut net);
-            conversion::from_net(&net, limit_lambda)
+            let nets = conversion::to_networks::<MyGC>(&text, encodings).unwrap_or_else(|e| fail(e, sources));
+            file_runs::<MyGC, MyCPTR>(nets, opts, vprint)
         }
     };
+    if !failed.is_empty() {
+        fail(failed, sources);
+    }
 
-    match res {
-        None => println!("<No conversion done -- lambda limit={:?}>", limit_lambda),
-        Some(l)=> println!("{}", l)
-    };
+}
+
+
+/// Report errors on stderr and exit.
+fn fail(mut errors:Vec<EoleError>, sources:&Sources) -> ! {
+    eprint!("{}", error::render_all(&mut errors, sources));
+    std::process::exit(1);
+}
+
+
+/// Reduce the networks of all the terms to run and of all the assertions, in order, and print the results of the terms.
+/// Named terms have their result prefixed by their name.
+/// With several terms, the graphs of each term go in their own sub-folder, named after the term (or its rank).
+/// Return the failed assertions.
+fn file_runs<'a, 'b, MyGC: GC, MyCPTR: Compactor>(
+    nets: Vec<Evaluation<MyGC>>,
+    opts: RunOpts<'b>,
+    vprint: &'a dyn Fn(String),
+) -> Vec<EoleError> {
+    let RunOpts{ red_opt, graph_opt, cptr_opt, out_opt, limit_reduce, limit_lambda, types, literals, ml, .. } = opts;
+    let several = nets.iter().filter(|e| matches!(e, Evaluation::Run(..))).count() > 1;
+    let mut failed = vec![];
+    let mut rank = 0;
+    for evaluation in nets {
+        let (i, run, mut net) = match evaluation {
+            Evaluation::Run(run, net) => {
+                rank += 1;
+                (rank - 1, run, net)
+            }
+            Evaluation::Assert(assert, lhs, rhs) => {
+                if let Some(e) = file_assert::<MyGC, MyCPTR>(assert, lhs, rhs, opts, vprint) {
+                    failed.push(e);
+                }
+                continue;
+            }
+        };
+        // Identify the run by its name, or by its rank
+        let label = run.name.clone().unwrap_or_else(|| i.to_string());
+        if several {
+            vprint(format!("--- {}", label));
+        }
+
+        // Graph folder of the run
+        let folder = match graph_opt {
+            GraphOpt::FILAS(f) | GraphOpt::ALL(f) if several => {
+                let p = f.join(&label);
+                if !p.exists() {
+                    fs::create_dir(&p).expect("Could not create the graph directory");
+                }
+                Some(p)
+            }
+            _ => None
+        };
+        let run_graph_opt = match (graph_opt, &folder) {
+            (GraphOpt::FILAS(_), Some(p)) => GraphOpt::FILAS(p),
+            (GraphOpt::ALL(_), Some(p)) => GraphOpt::ALL(p),
+            (g, _) => g
+        };
 
+        let outcome = file_run::<MyGC, MyCPTR>(red_opt, run_graph_opt, cptr_opt, limit_reduce, limits(&opts), vprint, &mut net);
+
+        let res = match unfinished(&outcome, &opts) {
+            Some(msg) => msg,
+            // No read back of an unfinished reduction: the net may be huge
+            None => match conversion::from_net(&net, limit_lambda) {
+                Err(e) => not_read_back(e, limit_lambda),
+                Ok(l) => match out_opt {
+                    OutOpt::LAMBDA => match ml {
+                        Some(p) => ml::decode(&l, &p.results[i], &p.datatypes),
+                        None => data::decode(&l, types, literals).to_string()
+                    },
+                    OutOpt::BLCBITS => blc::to_bits(&l).unwrap_or_else(|e| format!("<No conversion done -- {}>", e)),
+                    // Bytes are written as is, without name nor new line
+                    OutOpt::BLCBYTES => match blc::to_bytes(&l) {
+                        Ok(bytes) => {
+                            std::io::stdout().write_all(&bytes).expect("Could not write the result");
+                            continue;
+                        }
+                        Err(e) => format!("<No conversion done -- {}>", e)
+                    }
+                }
+            }
+        };
+        match &run.name {
+            None => println!("{}", res),
+            Some(n) => println!("{}: {}", n, res)
+        }
+    }
+    failed
+}
+
+
+/// Reduce both sides of an assertion, and compare their normal forms up to alpha-equivalence.
+/// Return the error to report if they differ, with both normal forms.
+fn file_assert<'a, 'b, MyGC: GC, MyCPTR: Compactor>(
+    assert: &SAssert,
+    mut lhs: Net<MyGC>,
+    mut rhs: Net<MyGC>,
+    opts: RunOpts<'b>,
+    vprint: &'a dyn Fn(String),
+) -> Option<EoleError> {
+    let RunOpts{ red_opt, cptr_opt, limit_reduce, limit_lambda, types, literals, .. } = opts;
+    vprint(format!("--- {}", assert));
+    let mut sides = vec![];
+    for net in [&mut lhs, &mut rhs].iter_mut() {
+        let outcome = file_run::<MyGC, MyCPTR>(red_opt, GraphOpt::NONE, cptr_opt, limit_reduce, limits(&opts), vprint, net);
+        sides.push(match unfinished(&outcome, &opts) {
+            Some(msg) => Err(msg),
+            None => conversion::from_net(net, limit_lambda)
+                .map_err(|e| not_read_back(e, limit_lambda))
+        });
+    }
+    if let (Ok(l), Ok(r)) = (&sides[0], &sides[1]) {
+        if l.alpha_eq(r) {
+            vprint(String::from("Assertion passed"));
+            return None;
+        }
+    }
+    let show = |side:&Result<Box<Term>, String>| match side {
+        Err(msg) => msg.clone(),
+        Ok(l) => data::decode(l, types, literals).to_string()
+    };
+    let kind = ErrorKind::AssertionFailed{lhs:show(&sides[0]), rhs:show(&sides[1])};
+    Some(EoleError::new(kind, assert.span))
 }
 
 
 fn file_run<'a, 'b, MyGC: GC, MyCPTR: Compactor>(
-    red_opt: RedOpt,
+    red_opt: Strategy,
     graph_opt: GraphOpt<'b>,
     cptr_opt: CptrOpt,
     limit_reduce:Option<usize>,
+    limits: Limits,
     vprint: &'a dyn Fn(String),
     // --- --- ---
-    mut net: &mut Net<MyGC>,
-) {
+    net: &mut Net<MyGC>,
+) -> ReductionOutcome {
     // "Should compact" function
-    let should_compact: Box<dyn Fn(&Net<MyGC>) -> bool> = match cptr_opt {
+    let should_compact: ShouldCompact<MyGC> = match cptr_opt {
         CptrOpt::NONE => Box::new(|net: &Net<MyGC>| false),
 
         CptrOpt::FACTOR(f) => {
@@ -282,20 +611,19 @@ fn file_run<'a, 'b, MyGC: GC, MyCPTR: Compactor>(
 
     // Create the first graph if "FIRST/LAST".
     // Note:    the action is called BEFORE a reduction: the first graph for "ALL" will be done.
-    match graph_opt {
-        GraphOpt::FILAS(folder) => conversion::do_graph(&net, folder, 0),
-        _ => {}
+    if let GraphOpt::FILAS(folder) = graph_opt {
+        conversion::do_graph(net, folder, 0);
     }
 
     // Create the counter graph
     let mut stepcell = std::cell::Cell::new(0);
 
     let step = &mut stepcell;
-    let mut reducer: Box<dyn FnMut(&mut Net<MyGC>, bool, usize)> = match red_opt {
-        RedOpt::LAZY => {
+    let mut reducer: Reducer<MyGC> = match red_opt {
+        Strategy::Lazy => {
             // "Action" function, based on the graph_opt
             // Specific per reduction kind
-            let do_graph: Box<dyn FnMut(&Net<MyGC>, ((usize, net::DstrK), &Vec<(usize, net::DstrK)>))> = match graph_opt {
+            let do_graph: reduce::LazyAction<MyGC> = match graph_opt {
                 GraphOpt::ALL(folder) => {
                     Box::new(move |net: &Net<MyGC>, extra:((usize, net::DstrK), &Vec<(usize, net::DstrK)>)| {
                         conversion::do_graph_lazy(net, folder, step.get(), extra);
@@ -308,13 +636,14 @@ fn file_run<'a, 'b, MyGC: GC, MyCPTR: Compactor>(
             Box::new(reduce::get_reducer_lazy::<MyGC, MyCPTR>(
                 &should_compact,
                 do_graph,
+                limits,
             ))
         }
 
-        RedOpt::FULL => {
+        Strategy::Full | Strategy::Head | Strategy::Cbv | Strategy::Applicative => {
             // "Action" function, based on the graph_opt
             // Specific per reduction kind
-            let do_graph: Box<dyn FnMut(&Net<MyGC>, (usize, &Vec<(net::Vertex, net::NodeKind)>))> =
+            let do_graph: reduce::FullAction<MyGC> =
                 match graph_opt {
                     GraphOpt::ALL(folder) => Box::new(
                         move |net: &Net<MyGC>, (idx, s): (usize, &Vec<(net::Vertex, net::NodeKind)>)| {
@@ -325,22 +654,24 @@ fn file_run<'a, 'b, MyGC: GC, MyCPTR: Compactor>(
 
                     _ => Box::new(|net: &Net<MyGC>, extra: _| {}),
                 };
-            Box::new(reduce::get_reducer_full::<MyGC, MyCPTR>(
-                &should_compact,
-                do_graph,
-            ))
+            match red_opt {
+                Strategy::Head => Box::new(reduce::get_reducer_head::<MyGC, MyCPTR>(&should_compact, do_graph, limits)),
+                Strategy::Cbv => Box::new(reduce::get_reducer_cbv::<MyGC, MyCPTR>(&should_compact, do_graph, limits)),
+                Strategy::Applicative => Box::new(reduce::get_reducer_applicative::<MyGC, MyCPTR>(&should_compact, do_graph, limits)),
+                _ => Box::new(reduce::get_reducer_full::<MyGC, MyCPTR>(&should_compact, do_graph, limits)),
+            }
         }
     };
 
-    vprint(format!("Starting reduction..."));
+    vprint(String::from("Starting reduction..."));
     let now = Instant::now();
-    match limit_reduce {
-        None => reducer(&mut net, false, 0),
-        Some(l) => reducer(&mut net, true, l)
+    let outcome = match limit_reduce {
+        None => reducer(net, false, 0),
+        Some(l) => reducer(net, true, l)
     };
     std::mem::drop(reducer); // Kill the closure, releasing ownership over cell stepcell
     let duration = now.elapsed();
-    let mili = duration.subsec_micros() / 1000; // quotient
+    let mili = duration.subsec_millis();
     let micro = duration.subsec_micros() % 1000; // remainder
     vprint(format!(
         "Done in {}s {:03}ms {:03}μs ",
@@ -349,13 +680,48 @@ fn file_run<'a, 'b, MyGC: GC, MyCPTR: Compactor>(
         micro
     ));
     vprint(net.print_stats());
+    vprint(format!("Outcome: {:?}", outcome));
 
     // Create the last graph, if needed
     // Note:    the action is called BEFORE a reduction: we need to produce the last graph
     //          for both "FIRST/LAST" and "ALL" options.
     match graph_opt {
-        GraphOpt::FILAS(folder) => conversion::do_graph(&net, folder, 1),
-        GraphOpt::ALL(folder) => conversion::do_graph(&net, folder, stepcell.get()),
+        GraphOpt::FILAS(folder) => conversion::do_graph(net, folder, 1),
+        GraphOpt::ALL(folder) => conversion::do_graph(net, folder, stepcell.get()),
         _ => {}
     }
+    outcome
+}
+
+
+/// Limits of a reduction starting now.
+fn limits(opts:&RunOpts) -> Limits {
+    let mut limits = Limits::default();
+    if let Some(t) = opts.limit_time {
+        limits = limits.with_deadline(Instant::now() + t);
+    }
+    if let Some(n) = opts.limit_nodes {
+        limits = limits.with_max_nodes(n);
+    }
+    limits
+}
+
+/// Text printed instead of the result of a reduction which did not finish, if any.
+fn unfinished(outcome:&ReductionOutcome, opts:&RunOpts) -> Option<String> {
+    match outcome {
+        ReductionOutcome::CreditExhausted => Some(format!("<Reduction limit reached -- reduction limit={:?}>", opts.limit_reduce)),
+        ReductionOutcome::TimeLimit => Some(format!("<Time limit reached -- time limit={:?}>", opts.limit_time)),
+        ReductionOutcome::NodeLimit => Some(format!("<Node limit reached -- node limit={:?}>", opts.limit_nodes)),
+        ReductionOutcome::Cancelled => Some(String::from("<Reduction cancelled>")),
+        ReductionOutcome::Error(e) => Some(format!("<Reduction failed -- {}>", e)),
+        _ => None
+    }
+}
+
+/// Text printed instead of a result which is not read back.
+fn not_read_back(e:ReadbackError, limit_lambda:Option<usize>) -> String {
+    match e {
+        ReadbackError::Limit => format!("<No conversion done -- lambda limit={:?}>", limit_lambda),
+        ReadbackError::Cyclic => String::from("<No conversion done -- cyclic term: its read back is infinite>"),
+    }
 }
@@ -0,0 +1,476 @@
+//! Compilation of Mini-ML to Éole terms.
+//! The programs must have been typed beforehand: the compilation only fails on non-exhaustive pattern matchings.
+//!
+//! ## Pattern matching
+//! A pattern matching is compiled to a decision tree, as in Maranget's "Compiling pattern matching to good decision trees".
+//! The values being matched are named by temporary variables (the columns), and each arm is a row of patterns.
+//! A value is tested against the constructors found in its column, i.e. applied to one branch per constructor
+//! (as in `data`), and the integers are compared one after the other.
+//! When no row is left, the matching is not exhaustive: the tests leading there give a value which is not matched.
+//!
+//! The body of an arm is abstracted over the variables of its pattern.
+//! It is inlined if only one leaf of the tree leads to it, and shared by a local definition otherwise.
+
+use eole_parser::ast::{Sentence, SLet, SRec, SRun, Span, Term};
+use eole_parser::ml::{BinOp, Binding, Expr, Pattern};
+use crate::error::{EoleError, ErrorKind};
+use super::{Env, NAMESPACE};
+
+
+/// State of the compilation: the number of temporary variables created so far.
+#[derive(Default)]
+pub(super) struct Compiler {
+    temps:usize,
+}
+
+/// A row of the pattern matrix: the patterns of the columns (`None` for a wildcard),
+/// the variables bound to the columns so far, and the arm.
+#[derive(Clone)]
+struct Row<'p> {
+    pats:Vec<Option<&'p Pattern>>,
+    binds:Vec<(String, String)>,
+    action:usize,
+}
+
+/// A decision tree.
+enum Tree {
+    /// Go to the arm `action`, with the columns bound to its variables.
+    Leaf{action:usize, binds:Vec<(String, String)>},
+    /// Apply the value to one branch per constructor, abstracted over the fields.
+    Switch{value:String, branches:Vec<(Vec<String>, Tree)>},
+    /// Compare the value to integers, with a default branch.
+    Ints{value:String, cases:Vec<(u64, Tree)>, default:Box<Tree>},
+}
+
+impl Compiler {
+
+    /// Compile top-level definitions, declaring them in `env`.
+    pub fn definitions(&mut self, env:&mut Env, rec:bool, bindings:&[Binding]) -> Result<Vec<Sentence>, EoleError> {
+        if rec {
+            let names:Vec<String> = bindings.iter()
+                .flat_map(|b| b.pattern.vars())
+                .map(|(name, _)| env.define(name))
+                .collect();
+            let defs = bindings.iter().zip(names)
+                .map(|(b, vname)| Ok(SLet{vname, body:self.function(env, &b.params, &b.body, &mut vec![])?, span:b.span}))
+                .collect::<Result<_, _>>()?;
+            let span = bindings[0].span.join(bindings[bindings.len()-1].span);
+            return Ok(vec![Sentence::Rec(SRec{defs, span})]);
+        }
+
+        // The bodies only see the previous definitions. The temporary definitions are not declared.
+        let mut compiled = vec![];
+        for b in bindings {
+            let value = self.function(env, &b.params, &b.body, &mut vec![])?;
+            match &b.pattern {
+                Pattern::Var{name, ..} => compiled.push((Some(name.as_str()), name.clone(), value, b.span)),
+                Pattern::Wildcard{..} => {}
+                // Matched once per variable
+                p => {
+                    let temp = self.temp();
+                    compiled.push((None, temp.clone(), value, b.span));
+                    for (name, span) in p.vars() {
+                        let arm = vec![(p, sym(name, span))];
+                        let body = self.matching(env, *sym(&temp, b.span), arm, p.span())?;
+                        compiled.push((Some(name), name.to_string(), body, b.span));
+                    }
+                }
+            }
+        }
+        Ok(compiled.into_iter()
+            .map(|(name, vname, body, span)| {
+                let vname = match name { Some(name) => env.define(name), None => vname };
+                Sentence::Let(SLet{vname, body, span})
+            })
+            .collect())
+    }
+
+    /// Compile an expression to evaluate.
+    pub fn run(&mut self, env:&Env, e:&Expr) -> Result<Sentence, EoleError> {
+        let term = self.expr(env, e, &mut vec![])?;
+        Ok(Sentence::Run(SRun{name:None, term, span:e.span()}))
+    }
+
+    /// New temporary variable, which cannot be written in Mini-ML.
+    fn temp(&mut self) -> String {
+        self.temps += 1;
+        format!("{}::v{}", NAMESPACE, self.temps)
+    }
+
+    /// Compile an expression, `locals` being the local variables in scope.
+    fn expr(&mut self, env:&Env, e:&Expr, locals:&mut Vec<String>) -> Result<Box<Term>, EoleError> {
+        let span = e.span();
+        Ok(match e {
+            Expr::Int{value, ..} => Box::new(Term::Num{value:*value, span}),
+            Expr::Str{value, ..} => Box::new(Term::Str{value:value.clone(), span}),
+            Expr::Bool{value, ..} => prelude(if *value { "true" } else { "false" }, span),
+            Expr::Unit{..} => prelude("unit", span),
+
+            Expr::Var{name, ..} => {
+                if locals.contains(name) {
+                    sym(name, span)
+                } else if let Some(global) = env.globals.get(name) {
+                    sym(global, span)
+                } else {
+                    prelude(name, span)
+                }
+            }
+
+            Expr::Ctor{name, arg, ..} => {
+                let (_, ctor) = env.ctor(name).expect("typed constructor");
+                let args = super::ctor_args(arg.as_deref(), ctor.fields.len()).expect("typed constructor");
+                let mut res = sym(name, span);
+                for a in args { res = app(res, self.expr(env, a, locals)?, span); }
+                res
+            }
+
+            // 's -> s a b'
+            Expr::Tuple{items, ..} => {
+                let s = self.temp();
+                let mut res = sym(&s, span);
+                for i in items { res = app(res, self.expr(env, i, locals)?, span); }
+                Box::new(Term::Lambda{vname:s, body:res, span})
+            }
+
+            Expr::List{items, ..} => Box::new(Term::List{
+                items:items.iter().map(|i| self.expr(env, i, locals)).collect::<Result<_, _>>()?,
+                span
+            }),
+
+            Expr::App{fun, arg, ..} => app(self.expr(env, fun, locals)?, self.expr(env, arg, locals)?, span),
+
+            Expr::Fun{params, body, ..} => self.function(env, params, body, locals)?,
+
+            Expr::Let{rec:true, bindings, body, ..} => {
+                let depth = locals.len();
+                locals.extend(bindings.iter().flat_map(|b| b.pattern.vars()).map(|(name, _)| name.to_string()));
+                let mut defs = vec![];
+                for (i, b) in bindings.iter().enumerate() {
+                    let vname = locals[depth + i].clone();
+                    match self.function(env, &b.params, &b.body, locals) {
+                        Ok(def) => defs.push((vname, def)),
+                        Err(e) => { locals.truncate(depth); return Err(e); }
+                    }
+                }
+                let body = self.expr(env, body, locals);
+                locals.truncate(depth);
+                Box::new(Term::Rec{defs, body:body?, span})
+            }
+
+            Expr::Let{rec:false, bindings, body, ..} => {
+                let values = bindings.iter()
+                    .map(|b| self.function(env, &b.params, &b.body, locals))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let depth = locals.len();
+                locals.extend(bindings.iter().flat_map(|b| b.pattern.vars()).map(|(name, _)| name.to_string()));
+                let body = self.expr(env, body, locals);
+                locals.truncate(depth);
+                let mut res = body?;
+                if let [Binding{pattern:Pattern::Var{name, ..}, ..}] = bindings.as_slice() {
+                    return Ok(let_(name, values.into_iter().next().unwrap(), res, span));
+                }
+                if bindings.len() == 1 {
+                    let value = values.into_iter().next().unwrap();
+                    return self.matching(env, *value, vec![(&bindings[0].pattern, res)], span);
+                }
+                // Several definitions: the values are computed before matching the patterns
+                let temps:Vec<String> = bindings.iter().map(|_| self.temp()).collect();
+                for (b, t) in bindings.iter().zip(&temps).rev() {
+                    res = self.matching(env, *sym(t, span), vec![(&b.pattern, res)], span)?;
+                }
+                for (value, t) in values.into_iter().zip(&temps).rev() {
+                    res = let_(t, value, res, span);
+                }
+                res
+            }
+
+            // 'c a b'
+            Expr::If{cond, then, other, ..} => {
+                let c = self.expr(env, cond, locals)?;
+                let t = self.expr(env, then, locals)?;
+                let o = self.expr(env, other, locals)?;
+                app(app(c, t, span), o, span)
+            }
+
+            Expr::Match{scrutinee, arms, ..} => {
+                let s = self.expr(env, scrutinee, locals)?;
+                let mut compiled = vec![];
+                for (p, body) in arms {
+                    let depth = locals.len();
+                    locals.extend(p.vars().into_iter().map(|(name, _)| name.to_string()));
+                    let body = self.expr(env, body, locals);
+                    locals.truncate(depth);
+                    compiled.push((p, body?));
+                }
+                self.matching(env, *s, compiled, span)?
+            }
+
+            Expr::BinOp{op, lhs, rhs, ..} => {
+                let l = self.expr(env, lhs, locals)?;
+                let r = self.expr(env, rhs, locals)?;
+                match op {
+                    // Lazy in their right operand
+                    BinOp::And => app(app(l, r, span), prelude("false", span), span),
+                    BinOp::Or => app(app(l, prelude("true", span), span), r, span),
+                    _ => {
+                        let f = match op {
+                            BinOp::Add => "add", BinOp::Sub => "sub", BinOp::Mul => "mul", BinOp::Div => "div", BinOp::Mod => "mod",
+                            BinOp::Eq => "eq", BinOp::Ne => "ne", BinOp::Lt => "lt", BinOp::Le => "le", BinOp::Gt => "gt", BinOp::Ge => "ge",
+                            BinOp::Cons => "cons", BinOp::Concat => "append",
+                            BinOp::And | BinOp::Or => unreachable!()
+                        };
+                        app(app(prelude(f, span), l, span), r, span)
+                    }
+                }
+            }
+        })
+    }
+
+    /// Compile a function `params -> body`, or the body alone without parameters.
+    /// A parameter which is not a variable is matched against a temporary variable.
+    fn function(&mut self, env:&Env, params:&[Pattern], body:&Expr, locals:&mut Vec<String>) -> Result<Box<Term>, EoleError> {
+        let (p, rest) = match params.split_first() {
+            None => return self.expr(env, body, locals),
+            Some(split) => split
+        };
+        let span = p.span().join(body.span());
+        let vname = match p {
+            Pattern::Var{name, ..} => name.clone(),
+            Pattern::Wildcard{..} => String::from("_"),
+            _ => self.temp()
+        };
+        let depth = locals.len();
+        locals.extend(p.vars().into_iter().map(|(name, _)| name.to_string()));
+        let inner = self.function(env, rest, body, locals);
+        locals.truncate(depth);
+        let inner = match p {
+            Pattern::Var{..} | Pattern::Wildcard{..} => inner?,
+            _ => self.matching(env, *sym(&vname, span), vec![(p, inner?)], span)?
+        };
+        Ok(Box::new(Term::Lambda{vname, body:inner, span}))
+    }
+
+    /// Compile a pattern matching of `scrutinee`, the bodies of the arms being compiled with the variables of their pattern in scope.
+    fn matching(&mut self, env:&Env, scrutinee:Term, arms:Vec<(&Pattern, Box<Term>)>, span:Span) -> Result<Box<Term>, EoleError> {
+        // A variable is matched as is, other values are bound to a temporary variable
+        let (value, bound) = match scrutinee {
+            Term::Sym{vname, ..} => (vname, None),
+            s => (self.temp(), Some(s))
+        };
+        let rows = arms.iter().enumerate()
+            .map(|(action, (p, _))| Row{pats:vec![Some(*p)], binds:vec![], action})
+            .collect();
+        let tree = self.tree(env, rows, vec![value.clone()])
+            .map_err(|missing| EoleError::new(ErrorKind::NonExhaustive{missing:missing[0].clone()}, span))?;
+
+        // The bodies, abstracted over their variables. Shared if several leaves lead to them.
+        let mut uses = vec![0; arms.len()];
+        count_uses(&tree, &mut uses);
+        let actions:Vec<(Vec<String>, Box<Term>)> = arms.into_iter()
+            .map(|(p, body)| (p.vars().into_iter().map(|(name, _)| name.to_string()).collect(), body))
+            .collect();
+        let shared:Vec<Option<String>> = uses.iter().map(|u| if *u > 1 { Some(self.temp()) } else { None }).collect();
+        let mut res = emit(&tree, &actions, &shared, span);
+        for ((vars, body), name) in actions.iter().zip(&shared).rev() {
+            if let Some(name) = name {
+                res = let_(name, abstract_over(vars, body.clone(), span), res, span);
+            }
+        }
+        Ok(match bound {
+            None => res,
+            Some(s) => let_(&value, Box::new(s), res, span)
+        })
+    }
+
+    /// Build the decision tree of the rows, the columns being the names of the values.
+    /// On failure, give a value which is not matched, one pattern per column.
+    fn tree<'p>(&mut self, env:&Env, mut rows:Vec<Row<'p>>, cols:Vec<String>) -> Result<Tree, Vec<String>> {
+        // Variables and wildcards match anything
+        for row in &mut rows {
+            for (p, col) in row.pats.iter_mut().zip(&cols) {
+                match p {
+                    Some(Pattern::Var{name, ..}) => {
+                        row.binds.push((name.clone(), col.clone()));
+                        *p = None;
+                    }
+                    Some(Pattern::Wildcard{..}) => *p = None,
+                    _ => {}
+                }
+            }
+        }
+        let first = match rows.first() {
+            None => return Err(vec![String::from("_"); cols.len()]),
+            Some(first) => first
+        };
+        // Test the first column where the first row has a pattern
+        let j = match first.pats.iter().position(Option::is_some) {
+            None => return Ok(Tree::Leaf{action:first.action, binds:first.binds.clone()}),
+            Some(j) => j
+        };
+        let others:Vec<String> = cols.iter().enumerate().filter(|(k, _)| *k != j).map(|(_, c)| c.clone()).collect();
+        let insert = |mut rest:Vec<String>, w:String| { rest.insert(j, w); rest };
+
+        if let Some(Pattern::Int{..}) = first.pats[j] {
+            let mut values = vec![];
+            for row in &rows {
+                if let Some(Pattern::Int{value, ..}) = row.pats[j] {
+                    if !values.contains(value) { values.push(*value); }
+                }
+            }
+            let mut cases = vec![];
+            for v in &values {
+                let sub = rows.iter()
+                    .filter(|row| match row.pats[j] { Some(Pattern::Int{value, ..}) => value == v, _ => true })
+                    .map(|row| without(row, j, vec![]))
+                    .collect();
+                cases.push((*v, self.tree(env, sub, others.clone()).map_err(|w| insert(w, v.to_string()))?));
+            }
+            let sub = rows.iter().filter(|row| row.pats[j].is_none()).map(|row| without(row, j, vec![])).collect();
+            let missing = (0..).find(|n| !values.contains(n)).unwrap();
+            let default = self.tree(env, sub, others).map_err(|w| insert(w, missing.to_string()))?;
+            return Ok(Tree::Ints{value:cols[j].clone(), cases, default:Box::new(default)});
+        }
+
+        let signature = signature(env, first.pats[j].unwrap());
+        let mut branches = vec![];
+        for (k, (name, arity)) in signature.iter().enumerate() {
+            let binders:Vec<String> = (0..*arity).map(|_| self.temp()).collect();
+            let sub = rows.iter()
+                .filter_map(|row| match row.pats[j] {
+                    None => Some(without(row, j, vec![None; *arity])),
+                    Some(p) => fields(env, p, k, *arity).map(|f| without(row, j, f))
+                })
+                .collect();
+            let mut cols = binders.clone();
+            cols.extend(others.iter().cloned());
+            let tree = self.tree(env, sub, cols).map_err(|w| {
+                let (args, rest) = w.split_at(*arity);
+                insert(rest.to_vec(), witness(name, args))
+            })?;
+            branches.push((binders, tree));
+        }
+        // A single constructor without field (unit) needs no test
+        if signature.len() == 1 && signature[0].1 == 0 {
+            return Ok(branches.pop().unwrap().1);
+        }
+        Ok(Tree::Switch{value:cols[j].clone(), branches})
+    }
+}
+
+
+/// Replace the column `j` of a row by the columns `pats`, put first.
+fn without<'p>(row:&Row<'p>, j:usize, mut pats:Vec<Option<&'p Pattern>>) -> Row<'p> {
+    pats.extend(row.pats.iter().enumerate().filter(|(k, _)| *k != j).map(|(_, p)| *p));
+    Row{pats, binds:row.binds.clone(), action:row.action}
+}
+
+/// Constructors of the type of a pattern, with their number of fields, in the order of their branches.
+fn signature(env:&Env, p:&Pattern) -> Vec<(String, usize)> {
+    let ctor = |name:&str, arity| (name.to_string(), arity);
+    match p {
+        Pattern::Unit{..} => vec![ctor("()", 0)],
+        Pattern::Tuple{items, ..} => vec![ctor(",", items.len())],
+        Pattern::Bool{..} => vec![ctor("true", 0), ctor("false", 0)],
+        Pattern::Nil{..} | Pattern::Cons{..} => vec![ctor("[]", 0), ctor("::", 2)],
+        Pattern::Ctor{name, ..} => {
+            let (data, _) = env.ctor(name).expect("typed constructor");
+            data.ctors.iter().map(|c| ctor(&c.name, c.fields.len())).collect()
+        }
+        Pattern::Wildcard{..} | Pattern::Var{..} | Pattern::Int{..} => unreachable!("not a constructor")
+    }
+}
+
+/// If the pattern matches the `k`-th constructor of its type, get the patterns of its `arity` fields.
+fn fields<'p>(env:&Env, p:&'p Pattern, k:usize, arity:usize) -> Option<Vec<Option<&'p Pattern>>> {
+    match p {
+        Pattern::Unit{..} | Pattern::Nil{..} if k == 0 => Some(vec![]),
+        Pattern::Tuple{items, ..} => Some(items.iter().map(Some).collect()),
+        Pattern::Bool{value, ..} if *value == (k == 0) => Some(vec![]),
+        Pattern::Cons{head, tail, ..} if k == 1 => Some(vec![Some(&**head), Some(&**tail)]),
+        Pattern::Ctor{name, arg, ..} if env.ctors.get(name).map(|(_, i)| *i) == Some(k) => super::ctor_patterns(arg.as_deref(), arity),
+        _ => None
+    }
+}
+
+/// Value of the constructor `name` with the fields `args`, as a pattern.
+fn witness(name:&str, args:&[String]) -> String {
+    match (name, args) {
+        (",", _) => format!("({})", args.join(", ")),
+        ("::", [h, t]) => format!("{} :: {}", atom(h), t),
+        (_, []) => name.to_string(),
+        (_, [a]) => format!("{} {}", name, atom(a)),
+        _ => format!("{} ({})", name, args.join(", "))
+    }
+}
+
+/// Parenthesize a pattern, unless it has no space outside of parenthesis and brackets.
+fn atom(p:&str) -> String {
+    let mut depth = 0;
+    for c in p.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ' ' if depth == 0 => return format!("({})", p),
+            _ => {}
+        }
+    }
+    p.to_string()
+}
+
+/// Count the leaves leading to each arm.
+fn count_uses(tree:&Tree, uses:&mut [usize]) {
+    match tree {
+        Tree::Leaf{action, ..} => uses[*action] += 1,
+        Tree::Switch{branches, ..} => for (_, t) in branches { count_uses(t, uses); },
+        Tree::Ints{cases, default, ..} => {
+            for (_, t) in cases { count_uses(t, uses); }
+            count_uses(default, uses);
+        }
+    }
+}
+
+/// Build the term of a decision tree. The arms which are shared are named by `shared`, the other ones are inlined.
+fn emit(tree:&Tree, actions:&[(Vec<String>, Box<Term>)], shared:&[Option<String>], span:Span) -> Box<Term> {
+    match tree {
+        Tree::Leaf{action, binds} => {
+            let (vars, body) = &actions[*action];
+            let head = match &shared[*action] {
+                Some(name) => sym(name, span),
+                None => abstract_over(vars, body.clone(), span)
+            };
+            vars.iter()
+                .map(|v| &binds.iter().rev().find(|(name, _)| name == v).expect("bound variable").1)
+                .fold(head, |acc, col| app(acc, sym(col, span), span))
+        }
+        Tree::Switch{value, branches} => branches.iter().fold(sym(value, span), |acc, (binders, t)| {
+            app(acc, abstract_over(binders, emit(t, actions, shared, span), span), span)
+        }),
+        // 'ml::eq value n case (...)'
+        Tree::Ints{value, cases, default} => cases.iter().rev().fold(emit(default, actions, shared, span), |acc, (n, t)| {
+            let test = app(app(prelude("eq", span), sym(value, span), span), Box::new(Term::Num{value:*n, span}), span);
+            app(app(test, emit(t, actions, shared, span), span), acc, span)
+        }),
+    }
+}
+
+fn sym(vname:&str, span:Span) -> Box<Term> {
+    Box::new(Term::Sym{vname:vname.to_string(), span})
+}
+
+/// Definition of the prelude.
+fn prelude(name:&str, span:Span) -> Box<Term> {
+    sym(&format!("{}::{}", NAMESPACE, name), span)
+}
+
+fn app(fun:Box<Term>, arg:Box<Term>, span:Span) -> Box<Term> {
+    Box::new(Term::App{fun, arg, span})
+}
+
+/// `(name -> body) value`
+fn let_(name:&str, value:Box<Term>, body:Box<Term>, span:Span) -> Box<Term> {
+    app(Box::new(Term::Lambda{vname:name.to_string(), body, span}), value, span)
+}
+
+fn abstract_over(vars:&[String], body:Box<Term>, span:Span) -> Box<Term> {
+    vars.iter().rev().fold(body, |acc, v| Box::new(Term::Lambda{vname:v.clone(), body:acc, span}))
+}
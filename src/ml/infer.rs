@@ -0,0 +1,443 @@
+//! Hindley-Milner type inference of Mini-ML.
+//! The error is located at the smallest expression (or pattern) whose type does not match the expected one.
+
+use eole_parser::ml::{BinOp, Binding, Expr, Pattern};
+use eole_parser::ast::Span;
+use crate::error::{self, EoleError, ErrorKind};
+use super::{Env, Type};
+
+use std::collections::HashMap;
+
+
+/// A type scheme: the type `ty` is polymorphic in the variables `vars`.
+#[derive(Clone, Debug)]
+struct Scheme {
+    vars:Vec<usize>,
+    ty:Type
+}
+
+impl Scheme {
+    /// A monomorphic type.
+    fn mono(ty:Type) -> Scheme {
+        Scheme{vars:vec![], ty}
+    }
+}
+
+/// Types of the local variables, the last one being the closest.
+type Locals = Vec<(String, Scheme)>;
+
+/// Variables bound by a pattern, with their types and locations.
+type Bound = Vec<(String, Type, Span)>;
+
+
+/// State of the inference.
+#[derive(Default)]
+pub(super) struct Infer {
+    /// Substitution of the type variables: `None` for an unknown type.
+    subst:Vec<Option<Type>>,
+    /// Type schemes of the definitions.
+    globals:HashMap<String, Scheme>,
+}
+
+impl Infer {
+
+    /// Type top-level definitions, and get their types.
+    /// On error, the names are bound to any type, to avoid further errors.
+    pub fn definitions(&mut self, env:&Env, rec:bool, bindings:&[Binding]) -> Result<Vec<(String, Type)>, EoleError> {
+        match self.bindings(env, rec, bindings, &mut vec![]) {
+            Ok(bound) => Ok(bound.into_iter()
+                .map(|(name, scheme)| {
+                    let ty = self.normalize(&scheme.ty);
+                    self.globals.insert(name.clone(), scheme);
+                    (name, ty)
+                })
+                .collect()),
+            Err(e) => {
+                for b in bindings {
+                    for (name, _) in b.pattern.vars() {
+                        let scheme = self.unknown();
+                        self.globals.insert(name.to_string(), scheme);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Type an expression to evaluate.
+    pub fn run(&mut self, env:&Env, e:&Expr) -> Result<Type, EoleError> {
+        let ty = self.infer(env, e, &mut vec![])?;
+        Ok(self.normalize(&ty))
+    }
+
+    /// New type variable.
+    fn fresh(&mut self) -> Type {
+        self.subst.push(None);
+        Type::Var(self.subst.len() - 1)
+    }
+
+    /// Type scheme of a definition which cannot be typed: any type.
+    fn unknown(&mut self) -> Scheme {
+        let ty = self.fresh();
+        Scheme{vars:vec![self.subst.len() - 1], ty}
+    }
+
+    /// Type definitions, `let` or `let rec`, and get the schemes of the names they bind, in order.
+    fn bindings(&mut self, env:&Env, rec:bool, bindings:&[Binding], locals:&mut Locals) -> Result<Vec<(String, Scheme)>, EoleError> {
+        let mut bound:Bound = vec![];
+        if rec {
+            // The names are monomorphic in the bodies
+            for b in bindings {
+                self.pattern(env, &b.pattern, &mut bound)?;
+            }
+            let depth = locals.len();
+            locals.extend(bound.iter().map(|(name, ty, _)| (name.clone(), Scheme::mono(ty.clone()))));
+            let res = bindings.iter().zip(&bound).try_for_each(|(b, (_, ty, _))| {
+                let inferred = self.function(env, &b.params, &b.body, locals)?;
+                self.expect(&b.body.span(), &inferred, ty)
+            });
+            locals.truncate(depth);
+            res?;
+        } else {
+            for b in bindings {
+                let inferred = self.function(env, &b.params, &b.body, locals)?;
+                let ty = self.pattern(env, &b.pattern, &mut bound)?;
+                self.expect(&b.body.span(), &inferred, &ty)?;
+            }
+        }
+        Ok(bound.into_iter().map(|(name, ty, _)| (name, self.generalize(&ty, locals))).collect())
+    }
+
+    /// Type a function `params -> body`, or the body alone without parameters.
+    fn function(&mut self, env:&Env, params:&[Pattern], body:&Expr, locals:&mut Locals) -> Result<Type, EoleError> {
+        let mut bound = vec![];
+        let mut args = vec![];
+        for p in params {
+            args.push(self.pattern(env, p, &mut bound)?);
+        }
+        let depth = locals.len();
+        locals.extend(bound.into_iter().map(|(name, ty, _)| (name, Scheme::mono(ty))));
+        let res = self.infer(env, body, locals);
+        locals.truncate(depth);
+        Ok(args.into_iter().rev().fold(res?, |acc, a| Type::Arrow(Box::new(a), Box::new(acc))))
+    }
+
+    /// Infer the type of an expression.
+    fn infer(&mut self, env:&Env, e:&Expr, locals:&mut Locals) -> Result<Type, EoleError> {
+        match e {
+            Expr::Int{..} => Ok(Type::base("int")),
+            Expr::Str{..} => Ok(Type::base("string")),
+            Expr::Bool{..} => Ok(Type::base("bool")),
+            Expr::Unit{..} => Ok(Type::base("unit")),
+
+            Expr::Var{name, span} => {
+                let scheme = match locals.iter().rev().find(|(n, _)| n == name) {
+                    Some((_, s)) => s.clone(),
+                    None => match self.globals.get(name) {
+                        Some(s) => s.clone(),
+                        None => match self.builtin(name) {
+                            Some(ty) => return Ok(ty),
+                            None => {
+                                let names:Vec<String> = locals.iter().map(|(n, _)| n.clone())
+                                    .chain(self.globals.keys().cloned())
+                                    .collect();
+                                let suggestion = error::suggest(name, &names);
+                                return Err(EoleError::new(ErrorKind::UnknownSymbol{name:name.clone(), suggestion}, *span));
+                            }
+                        }
+                    }
+                };
+                Ok(self.instantiate(&scheme))
+            }
+
+            Expr::Ctor{name, arg, span} => {
+                let (data, fields) = self.ctor(env, name, *span)?;
+                let args = super::ctor_args(arg.as_deref(), fields.len())
+                    .ok_or_else(|| arguments_error(name, fields.len(), arg.as_deref().map(expr_items), *span))?;
+                for (a, f) in args.iter().zip(&fields) {
+                    let ty = self.infer(env, a, locals)?;
+                    self.expect(&a.span(), &ty, f)?;
+                }
+                Ok(data)
+            }
+
+            Expr::Tuple{items, ..} => Ok(Type::Tuple(items.iter().map(|i| self.infer(env, i, locals)).collect::<Result<_, _>>()?)),
+
+            Expr::List{items, ..} => {
+                let item = self.fresh();
+                for i in items {
+                    let ty = self.infer(env, i, locals)?;
+                    self.expect(&i.span(), &ty, &item)?;
+                }
+                Ok(Type::Con(String::from("list"), vec![item]))
+            }
+
+            Expr::App{fun, arg, ..} => {
+                let f = self.infer(env, fun, locals)?;
+                let (param, res) = match self.shallow(&f) {
+                    Type::Arrow(param, res) => (*param, *res),
+                    Type::Var(_) => {
+                        let (param, res) = (self.fresh(), self.fresh());
+                        self.expect(&fun.span(), &f, &Type::Arrow(Box::new(param.clone()), Box::new(res.clone())))?;
+                        (param, res)
+                    }
+                    _ => {
+                        let ty = self.normalize(&f).to_string();
+                        return Err(EoleError::new(ErrorKind::NotAFunction{ty}, fun.span()));
+                    }
+                };
+                let a = self.infer(env, arg, locals)?;
+                self.expect(&arg.span(), &a, &param)?;
+                Ok(res)
+            }
+
+            Expr::Fun{params, body, ..} => self.function(env, params, body, locals),
+
+            Expr::Let{rec, bindings, body, ..} => {
+                let bound = self.bindings(env, *rec, bindings, locals)?;
+                let depth = locals.len();
+                locals.extend(bound);
+                let res = self.infer(env, body, locals);
+                locals.truncate(depth);
+                res
+            }
+
+            Expr::If{cond, then, other, ..} => {
+                let c = self.infer(env, cond, locals)?;
+                self.expect(&cond.span(), &c, &Type::base("bool"))?;
+                let t = self.infer(env, then, locals)?;
+                let o = self.infer(env, other, locals)?;
+                self.expect(&other.span(), &o, &t)?;
+                Ok(t)
+            }
+
+            Expr::Match{scrutinee, arms, ..} => {
+                let s = self.infer(env, scrutinee, locals)?;
+                let res = self.fresh();
+                for (p, body) in arms {
+                    let mut bound = vec![];
+                    let ty = self.pattern(env, p, &mut bound)?;
+                    self.expect(&p.span(), &ty, &s)?;
+                    let depth = locals.len();
+                    locals.extend(bound.into_iter().map(|(name, ty, _)| (name, Scheme::mono(ty))));
+                    let b = self.infer(env, body, locals);
+                    locals.truncate(depth);
+                    self.expect(&body.span(), &b?, &res)?;
+                }
+                Ok(res)
+            }
+
+            Expr::BinOp{op, lhs, rhs, ..} => {
+                let int = Type::base("int");
+                let (l, r, res) = match op {
+                    BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => (int.clone(), int.clone(), int),
+                    BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => (int.clone(), int, Type::base("bool")),
+                    BinOp::And | BinOp::Or => (Type::base("bool"), Type::base("bool"), Type::base("bool")),
+                    BinOp::Concat => (Type::base("string"), Type::base("string"), Type::base("string")),
+                    BinOp::Cons => {
+                        let item = self.fresh();
+                        let list = Type::Con(String::from("list"), vec![item.clone()]);
+                        (item, list.clone(), list)
+                    }
+                };
+                let lt = self.infer(env, lhs, locals)?;
+                self.expect(&lhs.span(), &lt, &l)?;
+                let rt = self.infer(env, rhs, locals)?;
+                self.expect(&rhs.span(), &rt, &r)?;
+                Ok(res)
+            }
+        }
+    }
+
+    /// Type of a pattern. Record the variables it binds in `bound`.
+    fn pattern(&mut self, env:&Env, p:&Pattern, bound:&mut Bound) -> Result<Type, EoleError> {
+        match p {
+            Pattern::Wildcard{..} => Ok(self.fresh()),
+            Pattern::Var{name, span} => {
+                if let Some((_, _, first)) = bound.iter().find(|(n, _, _)| n == name) {
+                    return Err(EoleError::new(ErrorKind::Duplicate{name:name.clone(), first:*first}, *span));
+                }
+                let ty = self.fresh();
+                bound.push((name.clone(), ty.clone(), *span));
+                Ok(ty)
+            }
+            Pattern::Int{..} => Ok(Type::base("int")),
+            Pattern::Bool{..} => Ok(Type::base("bool")),
+            Pattern::Unit{..} => Ok(Type::base("unit")),
+            Pattern::Tuple{items, ..} => Ok(Type::Tuple(items.iter().map(|i| self.pattern(env, i, bound)).collect::<Result<_, _>>()?)),
+            Pattern::Nil{..} => Ok(Type::Con(String::from("list"), vec![self.fresh()])),
+            Pattern::Cons{head, tail, ..} => {
+                let h = self.pattern(env, head, bound)?;
+                let list = Type::Con(String::from("list"), vec![h]);
+                let t = self.pattern(env, tail, bound)?;
+                self.expect(&tail.span(), &t, &list)?;
+                Ok(list)
+            }
+            Pattern::Ctor{name, arg, span} => {
+                let (data, fields) = self.ctor(env, name, *span)?;
+                let args = super::ctor_patterns(arg.as_deref(), fields.len())
+                    .ok_or_else(|| arguments_error(name, fields.len(), arg.as_deref().map(pattern_items), *span))?;
+                for (a, f) in args.iter().zip(&fields) {
+                    if let Some(a) = a {
+                        let ty = self.pattern(env, a, bound)?;
+                        self.expect(&a.span(), &ty, f)?;
+                    }
+                }
+                Ok(data)
+            }
+        }
+    }
+
+    /// Type of the functions of the prelude which are not operators, if `name` is one of them.
+    fn builtin(&mut self, name:&str) -> Option<Type> {
+        let arrow = |x:Type, y:Type| Type::Arrow(Box::new(x), Box::new(y));
+        match name {
+            "not" => Some(arrow(Type::base("bool"), Type::base("bool"))),
+            "fst" | "snd" => {
+                let (a, b) = (self.fresh(), self.fresh());
+                let res = if name == "fst" { a.clone() } else { b.clone() };
+                Some(arrow(Type::Tuple(vec![a, b]), res))
+            }
+            _ => None
+        }
+    }
+
+    /// Get the type of the values of a constructor, and the types of its fields, with fresh type parameters.
+    fn ctor(&mut self, env:&Env, name:&str, span:Span) -> Result<(Type, Vec<Type>), EoleError> {
+        match env.ctor(name) {
+            Some((data, ctor)) => {
+                let params:Vec<Type> = (0..data.params).map(|_| self.fresh()).collect();
+                let fields = ctor.fields.iter().map(|f| f.instantiate(&params)).collect();
+                Ok((Type::Con(data.name.clone(), params), fields))
+            }
+            None => {
+                let suggestion = error::suggest(name, env.ctors.keys());
+                Err(EoleError::new(ErrorKind::UnknownConstructor{name:name.to_string(), suggestion}, span))
+            }
+        }
+    }
+
+    /// Unify the type `found` of an expression (or a pattern) located at `span` with the type `expected`.
+    fn expect(&mut self, span:&Span, found:&Type, expected:&Type) -> Result<(), EoleError> {
+        if self.unify(found, expected) { return Ok(()); }
+        // Both types are printed with the same names for their variables
+        let mut names = HashMap::new();
+        let expected = self.rename(expected, &mut names).to_string();
+        let found = self.rename(found, &mut names).to_string();
+        Err(EoleError::new(ErrorKind::TypeMismatch{expected, found}, *span))
+    }
+
+    /// Follow the substitution of a variable.
+    fn shallow(&self, ty:&Type) -> Type {
+        match ty {
+            Type::Var(v) => match &self.subst[*v] {
+                Some(t) => self.shallow(t),
+                None => ty.clone()
+            },
+            _ => ty.clone()
+        }
+    }
+
+    /// Check if the variable `v` occurs in a type.
+    fn occurs(&self, v:usize, ty:&Type) -> bool {
+        match self.shallow(ty) {
+            Type::Var(w) => v == w,
+            Type::Con(_, items) | Type::Tuple(items) => items.iter().any(|t| self.occurs(v, t)),
+            Type::Arrow(a, b) => self.occurs(v, &a) || self.occurs(v, &b)
+        }
+    }
+
+    /// Unify two types, and tell if it succeeds.
+    fn unify(&mut self, a:&Type, b:&Type) -> bool {
+        match (self.shallow(a), self.shallow(b)) {
+            (Type::Var(x), Type::Var(y)) if x == y => true,
+            (Type::Var(x), t) | (t, Type::Var(x)) => {
+                if self.occurs(x, &t) { return false; }
+                self.subst[x] = Some(t);
+                true
+            }
+            (Type::Con(n1, a1), Type::Con(n2, a2)) => n1 == n2 && self.unify_all(&a1, &a2),
+            (Type::Tuple(a1), Type::Tuple(a2)) => self.unify_all(&a1, &a2),
+            (Type::Arrow(a1, r1), Type::Arrow(a2, r2)) => self.unify(&a1, &a2) && self.unify(&r1, &r2),
+            _ => false
+        }
+    }
+
+    fn unify_all(&mut self, a:&[Type], b:&[Type]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(x, y)| self.unify(x, y))
+    }
+
+    /// Free type variables of a type, in order of appearance.
+    fn free_vars(&self, ty:&Type, res:&mut Vec<usize>) {
+        match self.shallow(ty) {
+            Type::Var(v) => if !res.contains(&v) { res.push(v); },
+            Type::Con(_, items) | Type::Tuple(items) => for t in &items { self.free_vars(t, res); },
+            Type::Arrow(a, b) => {
+                self.free_vars(&a, res);
+                self.free_vars(&b, res);
+            }
+        }
+    }
+
+    /// Generalize a type over the variables which are not free in the local variables.
+    fn generalize(&self, ty:&Type, locals:&[(String, Scheme)]) -> Scheme {
+        let mut bound = vec![];
+        for (_, s) in locals {
+            let mut vars = vec![];
+            self.free_vars(&s.ty, &mut vars);
+            bound.extend(vars.into_iter().filter(|v| !s.vars.contains(v)));
+        }
+        let mut vars = vec![];
+        self.free_vars(ty, &mut vars);
+        vars.retain(|v| !bound.contains(v));
+        Scheme{vars, ty:ty.clone()}
+    }
+
+    /// Instantiate a type scheme with fresh variables.
+    fn instantiate(&mut self, scheme:&Scheme) -> Type {
+        let fresh:HashMap<usize, Type> = scheme.vars.iter().map(|v| (*v, self.fresh())).collect();
+        self.replace(&scheme.ty, &fresh)
+    }
+
+    fn replace(&self, ty:&Type, fresh:&HashMap<usize, Type>) -> Type {
+        match self.shallow(ty) {
+            Type::Var(v) => fresh.get(&v).cloned().unwrap_or(Type::Var(v)),
+            Type::Con(name, items) => Type::Con(name, items.iter().map(|t| self.replace(t, fresh)).collect()),
+            Type::Tuple(items) => Type::Tuple(items.iter().map(|t| self.replace(t, fresh)).collect()),
+            Type::Arrow(a, b) => Type::Arrow(Box::new(self.replace(&a, fresh)), Box::new(self.replace(&b, fresh)))
+        }
+    }
+
+    /// Apply the substitution to a type, numbering its variables from 0 in order of appearance.
+    fn normalize(&self, ty:&Type) -> Type {
+        self.rename(ty, &mut HashMap::new())
+    }
+
+    /// Apply the substitution to a type, numbering its variables in order of appearance, as recorded in `names`.
+    fn rename(&self, ty:&Type, names:&mut HashMap<usize, usize>) -> Type {
+        match self.shallow(ty) {
+            Type::Var(v) => {
+                let next = names.len();
+                Type::Var(*names.entry(v).or_insert(next))
+            }
+            Type::Con(name, items) => Type::Con(name, items.iter().map(|t| self.rename(t, names)).collect()),
+            Type::Tuple(items) => Type::Tuple(items.iter().map(|t| self.rename(t, names)).collect()),
+            Type::Arrow(a, b) => Type::Arrow(Box::new(self.rename(&a, names)), Box::new(self.rename(&b, names)))
+        }
+    }
+}
+
+
+/// Error of a constructor given `found` arguments (`None` for no argument).
+fn arguments_error(name:&str, expected:usize, found:Option<usize>, span:Span) -> EoleError {
+    EoleError::new(ErrorKind::ConstructorArguments{name:name.to_string(), expected, found:found.unwrap_or(0)}, span)
+}
+
+/// Number of arguments in the argument of a constructor.
+fn expr_items(e:&Expr) -> usize {
+    match e { Expr::Tuple{items, ..} => items.len(), _ => 1 }
+}
+
+fn pattern_items(p:&Pattern) -> usize {
+    match p { Pattern::Tuple{items, ..} => items.len(), _ => 1 }
+}
@@ -0,0 +1,473 @@
+//! # Mini-ML
+//! A small ML-like language, compiled to Éole terms. Files with the `.eml` extension are read as Mini-ML,
+//! see `eole_parser::ml` for the syntax.
+//!
+//! ## Typing
+//! A program is typed as in ML before being compiled: the definitions are polymorphic, the type errors are reported
+//! with the expected and the found types, and the pattern matchings must be exhaustive.
+//! The base types are `int`, `bool`, `string`, `unit` and `'a list`, with the tuples, the functions,
+//! and the data types declared with `type`. A definition shadows the previous definitions of the same name.
+//!
+//! ## Compilation
+//! Each phrase becomes a sentence: the definitions are Éole definitions, the declared types are Éole data types
+//! (see `data`), and the expressions to evaluate are terms to run. The values are encoded as follows:
+//!   * integers are Church's numerals, and strings are lists of Church's numerals;
+//!   * booleans are Church's booleans (`x y -> x` and `x y -> y`), `if c then a else b` being `c a b`;
+//!   * a tuple `(a, b)` is `s -> s a b`, and `()` is the identity;
+//!   * lists are in the Scott encoding, like the values of the declared types.
+//!
+//! The integers are natural numbers: the subtraction stops at 0, and the division by 0 does not terminate.
+//! The comparisons only apply to integers.
+//! The operators and the functions `not`, `fst` and `snd` are defined by a prelude, in the namespace `ml`.
+//!
+//! A pattern matching is compiled to a decision tree, testing each value once.
+//!
+//! ## Results
+//! The results are decoded back to ML values according to their types, e.g. `[(1, true)]` or `Some "hello"`.
+//! The functions, and the values which cannot be decoded, are printed as lambda terms.
+
+mod infer;
+mod compile;
+
+use eole_parser::{ast::*, ml::{self, Expr, Pattern, Phrase, TypeDecl, TypeExpr}};
+use crate::encoding::{self, Lists, Numerals};
+use crate::error::{self, EoleError, ErrorKind, Sources};
+use crate::import;
+use crate::typing::var_name;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+pub use eole_parser::ml::EXTENSION;
+
+
+/// Definitions of the prelude, qualified with `ml::` once parsed.
+const PRELUDE:&str = "
+true    = x y -> x.
+false   = x y -> y.
+not     = b -> b false true.
+unit    = x -> x.
+fst     = p -> p (a b -> a).
+snd     = p -> p (a b -> b).
+succ    = n f x -> f (n f x).
+pred    = n f x -> n (g h -> h (g f)) (u -> x) (u -> u).
+add     = a b f x -> a f (b f x).
+sub     = a b -> b pred a.
+mul     = a b f -> a (b f).
+is_zero = n -> n (x -> false) true.
+le      = a b -> is_zero (sub a b).
+lt      = a b -> not (le b a).
+ge      = a b -> le b a.
+gt      = a b -> lt b a.
+eq      = a b -> le a b (le b a) false.
+ne      = a b -> not (eq a b).
+rec div = a b -> lt a b 0 (succ (div (sub a b) b)).
+mod     = a b -> sub a (mul (div a b) b).
+cons    = h t n c -> c h t.
+rec append = l m -> l m (h t -> cons h (append t m)).
+";
+
+/// Namespace of the prelude.
+const NAMESPACE:&str = "ml";
+
+
+/// A type of Mini-ML.
+/// The base types are named types without argument (e.g. `int`), and `list` takes one argument.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Type {
+    Var(usize),
+    Con(String, Vec<Type>),
+    Tuple(Vec<Type>),
+    Arrow(Box<Type>, Box<Type>),
+}
+
+impl Type {
+    /// A named type without argument.
+    fn base(name:&str) -> Type {
+        Type::Con(name.to_string(), vec![])
+    }
+
+    /// Replace the variables by the types `args`.
+    fn instantiate(&self, args:&[Type]) -> Type {
+        match self {
+            Type::Var(v) => args[*v].clone(),
+            Type::Con(name, items) => Type::Con(name.clone(), items.iter().map(|t| t.instantiate(args)).collect()),
+            Type::Tuple(items) => Type::Tuple(items.iter().map(|t| t.instantiate(args)).collect()),
+            Type::Arrow(a, b) => Type::Arrow(Box::new(a.instantiate(args)), Box::new(b.instantiate(args))),
+        }
+    }
+
+    /// Print the type, parenthesized if it is an arrow or a tuple and `atom` is required.
+    fn fmt_prec(&self, f:&mut fmt::Formatter, atom:bool)->fmt::Result{
+        match self {
+            Type::Var(v) => write!(f, "'{}", var_name(*v)),
+            Type::Con(name, args) => {
+                match args.len() {
+                    0 => {}
+                    1 => { args[0].fmt_prec(f, true)?; write!(f, " ")?; }
+                    _ => {
+                        write!(f, "(")?;
+                        for (i, a) in args.iter().enumerate() {
+                            if i > 0 { write!(f, ", ")?; }
+                            a.fmt_prec(f, false)?;
+                        }
+                        write!(f, ") ")?;
+                    }
+                }
+                write!(f, "{}", name)
+            }
+            Type::Tuple(items) => {
+                if atom { write!(f, "(")?; }
+                for (i, t) in items.iter().enumerate() {
+                    if i > 0 { write!(f, " * ")?; }
+                    t.fmt_prec(f, true)?;
+                }
+                if atom { write!(f, ")")?; }
+                Ok(())
+            }
+            Type::Arrow(a, b) => {
+                if atom { write!(f, "(")?; }
+                a.fmt_prec(f, true)?;
+                write!(f, " -> ")?;
+                b.fmt_prec(f, false)?;
+                if atom { write!(f, ")")?; }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        self.fmt_prec(f, false)
+    }
+}
+
+
+/// A declared data type.
+#[derive(Clone, Debug)]
+pub struct DataType {
+    pub name:String,
+    pub params:usize,
+    pub ctors:Vec<Constructor>,
+}
+
+/// A constructor, with the types of its fields. The variable `i` of the fields stands for the parameter `i` of the type.
+#[derive(Clone, Debug)]
+pub struct Constructor {
+    pub name:String,
+    pub fields:Vec<Type>,
+}
+
+
+/// A compiled Mini-ML program.
+#[derive(Clone, Debug)]
+pub struct Program {
+    /// The sentences, with the prelude.
+    pub sentences:Vec<Sentence>,
+    /// The types of the definitions and of the expressions to evaluate, in order.
+    pub types:Vec<(String, Type)>,
+    /// The types of the expressions to evaluate, used to decode their results.
+    pub results:Vec<Type>,
+    /// The declared data types.
+    pub datatypes:Vec<DataType>,
+}
+
+
+/// Read, type and compile a Mini-ML file. The file and the prelude are recorded in `sources`.
+/// All the type errors are reported.
+pub fn load(path:&Path, sources:&mut Sources) -> Result<Program, Vec<EoleError>> {
+    let source = fs::read_to_string(path)
+        .map_err(|error| vec![EoleError::unlocated(ErrorKind::Io{path:path.to_path_buf(), error})])?;
+    let file = sources.add(path, source);
+    let phrases = ml::parse(file, sources.text(file)).map_err(|e| vec![EoleError::from_ml(file, e)])?;
+    let prelude = sources.add(Path::new("<prelude>"), PRELUDE.to_string());
+    let prelude = import::namespace(import::parse(prelude, sources.text(prelude))?, NAMESPACE);
+    translate(&phrases, prelude)
+}
+
+
+/// The data types and the definitions in scope.
+#[derive(Default)]
+struct Env {
+    /// The declared data types, and where they are declared.
+    datatypes:Vec<(DataType, Span)>,
+    /// The type and the index of each constructor.
+    ctors:HashMap<String, (usize, usize)>,
+    /// Name of the Éole definition of each Mini-ML definition.
+    globals:HashMap<String, String>,
+}
+
+impl Env {
+
+    /// Get a constructor, with its data type.
+    fn ctor(&self, name:&str) -> Option<(&DataType, &Constructor)> {
+        self.ctors.get(name).map(|(t, c)| {
+            let d = &self.datatypes[*t].0;
+            (d, &d.ctors[*c])
+        })
+    }
+
+    /// Name of a new Éole definition for the Mini-ML definition `name`: the definitions shadowing another one are renamed.
+    fn define(&mut self, name:&str) -> String {
+        let new = match self.globals.get(name) {
+            None => name.to_string(),
+            Some(_) => (2..).map(|i| format!("{}#{}", name, i)).find(|n| !self.globals.values().any(|v| v == n)).unwrap()
+        };
+        self.globals.insert(name.to_string(), new.clone());
+        new
+    }
+
+    /// Declare a data type, and get its Éole declaration.
+    fn declare(&mut self, decl:&TypeDecl) -> Result<SData, Vec<EoleError>> {
+        let mut errors = vec![];
+        if let Some((_, first)) = self.datatypes.iter().find(|(d, _)| d.name == decl.name) {
+            errors.push(EoleError::new(ErrorKind::Duplicate{name:decl.name.clone(), first:*first}, decl.span));
+        }
+        // Declared first: the type can be recursive
+        let index = self.datatypes.len();
+        self.datatypes.push((DataType{name:decl.name.clone(), params:decl.params.len(), ctors:vec![]}, decl.span));
+        let mut ctors = vec![];
+        let mut spans:HashMap<&str, Span> = HashMap::new();
+        for c in &decl.ctors {
+            if let Some(first) = spans.get(c.name.as_str()) {
+                errors.push(EoleError::new(ErrorKind::Duplicate{name:c.name.clone(), first:*first}, c.span));
+            }
+            spans.insert(&c.name, c.span);
+            let mut fields = vec![];
+            for t in &c.fields {
+                match self.type_expr(t, &decl.params) {
+                    Ok(t) => fields.push(t),
+                    Err(e) => errors.push(e)
+                }
+            }
+            ctors.push(Constructor{name:c.name.clone(), fields});
+        }
+        if !errors.is_empty() {
+            self.datatypes.pop();
+            return Err(errors);
+        }
+        for (i, c) in ctors.iter().enumerate() {
+            self.ctors.insert(c.name.clone(), (index, i));
+        }
+        self.datatypes[index].0.ctors = ctors;
+        Ok(SData{
+            name:decl.name.clone(),
+            ctors:decl.ctors.iter().map(|c| Ctor{
+                name:c.name.clone(),
+                fields:(1..=c.fields.len()).map(|k| format!("x{}", k)).collect(),
+                span:c.span
+            }).collect(),
+            span:decl.span
+        })
+    }
+
+    /// Convert a type of a declaration, with the type parameters `params`.
+    fn type_expr(&self, t:&TypeExpr, params:&[String]) -> Result<Type, EoleError> {
+        match t {
+            TypeExpr::Var{name, span} => match params.iter().position(|p| p == name) {
+                Some(i) => Ok(Type::Var(i)),
+                None => Err(EoleError::new(ErrorKind::UnknownType{name:name.clone(), suggestion:error::suggest(name, params)}, *span))
+            },
+            TypeExpr::Con{name, args, span} => {
+                let expected = match name.as_str() {
+                    "int" | "bool" | "string" | "unit" => 0,
+                    "list" => 1,
+                    _ => match self.datatypes.iter().find(|(d, _)| d.name == *name) {
+                        Some((d, _)) => d.params,
+                        None => {
+                            let names:Vec<String> = ["int", "bool", "string", "unit", "list"].iter().map(|n| n.to_string())
+                                .chain(self.datatypes.iter().map(|(d, _)| d.name.clone()))
+                                .collect();
+                            let suggestion = error::suggest(name, &names);
+                            return Err(EoleError::new(ErrorKind::UnknownType{name:name.clone(), suggestion}, *span));
+                        }
+                    }
+                };
+                if args.len() != expected {
+                    return Err(EoleError::new(ErrorKind::TypeArity{name:name.clone(), expected, found:args.len()}, *span));
+                }
+                let args = args.iter().map(|a| self.type_expr(a, params)).collect::<Result<_, _>>()?;
+                Ok(Type::Con(name.clone(), args))
+            }
+            TypeExpr::Tuple{items, ..} => Ok(Type::Tuple(items.iter().map(|t| self.type_expr(t, params)).collect::<Result<_, _>>()?)),
+            TypeExpr::Arrow{arg, res, ..} => Ok(Type::Arrow(Box::new(self.type_expr(arg, params)?), Box::new(self.type_expr(res, params)?))),
+        }
+    }
+}
+
+
+/// Arguments of a constructor with `arity` fields: a tuple is spread over the fields.
+/// Return `None` if the number of arguments does not match.
+fn ctor_args(arg:Option<&Expr>, arity:usize) -> Option<Vec<&Expr>> {
+    match (arg, arity) {
+        (None, 0) => Some(vec![]),
+        (Some(a), 1) => Some(vec![a]),
+        (Some(Expr::Tuple{items, ..}), n) if items.len() == n => Some(items.iter().collect()),
+        _ => None
+    }
+}
+
+/// Patterns of the fields of a constructor with `arity` fields, like `ctor_args`.
+/// As in OCaml, `C _` matches all the fields (`None` standing for a wildcard).
+fn ctor_patterns(arg:Option<&Pattern>, arity:usize) -> Option<Vec<Option<&Pattern>>> {
+    match (arg, arity) {
+        (None, 0) => Some(vec![]),
+        (Some(p), 1) => Some(vec![Some(p)]),
+        (Some(Pattern::Tuple{items, ..}), n) if items.len() == n => Some(items.iter().map(Some).collect()),
+        (Some(Pattern::Wildcard{..}), n) => Some(vec![None; n]),
+        _ => None
+    }
+}
+
+
+/// Type and compile the phrases of a program, after the definitions of the prelude.
+/// The phrases are compiled as long as no error is found: once an error is found, the other phrases are only typed.
+fn translate(phrases:&[Phrase], prelude:Vec<Sentence>) -> Result<Program, Vec<EoleError>> {
+    let mut env = Env::default();
+    let mut inf = infer::Infer::default();
+    let mut comp = compile::Compiler::default();
+    let mut sentences = prelude;
+    let mut types = vec![];
+    let mut results = vec![];
+    let mut errors = vec![];
+
+    for phrase in phrases {
+        match phrase {
+            Phrase::Type(decl) => match env.declare(decl) {
+                Ok(data) => sentences.push(Sentence::Data(data)),
+                Err(mut e) => errors.append(&mut e)
+            },
+
+            Phrase::Let{rec, bindings, ..} => {
+                let typed = inf.definitions(&env, *rec, bindings);
+                let compiled = if errors.is_empty() && typed.is_ok() { comp.definitions(&mut env, *rec, bindings) } else { Ok(vec![]) };
+                match (typed, compiled) {
+                    (Ok(mut t), Ok(mut s)) => {
+                        types.append(&mut t);
+                        sentences.append(&mut s);
+                    }
+                    (Err(e), _) | (_, Err(e)) => errors.push(e)
+                }
+            }
+
+            Phrase::Run(e) => {
+                let typed = inf.run(&env, e);
+                let compiled = if errors.is_empty() && typed.is_ok() { comp.run(&env, e).map(Some) } else { Ok(None) };
+                match (typed, compiled) {
+                    (Ok(ty), Ok(s)) => {
+                        types.push((results.len().to_string(), ty.clone()));
+                        results.push(ty);
+                        sentences.extend(s);
+                    }
+                    (Err(e), _) | (_, Err(e)) => errors.push(e)
+                }
+            }
+        }
+    }
+
+    if !errors.is_empty() { return Err(errors); }
+    let datatypes = env.datatypes.into_iter().map(|(d, _)| d).collect();
+    Ok(Program{ sentences, types, results, datatypes })
+}
+
+
+/// Decode the result of an expression of type `ty` as an ML value, e.g. `Some [1; 2]`.
+/// The functions, and the values which cannot be decoded, are printed as lambda terms.
+pub fn decode(term:&Term, ty:&Type, datatypes:&[DataType]) -> String {
+    decode_(term, ty, datatypes).0
+}
+
+/// Decode a value. Also tell if the printed value is atomic, i.e. does not need parenthesis as an argument.
+fn decode_(term:&Term, ty:&Type, datatypes:&[DataType]) -> (String, bool) {
+    value(term, ty, datatypes).unwrap_or_else(|| {
+        let lambda = term.canonical().to_string();
+        let atom = !lambda.contains(' ');
+        (lambda, atom)
+    })
+}
+
+fn value(term:&Term, ty:&Type, datatypes:&[DataType]) -> Option<(String, bool)> {
+    match ty {
+        Type::Con(name, args) => match (name.as_str(), args.as_slice()) {
+            ("int", _) => encoding::numeral_value(Numerals::Church, term).map(|n| (n.to_string(), true)),
+            ("unit", _) => Some((String::from("()"), true)),
+            ("bool", _) => match term {
+                Term::Lambda{vname:x, body, ..} => match &**body {
+                    Term::Lambda{vname:y, body, ..} => match &**body {
+                        Term::Sym{vname, ..} if vname == y => Some((String::from("false"), true)),
+                        Term::Sym{vname, ..} if vname == x => Some((String::from("true"), true)),
+                        _ => None
+                    },
+                    _ => None
+                },
+                _ => None
+            },
+            ("string", _) => {
+                let value:Option<String> = encoding::list_items(Lists::Scott, term)?.into_iter()
+                    .map(|c| encoding::numeral_value(Numerals::Church, c).and_then(|n| u32::try_from(n).ok()).and_then(char::from_u32))
+                    .collect();
+                Some((format!("{:?}", value?), true))
+            }
+            ("list", [item]) => {
+                let items:Vec<String> = encoding::list_items(Lists::Scott, term)?.into_iter()
+                    .map(|i| decode_(i, item, datatypes).0)
+                    .collect();
+                Some((format!("[{}]", items.join("; ")), true))
+            }
+            _ => {
+                let d = datatypes.iter().find(|d| d.name == *name)?;
+                let (i, fields) = scott(term, d.ctors.len())?;
+                let ctor = &d.ctors[i];
+                if ctor.fields.len() != fields.len() { return None; }
+                let fields:Vec<(String, bool)> = fields.iter().zip(&ctor.fields)
+                    .map(|(f, t)| decode_(f, &t.instantiate(args), datatypes))
+                    .collect();
+                Some(match fields.len() {
+                    0 => (ctor.name.clone(), true),
+                    1 if fields[0].1 => (format!("{} {}", ctor.name, fields[0].0), false),
+                    1 => (format!("{} ({})", ctor.name, fields[0].0), false),
+                    _ => {
+                        let fields:Vec<String> = fields.into_iter().map(|f| f.0).collect();
+                        (format!("{} ({})", ctor.name, fields.join(", ")), false)
+                    }
+                })
+            }
+        },
+        Type::Tuple(items) => {
+            let (_, fields) = scott(term, 1)?;
+            if fields.len() != items.len() { return None; }
+            let fields:Vec<String> = fields.iter().zip(items).map(|(f, t)| decode_(f, t, datatypes).0).collect();
+            Some((format!("({})", fields.join(", ")), true))
+        }
+        Type::Var(_) | Type::Arrow(..) => None
+    }
+}
+
+/// Read a value in the Scott encoding with `n` constructors: `c1 ... cn -> ci f1 ... fk`, where no `c` occurs in the fields.
+/// Return the index of the constructor, and the fields.
+fn scott(term:&Term, n:usize) -> Option<(usize, Vec<&Term>)> {
+    let mut selectors = vec![];
+    let mut body = term;
+    for _ in 0..n {
+        match body {
+            Term::Lambda{vname, body:b, ..} => { selectors.push(vname); body = b; }
+            _ => return None
+        }
+    }
+    let mut fields = vec![];
+    while let Term::App{fun, arg, ..} = body {
+        fields.push(&**arg);
+        body = fun;
+    }
+    fields.reverse();
+    let i = match body {
+        Term::Sym{vname, ..} => selectors.iter().rposition(|s| *s == vname)?,
+        _ => return None
+    };
+    let closed = fields.iter().all(|f| selectors.iter().all(|s| !f.occurs_free(s)));
+    if closed { Some((i, fields)) } else { None }
+}
@@ -0,0 +1,153 @@
+//! # Pragmas
+//! A file can set the options it is meant to run with, one `#pragma` per line:
+//! ```text
+//! #pragma strategy lazy
+//! #pragma gc none
+//! #pragma reduction-limit 50
+//! ```
+//! Each pragma stands for an option of the command line, which takes precedence when it is given explicitly.
+//! Only the pragmas of the file given on the command line are used: the ones of the files it reads are ignored.
+//!
+//! | Pragma            | Option              | Value                                          |
+//! |-------------------|---------------------|------------------------------------------------|
+//! | `strategy`        | `-s`                | `lazy`, `full`, `head`, `cbv` or `applicative` |
+//! | `gc`              | `-m`                | `erasink` or `none`                            |
+//! | `compactor`       | `-M`                | a number                                       |
+//! | `reduction-limit` | `-r`                | a number                                       |
+//! | `lambda-limit`    | `-l`                | a number                                       |
+//! | `time-limit`      | `--time-limit`      | a number of seconds                            |
+//! | `max-nodes`       | `--max-nodes`       | a number                                       |
+//! | `max-memory`      | `--max-memory`      | a number of MiB                                |
+//! | `numerals`        | `--numerals`        | `church`, `scott` or `binary`                  |
+//! | `lists`           | `--lists`           | `scott` or `church`                            |
+//! | `decode`          | `--decode`          | none                                           |
+//! | `eal`             | `--eal`             | none                                           |
+
+use eole_parser::ast::{Sentence, SPragma, Span};
+use crate::error::{self, EoleError, ErrorKind};
+
+use std::collections::HashMap;
+
+
+/// Reduction strategies, for `-s` and `#pragma strategy`.
+pub const STRATEGIES:&[&str] = &["lazy", "full", "head", "cbv", "applicative"];
+
+/// Garbage collectors, for `-m` and `#pragma gc`.
+pub const GCS:&[&str] = &["erasink", "none"];
+
+/// Encodings of the numeric literals, for `--numerals` and `#pragma numerals`.
+pub const NUMERALS:&[&str] = &["church", "scott", "binary"];
+
+/// Encodings of the list literals, for `--lists` and `#pragma lists`.
+pub const LISTS:&[&str] = &["scott", "church"];
+
+
+/// Value expected by a pragma.
+#[derive(Copy,Clone,Debug)]
+enum Value {
+    /// No value: the pragma is a switch.
+    Flag,
+    /// A positive integer.
+    Number,
+    /// A positive integer of MiB, which must fit in a number of bytes.
+    Mebibytes,
+    /// One of the given words.
+    Choice(&'static [&'static str]),
+}
+
+/// The known pragmas, with their value.
+const PRAGMAS:&[(&str, Value)] = &[
+    ("strategy", Value::Choice(STRATEGIES)),
+    ("gc", Value::Choice(GCS)),
+    ("compactor", Value::Number),
+    ("reduction-limit", Value::Number),
+    ("lambda-limit", Value::Number),
+    ("time-limit", Value::Number),
+    ("max-nodes", Value::Number),
+    ("max-memory", Value::Mebibytes),
+    ("numerals", Value::Choice(NUMERALS)),
+    ("lists", Value::Choice(LISTS)),
+    ("decode", Value::Flag),
+    ("eal", Value::Flag),
+];
+
+
+/// The pragmas of a file, checked against the known pragmas.
+#[derive(Debug,Default)]
+pub struct Pragmas {
+    values:HashMap<String, Option<String>>
+}
+
+impl Pragmas {
+    /// Get the value of a pragma, if it is set with a value.
+    pub fn value(&self, name:&str) -> Option<&str> {
+        self.values.get(name).and_then(|v| v.as_deref())
+    }
+
+    /// Check if a pragma is set.
+    pub fn is_set(&self, name:&str) -> bool {
+        self.values.contains_key(name)
+    }
+}
+
+
+/// Gather and check the pragmas of the sentences.
+pub fn collect(sentences:&[Sentence]) -> Result<Pragmas, Vec<EoleError>> {
+    let mut res = Pragmas::default();
+    let mut spans:HashMap<&str, Span> = HashMap::new();
+    let mut errors = vec![];
+    for s in sentences {
+        if let Sentence::Pragma(p) = s {
+            if let Err(e) = check(p) {
+                errors.push(e);
+                continue;
+            }
+            match spans.get(p.name.as_str()) {
+                Some(first) => errors.push(EoleError::new(ErrorKind::Duplicate{name:format!("#pragma {}", p.name), first:*first}, p.span)),
+                None => {
+                    spans.insert(&p.name, p.span);
+                    res.values.insert(p.name.clone(), p.value.clone());
+                }
+            }
+        }
+    }
+    if errors.is_empty() { Ok(res) } else { Err(errors) }
+}
+
+/// Check that a pragma is known, and that its value is valid.
+fn check(p:&SPragma) -> Result<(), EoleError> {
+    let value = match PRAGMAS.iter().find(|(name, _)| *name == p.name) {
+        Some((_, value)) => *value,
+        None => {
+            let names:Vec<String> = PRAGMAS.iter().map(|(name, _)| name.to_string()).collect();
+            let suggestion = error::suggest(&p.name, &names);
+            return Err(EoleError::new(ErrorKind::UnknownPragma{name:p.name.clone(), suggestion}, p.span));
+        }
+    };
+    let valid = match (value, &p.value) {
+        (Value::Flag, v) => v.is_none(),
+        (Value::Number, Some(v)) => v.parse::<usize>().is_ok(),
+        (Value::Mebibytes, Some(v)) => to_bytes(v).is_some(),
+        (Value::Choice(choices), Some(v)) => choices.contains(&v.as_str()),
+        (_, None) => false
+    };
+    if valid {
+        Ok(())
+    } else {
+        let expected = match value {
+            Value::Flag => String::from("no value"),
+            Value::Number => String::from("a positive integer"),
+            Value::Mebibytes => format!("a positive integer of at most {} MiB", usize::MAX >> 20),
+            Value::Choice(choices) => {
+                let choices:Vec<String> = choices.iter().map(|c| format!("`{}`", c)).collect();
+                format!("one of {}", choices.join(", "))
+            }
+        };
+        Err(EoleError::new(ErrorKind::InvalidPragma{name:p.name.clone(), expected}, p.span))
+    }
+}
+
+/// Convert a number of MiB into a number of bytes, if it fits.
+pub fn to_bytes(mib:&str) -> Option<usize> {
+    mib.parse::<usize>().ok().and_then(|m| m.checked_mul(1 << 20))
+}
@@ -0,0 +1,380 @@
+//! # Type inference
+//! Hindley-Milner type inference of the definitions and of the terms to run, with the `--typecheck` option.
+//!
+//! Éole reduces untyped terms, but the simply typed ones (and the ones typed with let-polymorphism) are well behaved.
+//! The principal type of each definition is inferred, the definitions being typed in dependency order (see `dependency`).
+//! The definitions are polymorphic, as well as the local definitions `with x = v, body`.
+//! The definitions of a `rec` group are monomorphic in their bodies, and polymorphic afterward.
+//!
+//! The literals have the type of their encoding (e.g. `(a -> a) -> a -> a` for Church's numerals).
+//! The constructors of the data types are typed like any other definition, from their Scott encoding.
+//!
+//! ## Annotations
+//! A term can be annotated with a type: `(term : T)`, and so can a binder: `(x : T) -> body`.
+//! A type is built from type variables, arrows and `_` (an unspecified type).
+//! As in OCaml, the type variables of the annotations are not required to stay polymorphic:
+//! they stand for unknown types, shared by all the annotations of a definition.
+//!
+//! ## Errors
+//! As there is no base type, the only possible error is an infinite type (e.g. in `x -> x x`).
+//! The recursive data types, such as lists, are infinite types in the Scott encoding: the functions matching them cannot be typed.
+//! The error is located at the smallest term that cannot be typed.
+
+use eole_parser::ast::{*, Term::*};
+use crate::dependency::Dependencies;
+use crate::encoding::{self, Encodings};
+use crate::error::{EoleError, ErrorKind};
+use crate::import;
+
+use std::collections::{HashMap, HashSet};
+
+
+/// A type, during the inference.
+#[derive(Clone, Debug)]
+enum Ty {
+    Var(usize),
+    Arrow(Box<Ty>, Box<Ty>),
+}
+
+/// A type scheme: the type `ty` is polymorphic in the variables `vars`.
+#[derive(Clone, Debug)]
+struct Scheme {
+    vars:Vec<usize>,
+    ty:Ty
+}
+
+impl Scheme {
+    /// A monomorphic type.
+    fn mono(ty:Ty) -> Scheme {
+        Scheme{vars:vec![], ty}
+    }
+}
+
+
+/// Infer the types of the definitions and of the terms to run.
+/// The types are given in the order of the sentences, with the name of the definition,
+/// or the name (or the rank) of the term to run, as for their results.
+/// The definitions which cannot be typed are reported as errors. They are left out of the types,
+/// as well as the definitions and the terms using them, without further errors.
+/// The data types must have been desugared, see `data::desugar`.
+pub fn infer(sentences:&[Sentence], encodings:Encodings) -> (Vec<(String, Type)>, Vec<EoleError>) {
+    // Scope errors and cycles first: the inference requires a valid text
+    let deps = Dependencies::new(sentences);
+    let mut errors = deps.cycles();
+    errors.extend(deps.check());
+    if !errors.is_empty() { return (vec![], errors); }
+
+    let mut inf = Infer{ subst:vec![], encodings, globals:HashMap::new(), annotations:HashMap::new() };
+    let mut failed:HashSet<&str> = HashSet::new();
+
+    // Definitions, in dependency order
+    for d in deps.sorted() {
+        match d {
+            // Using a definition which cannot be typed: already reported
+            _ if uses_failed(d, &failed) => for b in bodies(d) { failed.insert(b.0); },
+            Sentence::Let(SLet{vname, body, ..}) => {
+                let scheme = match inf.infer_top(body) {
+                    Ok(ty) => inf.generalize(&ty, &[]),
+                    Err(e) => { errors.push(e); failed.insert(vname.as_str()); inf.unknown() }
+                };
+                inf.globals.insert(vname.clone(), scheme);
+            }
+            Sentence::Rec(SRec{defs, ..}) => {
+                let tys:Vec<Ty> = defs.iter().map(|_| inf.fresh()).collect();
+                for (d, ty) in defs.iter().zip(&tys) {
+                    inf.globals.insert(d.vname.clone(), Scheme::mono(ty.clone()));
+                }
+                let res:Result<(), EoleError> = defs.iter().zip(&tys).try_for_each(|(d, ty)| {
+                    let inferred = inf.infer_top(&d.body)?;
+                    inf.unify(ty, &inferred, d.span)
+                });
+                for (d, ty) in defs.iter().zip(&tys) {
+                    let scheme = if res.is_ok() { inf.generalize(ty, &[]) } else { failed.insert(d.vname.as_str()); inf.unknown() };
+                    inf.globals.insert(d.vname.clone(), scheme);
+                }
+                if let Err(e) = res { errors.push(e); }
+            }
+            _ => {}
+        }
+    }
+
+    // Types in source order, leaving out the definitions which cannot be typed
+    let mut types = vec![];
+    let mut rank = 0;
+    for s in sentences {
+        match s {
+            Sentence::Let(SLet{vname, ..}) if !failed.contains(vname.as_str()) => types.push((vname.clone(), inf.globals[vname].clone())),
+            Sentence::Rec(SRec{defs, ..}) => types.extend(defs.iter()
+                .filter(|d| !failed.contains(d.vname.as_str()))
+                .map(|d| (d.vname.clone(), inf.globals[&d.vname].clone()))),
+            Sentence::Run(r) if r.term.free_vars().iter().any(|n| failed.contains(n.as_str())) => rank += 1,
+            Sentence::Run(r) => {
+                let name = r.name.clone().unwrap_or_else(|| rank.to_string());
+                rank += 1;
+                match inf.infer_top(&r.term) {
+                    Ok(ty) => types.push((name, inf.generalize(&ty, &[]))),
+                    Err(e) => errors.push(e)
+                }
+            }
+            _ => {}
+        }
+    }
+    let types = types.into_iter()
+        .map(|(name, s)| (name, inf.to_type(&s.ty, &mut HashMap::new())))
+        .collect();
+    (types, errors)
+}
+
+
+/// State of the inference.
+struct Infer {
+    /// Substitution of the type variables: `None` for an unknown type.
+    subst:Vec<Option<Ty>>,
+    /// Encodings of the literals.
+    encodings:Encodings,
+    /// Type schemes of the definitions.
+    globals:HashMap<String, Scheme>,
+    /// Type variables of the annotations of the current definition, by name.
+    annotations:HashMap<String, Ty>,
+}
+
+impl Infer {
+
+    /// New type variable.
+    fn fresh(&mut self) -> Ty {
+        self.subst.push(None);
+        Ty::Var(self.subst.len() - 1)
+    }
+
+    /// Type scheme of a definition which cannot be typed: any type.
+    fn unknown(&mut self) -> Scheme {
+        match self.fresh() {
+            Ty::Var(v) => Scheme{vars:vec![v], ty:Ty::Var(v)},
+            _ => unreachable!()
+        }
+    }
+
+    /// Infer the type of a top-level term.
+    fn infer_top(&mut self, term:&Term) -> Result<Ty, EoleError> {
+        self.annotations.clear();
+        self.infer(term, &mut vec![])
+    }
+
+    /// Infer the type of a term, `locals` being the types of the enclosing binders (the last one being the closest).
+    fn infer(&mut self, term:&Term, locals:&mut Vec<(String, Scheme)>) -> Result<Ty, EoleError> {
+        match term {
+            Sym{vname, ..} => {
+                let scheme = match locals.iter().rev().find(|(n, _)| n == vname) {
+                    Some((_, s)) => s.clone(),
+                    // Unknown symbols are reported by the scope check
+                    None => self.globals.get(vname).cloned().unwrap_or_else(|| self.unknown())
+                };
+                Ok(self.instantiate(&scheme))
+            }
+
+            Lambda{vname, body, ..} => {
+                let arg = self.fresh();
+                locals.push((vname.clone(), Scheme::mono(arg.clone())));
+                let res = self.infer(body, locals);
+                locals.pop();
+                Ok(Ty::Arrow(Box::new(arg), Box::new(res?)))
+            }
+
+            // Local definition: 'with x = arg, body' is polymorphic in 'x'
+            App{fun, arg, ..} if matches!(**fun, Lambda{..}) => {
+                let (vname, body) = match &**fun { Lambda{vname, body, ..} => (vname, body), _ => unreachable!() };
+                let arg = self.infer(arg, locals)?;
+                let scheme = self.generalize(&arg, locals);
+                locals.push((vname.clone(), scheme));
+                let res = self.infer(body, locals);
+                locals.pop();
+                res
+            }
+
+            App{fun, arg, span} => {
+                let fun = self.infer(fun, locals)?;
+                let arg = self.infer(arg, locals)?;
+                let res = self.fresh();
+                self.unify(&fun, &Ty::Arrow(Box::new(arg), Box::new(res.clone())), *span)?;
+                Ok(res)
+            }
+
+            // Literals: type of their encoding
+            Num{value, span} => {
+                let numeral = encoding::numeral(self.encodings.numerals, *value, *span);
+                self.infer(&numeral, &mut vec![])
+            }
+            List{items, span} => {
+                let list = encoding::list(self.encodings.lists, items.clone(), *span);
+                self.infer(&list, locals)
+            }
+            Str{value, span} => {
+                let string = encoding::string(self.encodings, value, *span);
+                self.infer(&string, &mut vec![])
+            }
+
+            Rec{defs, body, span} => {
+                let depth = locals.len();
+                let tys:Vec<Ty> = defs.iter().map(|_| self.fresh()).collect();
+                locals.extend(defs.iter().zip(&tys).map(|((vname, _), ty)| (vname.clone(), Scheme::mono(ty.clone()))));
+                for ((_, def), ty) in defs.iter().zip(&tys) {
+                    let inferred = self.infer(def, locals)?;
+                    self.unify(ty, &inferred, *span)?;
+                }
+                locals.truncate(depth);
+                for ((vname, _), ty) in defs.iter().zip(&tys) {
+                    let scheme = self.generalize(ty, locals);
+                    locals.push((vname.clone(), scheme));
+                }
+                let res = self.infer(body, locals);
+                locals.truncate(depth);
+                res
+            }
+
+            Annot{term, ty, span} => {
+                let inferred = self.infer(term, locals)?;
+                let annotation = self.annotation(ty);
+                self.unify(&annotation, &inferred, *span)?;
+                Ok(inferred)
+            }
+
+            Case{..} => unreachable!("case expressions are desugared before the type inference, see data::desugar"),
+            Infix{span, ..} => import::unresolved(*span),
+        }
+    }
+
+    /// Type of an annotation. The type variables are shared by all the annotations of the definition.
+    fn annotation(&mut self, ty:&Type) -> Ty {
+        match ty {
+            Type::Var(v) => match self.annotations.get(v) {
+                Some(t) => t.clone(),
+                None => {
+                    let t = self.fresh();
+                    self.annotations.insert(v.clone(), t.clone());
+                    t
+                }
+            },
+            Type::Arrow(a, b) => Ty::Arrow(Box::new(self.annotation(a)), Box::new(self.annotation(b))),
+            Type::Hole => self.fresh(),
+        }
+    }
+
+    /// Follow the substitution of a variable.
+    fn shallow(&self, ty:&Ty) -> Ty {
+        match ty {
+            Ty::Var(v) => match &self.subst[*v] {
+                Some(t) => self.shallow(t),
+                None => ty.clone()
+            },
+            _ => ty.clone()
+        }
+    }
+
+    /// Apply the substitution to a type.
+    fn resolve(&self, ty:&Ty) -> Ty {
+        match self.shallow(ty) {
+            Ty::Arrow(a, b) => Ty::Arrow(Box::new(self.resolve(&a)), Box::new(self.resolve(&b))),
+            t => t
+        }
+    }
+
+    /// Check if the variable `v` occurs in a type.
+    fn occurs(&self, v:usize, ty:&Ty) -> bool {
+        match self.shallow(ty) {
+            Ty::Var(w) => v == w,
+            Ty::Arrow(a, b) => self.occurs(v, &a) || self.occurs(v, &b)
+        }
+    }
+
+    /// Unify two types, reporting an infinite type at `span`.
+    fn unify(&mut self, a:&Ty, b:&Ty, span:Span) -> Result<(), EoleError> {
+        match (self.shallow(a), self.shallow(b)) {
+            (Ty::Var(x), Ty::Var(y)) if x == y => Ok(()),
+            (Ty::Var(x), t) | (t, Ty::Var(x)) => {
+                if self.occurs(x, &t) {
+                    let mut names = HashMap::new();
+                    let var = self.to_type(&Ty::Var(x), &mut names).to_string();
+                    let ty = self.to_type(&t, &mut names).to_string();
+                    return Err(EoleError::new(ErrorKind::InfiniteType{var, ty}, span));
+                }
+                self.subst[x] = Some(t);
+                Ok(())
+            }
+            (Ty::Arrow(a1, r1), Ty::Arrow(a2, r2)) => {
+                self.unify(&a1, &a2, span)?;
+                self.unify(&r1, &r2, span)
+            }
+        }
+    }
+
+    /// Free type variables of a type, in order of appearance.
+    fn free_vars(&self, ty:&Ty, res:&mut Vec<usize>) {
+        match self.shallow(ty) {
+            Ty::Var(v) => if !res.contains(&v) { res.push(v); },
+            Ty::Arrow(a, b) => {
+                self.free_vars(&a, res);
+                self.free_vars(&b, res);
+            }
+        }
+    }
+
+    /// Generalize a type over the variables which are not free in the enclosing binders.
+    fn generalize(&self, ty:&Ty, locals:&[(String, Scheme)]) -> Scheme {
+        let mut bound = vec![];
+        for (_, s) in locals {
+            let mut vars = vec![];
+            self.free_vars(&s.ty, &mut vars);
+            bound.extend(vars.into_iter().filter(|v| !s.vars.contains(v)));
+        }
+        let mut vars = vec![];
+        self.free_vars(ty, &mut vars);
+        vars.retain(|v| !bound.contains(v));
+        Scheme{vars, ty:self.resolve(ty)}
+    }
+
+    /// Instantiate a type scheme with fresh variables.
+    fn instantiate(&mut self, scheme:&Scheme) -> Ty {
+        let fresh:HashMap<usize, Ty> = scheme.vars.iter().map(|v| (*v, self.fresh())).collect();
+        self.replace(&scheme.ty, &fresh)
+    }
+
+    fn replace(&self, ty:&Ty, fresh:&HashMap<usize, Ty>) -> Ty {
+        match self.shallow(ty) {
+            Ty::Var(v) => fresh.get(&v).cloned().unwrap_or(Ty::Var(v)),
+            Ty::Arrow(a, b) => Ty::Arrow(Box::new(self.replace(&a, fresh)), Box::new(self.replace(&b, fresh)))
+        }
+    }
+
+    /// Convert a type to be printed. The variables are named `a`, `b`, ... in order of appearance, recorded in `names`.
+    fn to_type(&self, ty:&Ty, names:&mut HashMap<usize, String>) -> Type {
+        match self.shallow(ty) {
+            Ty::Var(v) => {
+                let next = names.len();
+                Type::Var(names.entry(v).or_insert_with(|| var_name(next)).clone())
+            }
+            Ty::Arrow(a, b) => Type::Arrow(Box::new(self.to_type(&a, names)), Box::new(self.to_type(&b, names)))
+        }
+    }
+}
+
+
+/// Check if a definition uses a definition which cannot be typed.
+fn uses_failed(d:&Sentence, failed:&HashSet<&str>) -> bool {
+    bodies(d).iter().any(|(_, body)| body.free_vars().iter().any(|n| failed.contains(n.as_str())))
+}
+
+/// Names and bodies of a definition.
+fn bodies(d:&Sentence) -> Vec<(&str, &Term)> {
+    match d {
+        Sentence::Let(SLet{vname, body, ..}) => vec![(vname.as_str(), &**body)],
+        Sentence::Rec(SRec{defs, ..}) => defs.iter().map(|d| (d.vname.as_str(), &*d.body)).collect(),
+        _ => vec![]
+    }
+}
+
+
+/// Name of the `i`-th type variable: `a`, `b`, ..., `z`, `a1`, `b1`, ...
+pub(crate) fn var_name(i:usize) -> String {
+    let letter = (b'a' + (i % 26) as u8) as char;
+    if i < 26 { letter.to_string() } else { format!("{}{}", letter, i / 26) }
+}
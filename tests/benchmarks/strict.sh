@@ -0,0 +1,28 @@
+#!/bin/bash
+# Check that the strict strategies are baselines for the default one:
+# on the factorial and fibonacci benchmarks, they must perform at least as many interactions as the full reducer.
+# The garbage collector is disabled: it erases the parts of the net which are not used any more,
+# earlier when the arguments are reduced first, which saves the interactions copying them.
+EOLE="$(dirname "$0")/../../target/release/eole"
+DIR="$(dirname "$0")"
+STATUS=0
+
+# Number of interactions of a benchmark with a strategy
+function interactions {
+  "$EOLE" "$1" -s "$2" -m none -v | grep "^Reductions" | awk '{print $3}'
+}
+
+for file in "$DIR"/fact0*.eole "$DIR"/fibo0*.eole "$DIR"/fibo1[03].eole; do
+  full=$(interactions "$file" full)
+  line="$(basename "$file"): full $full"
+  for strategy in cbv applicative; do
+    n=$(interactions "$file" $strategy)
+    line="$line, $strategy $n"
+    if [ -z "$n" ] || [ "$n" -lt "$full" ]; then
+      echo "$(basename "$file"): $strategy performs ${n:-no} interactions, fewer than the $full of full"
+      STATUS=1
+    fi
+  done
+  echo "$line"
+done
+exit $STATUS